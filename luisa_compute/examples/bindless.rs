@@ -1,6 +1,5 @@
 use std::path::PathBuf;
 
-use image::io::Reader as ImageReader;
 use luisa::*;
 use luisa_compute as luisa;
 fn main() {
@@ -22,28 +21,9 @@ fn main() {
     file_path.push("logo.png");
     let mip_levels = 4;
 
-    let img = {
-        let img = ImageReader::open(file_path).unwrap().decode().unwrap();
-        let tex: Tex2d<Float4> = device
-            .create_tex2d(PixelStorage::Float4, img.width(), img.height(), mip_levels)
-            .unwrap();
-        for i in 0..mip_levels {
-            let mip = img
-                .resize(
-                    img.width() >> i,
-                    img.height() >> i,
-                    image::imageops::FilterType::Triangle,
-                )
-                .to_rgba32f();
-            let buffer = mip
-                .pixels()
-                .map(|p| Float4::new(p[0] as f32, p[1] as f32, p[2] as f32, p[3] as f32))
-                .collect::<Vec<_>>();
-
-            tex.view(i).copy_from(&buffer);
-        }
-        tex
-    };
+    let img: Tex2d<Float4> =
+        Tex2d::from_image_file(&device, file_path, PixelStorage::Byte4, mip_levels).unwrap();
+    img.generate_mipmaps().unwrap();
     let bindless = device.create_bindless_array(2).unwrap();
     bindless.emplace_buffer_async(0, &x);
     bindless.emplace_buffer_async(1, &y);
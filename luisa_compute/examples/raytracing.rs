@@ -6,11 +6,11 @@ fn main() {
     init();
     init_logger();
     let device = create_device("cpu").unwrap();
-    let vbuffer: Buffer<Float3> = device
+    let vbuffer: Buffer<PackedFloat3> = device
         .create_buffer_from_slice(&[
-            Float3::new(-0.5, -0.5, 0.0),
-            Float3::new(0.5, 0.0, 0.0),
-            Float3::new(0.0, 0.5, 0.0),
+            PackedFloat3::new(-0.5, -0.5, 0.0),
+            PackedFloat3::new(0.5, 0.0, 0.0),
+            PackedFloat3::new(0.0, 0.5, 0.0),
         ])
         .unwrap();
     let tbuffer: Buffer<Uint3> = device
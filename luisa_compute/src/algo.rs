@@ -0,0 +1,712 @@
+// Device-wide parallel primitives built on the kernel language.
+// Every primitive compiles its kernels once, when it is created, for one element
+// type and one operator; keep the object around and reuse it across calls.
+// The kernel language has no shared memory, so each thread folds a fixed chunk
+// of elements sequentially and the per-chunk carries are processed recursively.
+use crate::*;
+
+pub(crate) const ITEMS_PER_THREAD: u32 = 128;
+
+pub(crate) fn n_chunks(n: usize) -> usize {
+    n.div_ceil(ITEMS_PER_THREAD as usize)
+}
+// Calls `f` for every index of the chunk owned by the calling thread
+pub(crate) fn for_each_in_chunk(n: Expr<u32>, f: impl Fn(Expr<u32>)) {
+    let begin = dispatch_id().x() * ITEMS_PER_THREAD;
+    let end = (begin + ITEMS_PER_THREAD).min(n);
+    let i = var!(u32, begin);
+    while_!(i.load().cmplt(end), {
+        f(i.load());
+        i.store(i.load() + 1u32);
+    });
+}
+
+type ChunkKernel<T> = Kernel<(Buffer<T>, Buffer<T>, Buffer<u32>)>;
+type ScanKernel<T> = Kernel<(Buffer<T>, Buffer<T>, Buffer<T>, Buffer<u32>)>;
+type SegmentedScanKernel<T> = Kernel<(
+    Buffer<T>,
+    Buffer<bool>,
+    Buffer<T>,
+    Buffer<T>,
+    Buffer<bool>,
+    Buffer<u32>,
+)>;
+type SegmentedFixupKernel<T> = Kernel<(Buffer<T>, Buffer<bool>, Buffer<T>, Buffer<u32>)>;
+
+// `op` must be associative, `identity` must be its identity element
+pub struct Reduce<T: Value + 'static> {
+    device: Device,
+    identity: T,
+    kernel: ChunkKernel<T>,
+}
+impl<T: Value + 'static> Reduce<T> {
+    pub fn new(
+        device: &Device,
+        identity: T,
+        op: impl Fn(Expr<T>, Expr<T>) -> Expr<T>,
+    ) -> backend::Result<Self> {
+        let kernel = device.create_kernel::<(Buffer<T>, Buffer<T>, Buffer<u32>)>(
+            &|input, output, params| {
+                let acc = var!(T, const_(identity));
+                for_each_in_chunk(params.read(0u32), |i| {
+                    acc.store(op(acc.load(), input.read(i)));
+                });
+                output.write(dispatch_id().x(), acc.load());
+            },
+        )?;
+        Ok(Self {
+            device: device.clone(),
+            identity,
+            kernel,
+        })
+    }
+    // Reduces `input` into the single element view `output`; an empty input yields the identity
+    pub fn reduce_to(&self, input: &BufferView<T>, output: &BufferView<T>) -> backend::Result<()> {
+        assert_eq!(output.len(), 1);
+        if input.is_empty() {
+            output.fill(self.identity);
+            return Ok(());
+        }
+        let mut len = input.len();
+        let mut partial: Option<Buffer<T>> = None;
+        loop {
+            let m = n_chunks(len);
+            let params = self.device.create_buffer_from_slice(&[len as u32])?;
+            let next = self.device.create_buffer::<T>(m)?;
+            match &partial {
+                Some(src) => self
+                    .kernel
+                    .dispatch([m as u32, 1, 1], src, &next, &params)?,
+                None => self
+                    .kernel
+                    .dispatch([m as u32, 1, 1], input, &next, &params)?,
+            }
+            len = m;
+            partial = Some(next);
+            if m == 1 {
+                break;
+            }
+        }
+        partial.unwrap().view(..).copy_to_buffer(output);
+        Ok(())
+    }
+    pub fn reduce(&self, input: &BufferView<T>) -> backend::Result<T> {
+        let out = self.device.create_buffer::<T>(1)?;
+        self.reduce_to(input, &out.view(..))?;
+        Ok(out.view(..).copy_to_vec()[0])
+    }
+}
+
+// Inclusive and exclusive prefix scans; `op` must be associative but not
+// necessarily commutative, `identity` must be its identity element
+pub struct Scan<T: Value + 'static> {
+    device: Device,
+    local_scan: ScanKernel<T>,
+    add_offsets: ChunkKernel<T>,
+}
+impl<T: Value + 'static> Scan<T> {
+    pub fn new(
+        device: &Device,
+        identity: T,
+        op: impl Fn(Expr<T>, Expr<T>) -> Expr<T>,
+    ) -> backend::Result<Self> {
+        // params: [n, inclusive]
+        let local_scan = device.create_kernel::<(Buffer<T>, Buffer<T>, Buffer<T>, Buffer<u32>)>(
+            &|input, output, chunk_sums, params| {
+                let inclusive = params.read(1u32).cmpne(0u32);
+                let acc = var!(T, const_(identity));
+                for_each_in_chunk(params.read(0u32), |i| {
+                    let prev = acc.load();
+                    let next = op(prev, input.read(i));
+                    output.write(i, select(inclusive, next, prev));
+                    acc.store(next);
+                });
+                chunk_sums.write(dispatch_id().x(), acc.load());
+            },
+        )?;
+        let add_offsets = device.create_kernel::<(Buffer<T>, Buffer<T>, Buffer<u32>)>(
+            &|output, offsets, params| {
+                let offset = offsets.read(dispatch_id().x());
+                for_each_in_chunk(params.read(0u32), |i| {
+                    output.write(i, op(offset, output.read(i)));
+                });
+            },
+        )?;
+        Ok(Self {
+            device: device.clone(),
+            local_scan,
+            add_offsets,
+        })
+    }
+    fn scan(
+        &self,
+        input: &BufferView<T>,
+        output: &BufferView<T>,
+        inclusive: bool,
+    ) -> backend::Result<()> {
+        assert_eq!(input.len(), output.len());
+        if input.is_empty() {
+            return Ok(());
+        }
+        let n = input.len();
+        let m = n_chunks(n);
+        let params = self
+            .device
+            .create_buffer_from_slice(&[n as u32, inclusive as u32])?;
+        let chunk_sums = self.device.create_buffer::<T>(m)?;
+        self.local_scan
+            .dispatch([m as u32, 1, 1], input, output, &chunk_sums, &params)?;
+        if m > 1 {
+            let offsets = self.device.create_buffer::<T>(m)?;
+            self.scan(&chunk_sums.view(..), &offsets.view(..), false)?;
+            self.add_offsets
+                .dispatch([m as u32, 1, 1], output, &offsets, &params)?;
+        }
+        Ok(())
+    }
+    // output[i] = input[0] op ... op input[i]
+    pub fn inclusive_scan(
+        &self,
+        input: &BufferView<T>,
+        output: &BufferView<T>,
+    ) -> backend::Result<()> {
+        self.scan(input, output, true)
+    }
+    // output[i] = identity op input[0] op ... op input[i - 1]
+    pub fn exclusive_scan(
+        &self,
+        input: &BufferView<T>,
+        output: &BufferView<T>,
+    ) -> backend::Result<()> {
+        self.scan(input, output, false)
+    }
+}
+
+// Scans that restart at every element whose head flag is set
+pub struct SegmentedScan<T: Value + 'static> {
+    device: Device,
+    local_scan: SegmentedScanKernel<T>,
+    fixup: SegmentedFixupKernel<T>,
+}
+impl<T: Value + 'static> SegmentedScan<T> {
+    pub fn new(
+        device: &Device,
+        identity: T,
+        op: impl Fn(Expr<T>, Expr<T>) -> Expr<T>,
+    ) -> backend::Result<Self> {
+        // params: [n, inclusive]
+        let local_scan =
+            device.create_kernel::<(
+                Buffer<T>,
+                Buffer<bool>,
+                Buffer<T>,
+                Buffer<T>,
+                Buffer<bool>,
+                Buffer<u32>,
+            )>(&|input, heads, output, chunk_sums, chunk_heads, params| {
+                let inclusive = params.read(1u32).cmpne(0u32);
+                let acc = var!(T, const_(identity));
+                let any_head = var!(bool, false);
+                for_each_in_chunk(params.read(0u32), |i| {
+                    let head = heads.read(i);
+                    let prev = select(head, const_(identity), acc.load());
+                    let next = op(prev, input.read(i));
+                    output.write(i, select(inclusive, next, prev));
+                    acc.store(next);
+                    any_head.store(any_head.load() | head);
+                });
+                chunk_sums.write(dispatch_id().x(), acc.load());
+                chunk_heads.write(dispatch_id().x(), any_head.load());
+            })?;
+        // applies the carry to the elements before the first head of chunks 1..
+        let fixup = device.create_kernel::<(Buffer<T>, Buffer<bool>, Buffer<T>, Buffer<u32>)>(
+            &|output, heads, carries, params| {
+                let offset = carries.read(dispatch_id().x());
+                let n = params.read(0u32);
+                let begin = (dispatch_id().x() + 1u32) * ITEMS_PER_THREAD;
+                let end = (begin + ITEMS_PER_THREAD).min(n);
+                let i = var!(u32, begin);
+                while_!(i.load().cmplt(end) & !heads.read(i.load().min(n - 1u32)), {
+                    output.write(i.load(), op(offset, output.read(i.load())));
+                    i.store(i.load() + 1u32);
+                });
+            },
+        )?;
+        Ok(Self {
+            device: device.clone(),
+            local_scan,
+            fixup,
+        })
+    }
+    fn scan(
+        &self,
+        input: &BufferView<T>,
+        heads: &BufferView<bool>,
+        output: &BufferView<T>,
+        inclusive: bool,
+    ) -> backend::Result<()> {
+        assert_eq!(input.len(), output.len());
+        assert_eq!(input.len(), heads.len());
+        if input.is_empty() {
+            return Ok(());
+        }
+        let n = input.len();
+        let m = n_chunks(n);
+        let params = self
+            .device
+            .create_buffer_from_slice(&[n as u32, inclusive as u32])?;
+        let chunk_sums = self.device.create_buffer::<T>(m)?;
+        let chunk_heads = self.device.create_buffer::<bool>(m)?;
+        self.local_scan.dispatch(
+            [m as u32, 1, 1],
+            input,
+            heads,
+            output,
+            &chunk_sums,
+            &chunk_heads,
+            &params,
+        )?;
+        if m > 1 {
+            // A chunk containing a head restarts its carry out exactly like a head element,
+            // so the carry into chunk c is the inclusive scan of the chunk sums at c - 1
+            let carries = self.device.create_buffer::<T>(m)?;
+            self.scan(
+                &chunk_sums.view(..),
+                &chunk_heads.view(..),
+                &carries.view(..),
+                true,
+            )?;
+            self.fixup
+                .dispatch([m as u32 - 1, 1, 1], output, heads, &carries, &params)?;
+        }
+        Ok(())
+    }
+    pub fn inclusive_scan(
+        &self,
+        input: &BufferView<T>,
+        heads: &BufferView<bool>,
+        output: &BufferView<T>,
+    ) -> backend::Result<()> {
+        self.scan(input, heads, output, true)
+    }
+    pub fn exclusive_scan(
+        &self,
+        input: &BufferView<T>,
+        heads: &BufferView<bool>,
+        output: &BufferView<T>,
+    ) -> backend::Result<()> {
+        self.scan(input, heads, output, false)
+    }
+}
+
+pub(crate) const RADIX_BITS: u32 = 4;
+pub(crate) const RADIX: u32 = 1 << RADIX_BITS;
+
+// Keys accepted by the radix sort
+pub trait RadixKey: Value + 'static {
+    const BITS: u32;
+    // (key >> shift) & mask
+    fn digit(key: Expr<Self>, shift: Expr<u32>, mask: Expr<u32>) -> Expr<u32>;
+}
+impl RadixKey for u32 {
+    const BITS: u32 = 32;
+    fn digit(key: Expr<u32>, shift: Expr<u32>, mask: Expr<u32>) -> Expr<u32> {
+        (key >> shift) & mask
+    }
+}
+impl RadixKey for u64 {
+    const BITS: u32 = 64;
+    fn digit(key: Expr<u64>, shift: Expr<u32>, mask: Expr<u32>) -> Expr<u32> {
+        (key >> shift.ulong()).uint() & mask
+    }
+}
+
+type RadixCountKernel<K> = Kernel<(Buffer<K>, Buffer<u32>, Buffer<u32>)>;
+type RadixScatterKernel<K> = Kernel<(Buffer<K>, Buffer<K>, Buffer<u32>, Buffer<u32>)>;
+type RadixScatterPairsKernel<K, V> = Kernel<(
+    Buffer<K>,
+    Buffer<V>,
+    Buffer<K>,
+    Buffer<V>,
+    Buffer<u32>,
+    Buffer<u32>,
+)>;
+
+// params: [n, m, shift, mask], the histogram is laid out digit-major so that
+// its exclusive scan yields the first output slot of every (digit, chunk) pair
+fn radix_params(
+    device: &Device,
+    n: usize,
+    shift: u32,
+    end_bit: u32,
+) -> backend::Result<Buffer<u32>> {
+    let bits = RADIX_BITS.min(end_bit - shift);
+    device.create_buffer_from_slice(&[n as u32, n_chunks(n) as u32, shift, (1u32 << bits) - 1])
+}
+
+// Stable LSD radix sort over the key bits in `bits`; keys compare as
+// (key >> bits.start) & ((1 << bits.len()) - 1), the rest of the key is ignored
+pub struct RadixSort<K: RadixKey> {
+    device: Device,
+    scan: Scan<u32>,
+    count: RadixCountKernel<K>,
+    scatter: RadixScatterKernel<K>,
+}
+impl<K: RadixKey> RadixSort<K> {
+    pub fn new(device: &Device) -> backend::Result<Self> {
+        let count = device.create_kernel::<(Buffer<K>, Buffer<u32>, Buffer<u32>)>(
+            &|keys, hist, params| {
+                let m = params.read(1u32);
+                let shift = params.read(2u32);
+                let mask = params.read(3u32);
+                let chunk = dispatch_id().x();
+                for d in 0..RADIX {
+                    hist.write(m * d + chunk, 0u32);
+                }
+                for_each_in_chunk(params.read(0u32), |i| {
+                    let slot = K::digit(keys.read(i), shift, mask) * m + chunk;
+                    hist.write(slot, hist.read(slot) + 1u32);
+                });
+            },
+        )?;
+        let scatter = device.create_kernel::<(Buffer<K>, Buffer<K>, Buffer<u32>, Buffer<u32>)>(
+            &|src, dst, offsets, params| {
+                let m = params.read(1u32);
+                let shift = params.read(2u32);
+                let mask = params.read(3u32);
+                let chunk = dispatch_id().x();
+                for_each_in_chunk(params.read(0u32), |i| {
+                    let key = src.read(i);
+                    let slot = K::digit(key, shift, mask) * m + chunk;
+                    let pos = offsets.read(slot);
+                    dst.write(pos, key);
+                    offsets.write(slot, pos + 1u32);
+                });
+            },
+        )?;
+        Ok(Self {
+            device: device.clone(),
+            scan: Scan::new(device, 0u32, |a, b| a + b)?,
+            count,
+            scatter,
+        })
+    }
+    fn passes(&self, bits: &std::ops::Range<u32>) -> impl Iterator<Item = u32> {
+        assert!(bits.start <= bits.end && bits.end <= K::BITS);
+        (bits.start..bits.end).step_by(RADIX_BITS as usize)
+    }
+    // Computes the scatter offsets of one pass
+    fn offsets(
+        &self,
+        keys: &BufferView<K>,
+        params: &Buffer<u32>,
+        hist: &Buffer<u32>,
+        offsets: &Buffer<u32>,
+    ) -> backend::Result<()> {
+        let m = n_chunks(keys.len());
+        self.count.dispatch([m as u32, 1, 1], keys, hist, params)?;
+        self.scan.exclusive_scan(&hist.view(..), &offsets.view(..))
+    }
+    pub fn sort(&self, keys: &BufferView<K>, bits: std::ops::Range<u32>) -> backend::Result<()> {
+        let n = keys.len();
+        if n == 0 {
+            return Ok(());
+        }
+        let m = n_chunks(n);
+        let hist = self.device.create_buffer::<u32>(m * RADIX as usize)?;
+        let offsets = self.device.create_buffer::<u32>(m * RADIX as usize)?;
+        let tmp = self.device.create_buffer::<K>(n)?;
+        let tmp = tmp.view(..);
+        let mut in_tmp = false;
+        for shift in self.passes(&bits) {
+            let params = radix_params(&self.device, n, shift, bits.end)?;
+            let (src, dst) = if in_tmp { (&tmp, keys) } else { (keys, &tmp) };
+            self.offsets(src, &params, &hist, &offsets)?;
+            self.scatter
+                .dispatch([m as u32, 1, 1], src, dst, &offsets, &params)?;
+            in_tmp = !in_tmp;
+        }
+        if in_tmp {
+            tmp.copy_to_buffer(keys);
+        }
+        Ok(())
+    }
+    pub fn sort_all(&self, keys: &BufferView<K>) -> backend::Result<()> {
+        self.sort(keys, 0..K::BITS)
+    }
+}
+
+// Stable radix sort of (key, value) pairs by key, see `RadixSort`
+pub struct RadixSortByKey<K: RadixKey, V: Value + 'static> {
+    keys: RadixSort<K>,
+    scatter: RadixScatterPairsKernel<K, V>,
+}
+impl<K: RadixKey, V: Value + 'static> RadixSortByKey<K, V> {
+    pub fn new(device: &Device) -> backend::Result<Self> {
+        let scatter = device.create_kernel::<(
+            Buffer<K>,
+            Buffer<V>,
+            Buffer<K>,
+            Buffer<V>,
+            Buffer<u32>,
+            Buffer<u32>,
+        )>(&|src_keys,
+                                          src_values,
+                                          dst_keys,
+                                          dst_values,
+                                          offsets,
+                                          params| {
+            let m = params.read(1u32);
+            let shift = params.read(2u32);
+            let mask = params.read(3u32);
+            let chunk = dispatch_id().x();
+            for_each_in_chunk(params.read(0u32), |i| {
+                let key = src_keys.read(i);
+                let slot = K::digit(key, shift, mask) * m + chunk;
+                let pos = offsets.read(slot);
+                dst_keys.write(pos, key);
+                dst_values.write(pos, src_values.read(i));
+                offsets.write(slot, pos + 1u32);
+            });
+        })?;
+        Ok(Self {
+            keys: RadixSort::new(device)?,
+            scatter,
+        })
+    }
+    pub fn sort(
+        &self,
+        keys: &BufferView<K>,
+        values: &BufferView<V>,
+        bits: std::ops::Range<u32>,
+    ) -> backend::Result<()> {
+        assert_eq!(keys.len(), values.len());
+        let n = keys.len();
+        if n == 0 {
+            return Ok(());
+        }
+        let device = &self.keys.device;
+        let m = n_chunks(n);
+        let hist = device.create_buffer::<u32>(m * RADIX as usize)?;
+        let offsets = device.create_buffer::<u32>(m * RADIX as usize)?;
+        let tmp_keys = device.create_buffer::<K>(n)?;
+        let tmp_values = device.create_buffer::<V>(n)?;
+        let tmp_keys = tmp_keys.view(..);
+        let tmp_values = tmp_values.view(..);
+        let mut in_tmp = false;
+        for shift in self.keys.passes(&bits) {
+            let params = radix_params(device, n, shift, bits.end)?;
+            let (src_keys, src_values, dst_keys, dst_values) = if in_tmp {
+                (&tmp_keys, &tmp_values, keys, values)
+            } else {
+                (keys, values, &tmp_keys, &tmp_values)
+            };
+            self.keys.offsets(src_keys, &params, &hist, &offsets)?;
+            self.scatter.dispatch(
+                [m as u32, 1, 1],
+                src_keys,
+                src_values,
+                dst_keys,
+                dst_values,
+                &offsets,
+                &params,
+            )?;
+            in_tmp = !in_tmp;
+        }
+        if in_tmp {
+            tmp_keys.copy_to_buffer(keys);
+            tmp_values.copy_to_buffer(values);
+        }
+        Ok(())
+    }
+    pub fn sort_all(&self, keys: &BufferView<K>, values: &BufferView<V>) -> backend::Result<()> {
+        self.sort(keys, values, 0..K::BITS)
+    }
+}
+
+type CompactFlagKernel<T> = Kernel<(Buffer<T>, Buffer<u32>, Buffer<u32>)>;
+type CompactScatterKernel<T> =
+    Kernel<(Buffer<T>, Buffer<u32>, Buffer<T>, Buffer<u32>, Buffer<u32>)>;
+
+// Stable stream compaction and partition by a predicate written in the kernel language.
+// The number of selected elements is returned to the host and also written into
+// `count[0]`, so that later kernels can size their work without a readback.
+pub struct Compact<T: Value + 'static> {
+    device: Device,
+    scan: Scan<u32>,
+    flag: CompactFlagKernel<T>,
+    scatter: CompactScatterKernel<T>,
+}
+impl<T: Value + 'static> Compact<T> {
+    pub fn new(
+        device: &Device,
+        predicate: impl Fn(Expr<T>) -> Expr<bool>,
+    ) -> backend::Result<Self> {
+        Self::with_flags(device, |input, i| predicate(input.read(i)))
+    }
+    // `flag` decides from the whole input whether element i is kept
+    pub(crate) fn with_flags(
+        device: &Device,
+        flag: impl Fn(&BufferVar<T>, Expr<u32>) -> Expr<bool>,
+    ) -> backend::Result<Self> {
+        let flag = device.create_kernel::<(Buffer<T>, Buffer<u32>, Buffer<u32>)>(
+            &|input, flags, params| {
+                let i = dispatch_id().x();
+                if_!(i.cmplt(params.read(0u32)), {
+                    flags.write(i, select(flag(&input, i), const_(1u32), const_(0u32)));
+                });
+            },
+        )?;
+        // params: [n, partition]
+        let scatter = device
+            .create_kernel::<(Buffer<T>, Buffer<u32>, Buffer<T>, Buffer<u32>, Buffer<u32>)>(
+                &|input, selected, output, count, params| {
+                    let i = dispatch_id().x();
+                    let n = params.read(0u32);
+                    if_!(i.cmplt(n), {
+                        let total = selected.read(n - 1u32);
+                        let before = select(
+                            i.cmpeq(0u32),
+                            const_(0u32),
+                            selected.read(i.max(1u32) - 1u32),
+                        );
+                        let upto = selected.read(i);
+                        if_!(upto.cmpne(before), {
+                            output.write(before, input.read(i));
+                        }, else {
+                            if_!(params.read(1u32).cmpne(0u32), {
+                                output.write(total + i - upto, input.read(i));
+                            });
+                        });
+                        if_!(i.cmpeq(n - 1u32), {
+                            count.write(0u32, total);
+                        });
+                    });
+                },
+            )?;
+        Ok(Self {
+            device: device.clone(),
+            scan: Scan::new(device, 0u32, |a, b| a + b)?,
+            flag,
+            scatter,
+        })
+    }
+    fn run(
+        &self,
+        input: &BufferView<T>,
+        output: &BufferView<T>,
+        count: &BufferView<u32>,
+        partition: bool,
+    ) -> backend::Result<u32> {
+        assert_eq!(count.len(), 1);
+        let n = input.len();
+        if n == 0 {
+            count.fill(0);
+            return Ok(0);
+        }
+        let params = self
+            .device
+            .create_buffer_from_slice(&[n as u32, partition as u32])?;
+        let flags = self.device.create_buffer::<u32>(n)?;
+        let selected = self.device.create_buffer::<u32>(n)?;
+        self.flag
+            .dispatch([n as u32, 1, 1], input, &flags, &params)?;
+        self.scan
+            .inclusive_scan(&flags.view(..), &selected.view(..))?;
+        self.scatter
+            .dispatch([n as u32, 1, 1], input, &selected, output, count, &params)?;
+        Ok(count.copy_to_vec()[0])
+    }
+    // Writes the selected elements to the front of `output`, keeping their order
+    pub fn compact(
+        &self,
+        input: &BufferView<T>,
+        output: &BufferView<T>,
+        count: &BufferView<u32>,
+    ) -> backend::Result<u32> {
+        assert!(output.len() >= input.len());
+        self.run(input, output, count, false)
+    }
+    // Like `compact`, followed by the rejected elements in their original order
+    pub fn partition(
+        &self,
+        input: &BufferView<T>,
+        output: &BufferView<T>,
+        count: &BufferView<u32>,
+    ) -> backend::Result<u32> {
+        assert_eq!(output.len(), input.len());
+        self.run(input, output, count, true)
+    }
+}
+
+// Keeps the first element of every run of equal consecutive elements
+pub struct Unique<T: Value + 'static> {
+    compact: Compact<T>,
+}
+impl<T: Value + 'static> Unique<T> {
+    pub fn new(
+        device: &Device,
+        eq: impl Fn(Expr<T>, Expr<T>) -> Expr<bool>,
+    ) -> backend::Result<Self> {
+        let compact = Compact::with_flags(device, |input, i| {
+            i.cmpeq(0u32) | !eq(input.read(i.max(1u32) - 1u32), input.read(i))
+        })?;
+        Ok(Self { compact })
+    }
+    pub fn unique(
+        &self,
+        input: &BufferView<T>,
+        output: &BufferView<T>,
+        count: &BufferView<u32>,
+    ) -> backend::Result<u32> {
+        self.compact.compact(input, output, count)
+    }
+}
+
+// Counts keys per bin; `bin` maps a key to its bin, keys mapped outside
+// the output are ignored
+pub struct Histogram<K: Value + 'static> {
+    device: Device,
+    kernel: Kernel<(Buffer<K>, Buffer<u32>, Buffer<u32>)>,
+}
+impl<K: Value + 'static> Histogram<K> {
+    pub fn new(device: &Device, bin: impl Fn(Expr<K>) -> Expr<u32>) -> backend::Result<Self> {
+        // params: [n, bins]
+        let kernel = device.create_kernel::<(Buffer<K>, Buffer<u32>, Buffer<u32>)>(
+            &|keys, bins, params| {
+                let i = dispatch_id().x();
+                if_!(i.cmplt(params.read(0u32)), {
+                    let b = bin(keys.read(i));
+                    if_!(b.cmplt(params.read(1u32)), {
+                        bins.atomic_fetch_add(b, 1u32);
+                    });
+                });
+            },
+        )?;
+        Ok(Self {
+            device: device.clone(),
+            kernel,
+        })
+    }
+    // Overwrites `bins` with the counts, the number of bins is `bins.len()`
+    pub fn histogram_to(
+        &self,
+        keys: &BufferView<K>,
+        bins: &BufferView<u32>,
+    ) -> backend::Result<()> {
+        bins.fill(0);
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let params = self
+            .device
+            .create_buffer_from_slice(&[keys.len() as u32, bins.len() as u32])?;
+        self.kernel
+            .dispatch([keys.len() as u32, 1, 1], keys, bins, &params)
+    }
+    pub fn histogram(&self, keys: &BufferView<K>, bins: usize) -> backend::Result<Vec<u32>> {
+        let out = self.device.create_buffer::<u32>(bins)?;
+        self.histogram_to(keys, &out.view(..))?;
+        Ok(out.view(..).copy_to_vec())
+    }
+}
@@ -0,0 +1,364 @@
+// Dense linear algebra: GEMM and GEMV over row-major matrices in buffers, and
+// small-matrix solves meant to be called per thread inside kernels.
+// There is no shared memory in the kernel language, so GEMM tiles in registers:
+// every thread accumulates a TILE x TILE block of the output.
+use crate::*;
+
+const TILE: u32 = 4;
+
+pub trait BlasScalar: Value + PartialEq + std::ops::AddAssign + Into<f64> + 'static {
+    const ZERO: Self;
+    const ONE: Self;
+    fn add(a: Expr<Self>, b: Expr<Self>) -> Expr<Self>;
+    fn sub(a: Expr<Self>, b: Expr<Self>) -> Expr<Self>;
+    fn mul(a: Expr<Self>, b: Expr<Self>) -> Expr<Self>;
+    fn div(a: Expr<Self>, b: Expr<Self>) -> Expr<Self>;
+}
+macro_rules! impl_blas_scalar {
+    ($t:ty) => {
+        impl BlasScalar for $t {
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+            fn add(a: Expr<Self>, b: Expr<Self>) -> Expr<Self> {
+                a + b
+            }
+            fn sub(a: Expr<Self>, b: Expr<Self>) -> Expr<Self> {
+                a - b
+            }
+            fn mul(a: Expr<Self>, b: Expr<Self>) -> Expr<Self> {
+                a * b
+            }
+            fn div(a: Expr<Self>, b: Expr<Self>) -> Expr<Self> {
+                a / b
+            }
+        }
+    };
+}
+impl_blas_scalar!(f32);
+impl_blas_scalar!(f64);
+
+// A row-major matrix in `data` whose rows start `ld` elements apart.
+// `t()` flips the transposition flag, so rows() and cols() are those of the transposed matrix.
+#[derive(Clone, Copy)]
+pub struct Matrix<'a, T: Value> {
+    data: BufferView<'a, T>,
+    rows: u32,
+    cols: u32,
+    ld: u32,
+    transposed: bool,
+}
+impl<'a, T: Value> Matrix<'a, T> {
+    pub fn new(data: BufferView<'a, T>, rows: u32, cols: u32) -> Self {
+        Self::with_ld(data, rows, cols, cols)
+    }
+    pub fn with_ld(data: BufferView<'a, T>, rows: u32, cols: u32, ld: u32) -> Self {
+        assert!(ld >= cols);
+        if rows > 0 && cols > 0 {
+            assert!(((rows - 1) * ld + cols) as usize <= data.len());
+        }
+        Self {
+            data,
+            rows,
+            cols,
+            ld,
+            transposed: false,
+        }
+    }
+    pub fn t(self) -> Self {
+        Self {
+            transposed: !self.transposed,
+            ..self
+        }
+    }
+    pub fn rows(&self) -> u32 {
+        if self.transposed {
+            self.cols
+        } else {
+            self.rows
+        }
+    }
+    pub fn cols(&self) -> u32 {
+        if self.transposed {
+            self.rows
+        } else {
+            self.cols
+        }
+    }
+    pub fn is_transposed(&self) -> bool {
+        self.transposed
+    }
+    pub fn data(&self) -> &BufferView<'a, T> {
+        &self.data
+    }
+}
+
+// Element (row, col) of op(A), where A is stored with leading dimension `ld`
+fn matrix_index(
+    transposed: Expr<bool>,
+    row: Expr<u32>,
+    col: Expr<u32>,
+    ld: Expr<u32>,
+) -> Expr<u32> {
+    select(transposed, col * ld + row, row * ld + col)
+}
+
+type MatrixKernelArgs<T> = (Buffer<T>, Buffer<T>, Buffer<T>, Buffer<T>, Buffer<u32>);
+
+pub struct Blas<T: BlasScalar> {
+    device: Device,
+    gemm: Kernel<MatrixKernelArgs<T>>,
+    gemv: Kernel<MatrixKernelArgs<T>>,
+}
+impl<T: BlasScalar> Blas<T> {
+    pub fn new(device: &Device) -> backend::Result<Self> {
+        // scalars: [alpha, beta]
+        // params: [m, n, k, lda, ldb, ldc, trans_a, trans_b, beta_is_zero]
+        let gemm = device.create_kernel::<MatrixKernelArgs<T>>(&|a, b, c, scalars, params| {
+            let m = params.read(0u32);
+            let n = params.read(1u32);
+            let k = params.read(2u32);
+            let (lda, ldb, ldc) = (params.read(3u32), params.read(4u32), params.read(5u32));
+            let trans_a = params.read(6u32).cmpne(0u32);
+            let trans_b = params.read(7u32).cmpne(0u32);
+            let row0 = dispatch_id().y() * TILE;
+            let col0 = dispatch_id().x() * TILE;
+            // out of range rows and columns are clamped for loading and skipped on store
+            let rows: Vec<_> = (0..TILE).map(|i| (row0 + i).min(m - 1u32)).collect();
+            let cols: Vec<_> = (0..TILE).map(|j| (col0 + j).min(n - 1u32)).collect();
+            let acc: Vec<_> = (0..TILE * TILE).map(|_| var!(T)).collect();
+            let p = var!(u32, 0u32);
+            while_!(p.load().cmplt(k), {
+                let a_col: Vec<_> = rows
+                    .iter()
+                    .map(|r| a.read(matrix_index(trans_a, *r, p.load(), lda)))
+                    .collect();
+                let b_row: Vec<_> = cols
+                    .iter()
+                    .map(|c| b.read(matrix_index(trans_b, p.load(), *c, ldb)))
+                    .collect();
+                for (i, a_i) in a_col.iter().enumerate() {
+                    for (j, b_j) in b_row.iter().enumerate() {
+                        let acc = acc[i * TILE as usize + j];
+                        acc.store(T::add(acc.load(), T::mul(*a_i, *b_j)));
+                    }
+                }
+                p.store(p.load() + 1u32);
+            });
+            let alpha = scalars.read(0u32);
+            let beta = scalars.read(1u32);
+            let beta_is_zero = params.read(8u32).cmpne(0u32);
+            for i in 0..TILE {
+                for j in 0..TILE {
+                    let (row, col) = (row0 + i, col0 + j);
+                    if_!(row.cmplt(m) & col.cmplt(n), {
+                        let index = row * ldc + col;
+                        let v = T::mul(alpha, acc[(i * TILE + j) as usize].load());
+                        // with beta = 0 the old contents are ignored, even if they are NaN
+                        let v = select(beta_is_zero, v, T::add(v, T::mul(beta, c.read(index))));
+                        c.write(index, v);
+                    });
+                }
+            }
+        })?;
+        // params: [m, n, lda, trans_a, beta_is_zero]
+        let gemv = device.create_kernel::<MatrixKernelArgs<T>>(&|a, x, y, scalars, params| {
+            let row = dispatch_id().x();
+            let n = params.read(1u32);
+            let lda = params.read(2u32);
+            let trans_a = params.read(3u32).cmpne(0u32);
+            let acc = var!(T);
+            let j = var!(u32, 0u32);
+            while_!(j.load().cmplt(n), {
+                let a_ij = a.read(matrix_index(trans_a, row, j.load(), lda));
+                acc.store(T::add(acc.load(), T::mul(a_ij, x.read(j.load()))));
+                j.store(j.load() + 1u32);
+            });
+            let v = T::mul(scalars.read(0u32), acc.load());
+            let beta = scalars.read(1u32);
+            let beta_is_zero = params.read(4u32).cmpne(0u32);
+            y.write(
+                row,
+                select(beta_is_zero, v, T::add(v, T::mul(beta, y.read(row)))),
+            );
+        })?;
+        Ok(Self {
+            device: device.clone(),
+            gemm,
+            gemv,
+        })
+    }
+    // c = alpha * a * b + beta * c, transposition is taken from the matrices
+    pub fn gemm(
+        &self,
+        alpha: T,
+        a: &Matrix<T>,
+        b: &Matrix<T>,
+        beta: T,
+        c: &Matrix<T>,
+    ) -> backend::Result<()> {
+        let (m, n, k) = (a.rows(), b.cols(), a.cols());
+        assert_eq!(b.rows(), k, "inner dimensions of a and b differ");
+        assert_eq!((c.rows(), c.cols()), (m, n), "c has the wrong shape");
+        assert!(!c.is_transposed(), "c cannot be transposed");
+        if m == 0 || n == 0 {
+            return Ok(());
+        }
+        let beta_is_zero = beta == T::ZERO;
+        let scalars = self.device.create_buffer_from_slice(&[alpha, beta])?;
+        let params = self.device.create_buffer_from_slice(&[
+            m,
+            n,
+            k,
+            a.ld,
+            b.ld,
+            c.ld,
+            a.transposed as u32,
+            b.transposed as u32,
+            beta_is_zero as u32,
+        ])?;
+        self.gemm.dispatch(
+            [n.div_ceil(TILE), m.div_ceil(TILE), 1],
+            &a.data,
+            &b.data,
+            &c.data,
+            &scalars,
+            &params,
+        )
+    }
+    // y = alpha * a * x + beta * y
+    pub fn gemv(
+        &self,
+        alpha: T,
+        a: &Matrix<T>,
+        x: &BufferView<T>,
+        beta: T,
+        y: &BufferView<T>,
+    ) -> backend::Result<()> {
+        let (m, n) = (a.rows(), a.cols());
+        assert_eq!(x.len(), n as usize, "x has the wrong length");
+        assert_eq!(y.len(), m as usize, "y has the wrong length");
+        if m == 0 {
+            return Ok(());
+        }
+        let beta_is_zero = beta == T::ZERO;
+        let scalars = self.device.create_buffer_from_slice(&[alpha, beta])?;
+        let params = self.device.create_buffer_from_slice(&[
+            m,
+            n,
+            a.ld,
+            a.transposed as u32,
+            beta_is_zero as u32,
+        ])?;
+        self.gemv
+            .dispatch([m, 1, 1], &a.data, x, y, &scalars, &params)
+    }
+}
+
+// Small dense solves on the calling thread; matrices are handled as arrays of rows.
+// Gaussian elimination with partial pivoting, `a` must be nonsingular
+fn lu_solve<const N: usize>(mut a: [[Expr<f32>; N]; N], mut b: [Expr<f32>; N]) -> [Expr<f32>; N] {
+    for k in 0..N {
+        // move the row with the largest |a[r][k]| to row k
+        for r in k + 1..N {
+            let swap = a[r][k].abs().cmpgt(a[k][k].abs());
+            let (row_k, row_r) = (a[k], a[r]);
+            a[k] = std::array::from_fn(|c| select(swap, row_r[c], row_k[c]));
+            a[r] = std::array::from_fn(|c| select(swap, row_k[c], row_r[c]));
+            let (b_k, b_r) = (b[k], b[r]);
+            b[k] = select(swap, b_r, b_k);
+            b[r] = select(swap, b_k, b_r);
+        }
+        let pivot = a[k];
+        for r in k + 1..N {
+            let f = a[r][k] / pivot[k];
+            for (x, p) in a[r].iter_mut().zip(pivot).skip(k) {
+                *x = *x - f * p;
+            }
+            b[r] = b[r] - f * b[k];
+        }
+    }
+    let mut x = b;
+    for k in (0..N).rev() {
+        let s = a[k]
+            .iter()
+            .zip(x)
+            .skip(k + 1)
+            .fold(b[k], |s, (a, x)| s - *a * x);
+        x[k] = s / a[k][k];
+    }
+    x
+}
+// Lower triangular l with l * l^T = a, `a` must be symmetric positive definite
+fn cholesky<const N: usize>(a: [[Expr<f32>; N]; N]) -> [[Expr<f32>; N]; N] {
+    let mut l = [[const_(0.0f32); N]; N];
+    for j in 0..N {
+        let d = (0..j).fold(a[j][j], |s, p| s - l[j][p] * l[j][p]);
+        l[j][j] = d.sqrt();
+        for i in j + 1..N {
+            let s = (0..j).fold(a[i][j], |s, p| s - l[i][p] * l[j][p]);
+            l[i][j] = s / l[j][j];
+        }
+    }
+    l
+}
+fn cholesky_solve<const N: usize>(a: [[Expr<f32>; N]; N], b: [Expr<f32>; N]) -> [Expr<f32>; N] {
+    let l = cholesky(a);
+    let mut y = b;
+    for i in 0..N {
+        y[i] = (0..i).fold(b[i], |s, p| s - l[i][p] * y[p]) / l[i][i];
+    }
+    let mut x = y;
+    for i in (0..N).rev() {
+        x[i] = (i + 1..N).fold(y[i], |s, p| s - l[p][i] * x[p]) / l[i][i];
+    }
+    x
+}
+
+fn mat3_rows(a: Expr<Mat3>) -> [[Expr<f32>; 3]; 3] {
+    let cols = [0, 1, 2].map(|j| {
+        let c = a.col(j);
+        [c.x(), c.y(), c.z()]
+    });
+    std::array::from_fn(|i| cols.map(|c| c[i]))
+}
+fn mat4_rows(a: Expr<Mat4>) -> [[Expr<f32>; 4]; 4] {
+    let cols = [0, 1, 2, 3].map(|j| {
+        let c = a.col(j);
+        [c.x(), c.y(), c.z(), c.w()]
+    });
+    std::array::from_fn(|i| cols.map(|c| c[i]))
+}
+fn mat3_from_rows(m: [[Expr<f32>; 3]; 3]) -> Expr<Mat3> {
+    let col = |j: usize| make_float3(m[0][j], m[1][j], m[2][j]);
+    Mat3Expr::new(col(0), col(1), col(2))
+}
+fn mat4_from_rows(m: [[Expr<f32>; 4]; 4]) -> Expr<Mat4> {
+    let col = |j: usize| make_float4(m[0][j], m[1][j], m[2][j], m[3][j]);
+    Mat4Expr::new(col(0), col(1), col(2), col(3))
+}
+
+// Solves a * x = b
+pub fn lu_solve3(a: Expr<Mat3>, b: Expr<Float3>) -> Expr<Float3> {
+    let x = lu_solve(mat3_rows(a), [b.x(), b.y(), b.z()]);
+    make_float3(x[0], x[1], x[2])
+}
+pub fn lu_solve4(a: Expr<Mat4>, b: Expr<Float4>) -> Expr<Float4> {
+    let x = lu_solve(mat4_rows(a), [b.x(), b.y(), b.z(), b.w()]);
+    make_float4(x[0], x[1], x[2], x[3])
+}
+// Lower triangular factor of a symmetric positive definite matrix
+pub fn cholesky3(a: Expr<Mat3>) -> Expr<Mat3> {
+    mat3_from_rows(cholesky(mat3_rows(a)))
+}
+pub fn cholesky4(a: Expr<Mat4>) -> Expr<Mat4> {
+    mat4_from_rows(cholesky(mat4_rows(a)))
+}
+// Solves a * x = b for a symmetric positive definite `a`
+pub fn cholesky_solve3(a: Expr<Mat3>, b: Expr<Float3>) -> Expr<Float3> {
+    let x = cholesky_solve(mat3_rows(a), [b.x(), b.y(), b.z()]);
+    make_float3(x[0], x[1], x[2])
+}
+pub fn cholesky_solve4(a: Expr<Mat4>, b: Expr<Float4>) -> Expr<Float4> {
+    let x = cholesky_solve(mat4_rows(a), [b.x(), b.y(), b.z(), b.w()]);
+    make_float4(x[0], x[1], x[2], x[3])
+}
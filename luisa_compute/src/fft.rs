@@ -0,0 +1,402 @@
+// Fast Fourier transforms of complex data stored as Float2 (x = real, y = imaginary).
+// Arrays are laid out with x fastest and `shape` lists the extents starting from x.
+// Every axis is transformed by Stockham passes, one per factor of its length: radices
+// 2, 3, 4 and 5 have unrolled kernels, other prime factors go through a generic kernel
+// costing O(radix^2) per output group.
+// Forward transforms use e^(-2 pi i jk / n) and are unnormalized, inverse transforms divide by n.
+use std::f32::consts::PI;
+
+use crate::*;
+
+const UNROLLED_RADICES: [u32; 4] = [4, 2, 3, 5];
+
+fn factorize(mut n: u32) -> Vec<u32> {
+    let mut factors = vec![];
+    for radix in UNROLLED_RADICES {
+        while n % radix == 0 {
+            factors.push(radix);
+            n /= radix;
+        }
+    }
+    let mut p = 7;
+    while n > 1 {
+        while n % p == 0 {
+            factors.push(p);
+            n /= p;
+        }
+        p += 2;
+    }
+    factors
+}
+fn complex_mul(a: Expr<Float2>, b: Expr<Float2>) -> Expr<Float2> {
+    make_float2(a.x() * b.x() - a.y() * b.y(), a.x() * b.y() + a.y() * b.x())
+}
+// e^(i * angle)
+fn phasor(angle: Expr<f32>) -> Expr<Float2> {
+    make_float2(angle.cos(), angle.sin())
+}
+
+type PassKernel = Kernel<(Buffer<Float2>, Buffer<Float2>, Buffer<u32>)>;
+type CopyKernel = Kernel<(Buffer<Float2>, Buffer<Float2>, Buffer<u32>)>;
+type TexLoadKernel<T> = Kernel<(Tex2d<T>, Buffer<Float2>)>;
+type TexStoreKernel<T> = Kernel<(Buffer<Float2>, Tex2d<T>)>;
+
+// One Stockham pass over every line of one axis; the x dispatch index selects a group of
+// `radix` elements, the y index selects the line.
+// params: [n, ns, stride, inverse, normalize, radix] where ns is the product of the radices
+// of the previous passes and stride is the distance between consecutive elements of a line
+fn create_pass_kernel(device: &Device, radix: Option<u32>) -> backend::Result<PassKernel> {
+    device.create_kernel::<(Buffer<Float2>, Buffer<Float2>, Buffer<u32>)>(&|src, dst, params| {
+        let n = params.read(0u32);
+        let ns = params.read(1u32);
+        let stride = params.read(2u32);
+        let sign = select(
+            params.read(3u32).cmpne(0u32),
+            const_(1.0f32),
+            const_(-1.0f32),
+        );
+        let scale = select(
+            params.read(4u32).cmpne(0u32),
+            const_(1.0f32) / n.float(),
+            const_(1.0f32),
+        );
+        let r = match radix {
+            Some(radix) => const_(radix),
+            None => params.read(5u32),
+        };
+        let j = dispatch_id().x();
+        let line = dispatch_id().y();
+        let base = line / stride * (stride * n) + line % stride;
+        let m = n / r;
+        let k = j % ns;
+        let out = j / ns * ns * r + k;
+        let load = |s: Expr<u32>| src.read(base + (j + s * m) * stride);
+        let store = |q: Expr<u32>, v: Expr<Float2>| {
+            dst.write(base + (out + q * ns) * stride, v * scale);
+        };
+        match radix {
+            Some(radix) => {
+                // twiddle, then a DFT of size `radix` with constant roots of unity
+                let v: Vec<_> = (0..radix)
+                    .map(|s| {
+                        let x = load(const_(s));
+                        if s == 0 {
+                            x
+                        } else {
+                            let angle = sign * (2.0 * PI) * (k * s).float() / (ns * radix).float();
+                            complex_mul(x, phasor(angle))
+                        }
+                    })
+                    .collect();
+                for q in 0..radix {
+                    let sum = (1..radix).fold(v[0], |sum, s| {
+                        let t = q * s % radix;
+                        if t == 0 {
+                            sum + v[s as usize]
+                        } else {
+                            let theta = 2.0 * PI * t as f32 / radix as f32;
+                            let w = make_float2(theta.cos(), sign * theta.sin());
+                            sum + complex_mul(v[s as usize], w)
+                        }
+                    });
+                    store(const_(q), sum);
+                }
+            }
+            None => {
+                let q = var!(u32, 0u32);
+                while_!(q.load().cmplt(r), {
+                    let sum = var!(Float2);
+                    let s = var!(u32, 0u32);
+                    while_!(s.load().cmplt(r), {
+                        let t = s.load() * (k + q.load() * ns) % (ns * r);
+                        let angle = sign * (2.0 * PI) * t.float() / (ns * r).float();
+                        sum.store(sum.load() + complex_mul(load(s.load()), phasor(angle)));
+                        s.store(s.load() + 1u32);
+                    });
+                    store(q.load(), sum.load());
+                    q.store(q.load() + 1u32);
+                });
+            }
+        }
+    })
+}
+
+// Shape of the spectrum kept by the real transforms: the x extent becomes n / 2 + 1,
+// the remaining coefficients follow from Hermitian symmetry
+pub fn real_spectrum_shape(shape: &[u32]) -> Vec<u32> {
+    let mut half = shape.to_vec();
+    half[0] = shape[0] / 2 + 1;
+    half
+}
+fn check_shape(shape: &[u32]) {
+    assert!(
+        (1..=3).contains(&shape.len()),
+        "only 1D, 2D and 3D transforms are supported"
+    );
+    assert!(shape.iter().all(|s| *s > 0), "{:?} is empty", shape);
+}
+
+pub struct Fft {
+    device: Device,
+    unrolled: Vec<(u32, PassKernel)>,
+    generic: PassKernel,
+    real_to_complex: Kernel<(Buffer<f32>, Buffer<Float2>)>,
+    complex_to_real: Kernel<(Buffer<Float2>, Buffer<f32>)>,
+    // copies between full and half spectra along x, params: [width, half_width]
+    truncate: CopyKernel,
+    expand: CopyKernel,
+}
+impl Fft {
+    pub fn new(device: &Device) -> backend::Result<Self> {
+        let unrolled = UNROLLED_RADICES
+            .iter()
+            .map(|radix| Ok((*radix, create_pass_kernel(device, Some(*radix))?)))
+            .collect::<backend::Result<Vec<_>>>()?;
+        let real_to_complex =
+            device.create_kernel::<(Buffer<f32>, Buffer<Float2>)>(&|src, dst| {
+                let i = dispatch_id().x();
+                dst.write(i, make_float2(src.read(i), 0.0f32));
+            })?;
+        let complex_to_real =
+            device.create_kernel::<(Buffer<Float2>, Buffer<f32>)>(&|src, dst| {
+                let i = dispatch_id().x();
+                dst.write(i, src.read(i).x());
+            })?;
+        let truncate = device.create_kernel::<(Buffer<Float2>, Buffer<Float2>, Buffer<u32>)>(
+            &|full, half, params| {
+                let (x, row) = (dispatch_id().x(), dispatch_id().y());
+                let (w, hw) = (params.read(0u32), params.read(1u32));
+                half.write(row * hw + x, full.read(row * w + x));
+            },
+        )?;
+        // the rows must already be inverse transformed along the other axes, so every row
+        // is the spectrum of a real sequence and X[w - x] = conj(X[x])
+        let expand = device.create_kernel::<(Buffer<Float2>, Buffer<Float2>, Buffer<u32>)>(
+            &|half, full, params| {
+                let (x, row) = (dispatch_id().x(), dispatch_id().y());
+                let (w, hw) = (params.read(0u32), params.read(1u32));
+                let stored = x.cmplt(hw);
+                let v = half.read(row * hw + select(stored, x, w - x));
+                full.write(row * w + x, select(stored, v, make_float2(v.x(), -v.y())));
+            },
+        )?;
+        Ok(Self {
+            device: device.clone(),
+            unrolled,
+            generic: create_pass_kernel(device, None)?,
+            real_to_complex,
+            complex_to_real,
+            truncate,
+            expand,
+        })
+    }
+    fn transform_axis(
+        &self,
+        data: &BufferView<Float2>,
+        shape: &[u32],
+        axis: usize,
+        inverse: bool,
+    ) -> backend::Result<()> {
+        let n = shape[axis];
+        let factors = factorize(n);
+        if factors.is_empty() {
+            return Ok(());
+        }
+        let stride = shape[..axis].iter().product::<u32>();
+        let lines = shape.iter().product::<u32>() / n;
+        let tmp_buffer = self.device.create_buffer::<Float2>(data.len())?;
+        let tmp = tmp_buffer.view(..);
+        let mut ns = 1;
+        for (i, radix) in factors.iter().enumerate() {
+            let normalize = inverse && i + 1 == factors.len();
+            let params = self.device.create_buffer_from_slice(&[
+                n,
+                ns,
+                stride,
+                inverse as u32,
+                normalize as u32,
+                *radix,
+            ])?;
+            let kernel = self
+                .unrolled
+                .iter()
+                .find(|(r, _)| r == radix)
+                .map_or(&self.generic, |(_, kernel)| kernel);
+            let (src, dst) = if i % 2 == 0 {
+                (data, &tmp)
+            } else {
+                (&tmp, data)
+            };
+            kernel.dispatch([n / radix, lines, 1], src, dst, &params)?;
+            ns *= radix;
+        }
+        if factors.len() % 2 == 1 {
+            tmp.copy_to_buffer(data);
+        }
+        Ok(())
+    }
+    fn transform(
+        &self,
+        data: &BufferView<Float2>,
+        shape: &[u32],
+        inverse: bool,
+    ) -> backend::Result<()> {
+        check_shape(shape);
+        assert_eq!(data.len(), shape.iter().product::<u32>() as usize);
+        for axis in 0..shape.len() {
+            self.transform_axis(data, shape, axis, inverse)?;
+        }
+        Ok(())
+    }
+    // In place complex-to-complex transforms
+    pub fn forward(&self, data: &BufferView<Float2>, shape: &[u32]) -> backend::Result<()> {
+        self.transform(data, shape, false)
+    }
+    pub fn inverse(&self, data: &BufferView<Float2>, shape: &[u32]) -> backend::Result<()> {
+        self.transform(data, shape, true)
+    }
+    // Real-to-complex transform, `output` has real_spectrum_shape(shape)
+    pub fn forward_real(
+        &self,
+        input: &BufferView<f32>,
+        output: &BufferView<Float2>,
+        shape: &[u32],
+    ) -> backend::Result<()> {
+        check_shape(shape);
+        let half_shape = real_spectrum_shape(shape);
+        let len = shape.iter().product::<u32>();
+        assert_eq!(input.len(), len as usize);
+        assert_eq!(output.len(), half_shape.iter().product::<u32>() as usize);
+        let full = self.device.create_buffer::<Float2>(len as usize)?;
+        self.real_to_complex.dispatch([len, 1, 1], input, &full)?;
+        self.transform_axis(&full.view(..), shape, 0, false)?;
+        let rows = len / shape[0];
+        let params = self
+            .device
+            .create_buffer_from_slice(&[shape[0], half_shape[0]])?;
+        self.truncate
+            .dispatch([half_shape[0], rows, 1], &full, output, &params)?;
+        for axis in 1..shape.len() {
+            self.transform_axis(output, &half_shape, axis, false)?;
+        }
+        Ok(())
+    }
+    // Complex-to-real inverse of forward_real, `input` is left untouched
+    pub fn inverse_real(
+        &self,
+        input: &BufferView<Float2>,
+        output: &BufferView<f32>,
+        shape: &[u32],
+    ) -> backend::Result<()> {
+        check_shape(shape);
+        let half_shape = real_spectrum_shape(shape);
+        let len = shape.iter().product::<u32>();
+        assert_eq!(input.len(), half_shape.iter().product::<u32>() as usize);
+        assert_eq!(output.len(), len as usize);
+        let half = self.device.create_buffer::<Float2>(input.len())?;
+        input.copy_to_buffer(&half.view(..));
+        for axis in 1..shape.len() {
+            self.transform_axis(&half.view(..), &half_shape, axis, true)?;
+        }
+        let full = self.device.create_buffer::<Float2>(len as usize)?;
+        let rows = len / shape[0];
+        let params = self
+            .device
+            .create_buffer_from_slice(&[shape[0], half_shape[0]])?;
+        self.expand
+            .dispatch([shape[0], rows, 1], &half, &full, &params)?;
+        self.transform_axis(&full.view(..), shape, 0, true)?;
+        self.complex_to_real.dispatch([len, 1, 1], &full, output)
+    }
+    // 2D transform of a texture: the first two channels of a texel are the real and
+    // imaginary parts, so single channel textures are transformed as real data
+    pub fn forward_tex2d<T: IoTexel>(
+        &self,
+        tex: &Tex2dView<T>,
+        output: &BufferView<Float2>,
+    ) -> backend::Result<()> {
+        let [w, h, _] = tex.size();
+        assert_eq!(output.len(), (w * h) as usize);
+        // the texture load/store kernels are compiled on first use and kept on the texture
+        let device = &self.device;
+        let kernel = tex.tex.handle.cached::<TexLoadKernel<T>>([0; 3], || {
+            device.create_kernel::<(Tex2d<T>, Buffer<Float2>)>(&|tex, data| {
+                let p = dispatch_id();
+                let v = T::to_float4(tex.read(p.xy()));
+                data.write(p.y() * dispatch_size().x() + p.x(), v.xy());
+            })
+        })?;
+        kernel.dispatch([w, h, 1], tex, output)?;
+        self.forward(output, &[w, h])
+    }
+    // Inverse 2D transform of `input` written to the first two channels of `tex`,
+    // `input` is left untouched
+    pub fn inverse_tex2d<T: IoTexel>(
+        &self,
+        input: &BufferView<Float2>,
+        tex: &Tex2dView<T>,
+    ) -> backend::Result<()> {
+        let [w, h, _] = tex.size();
+        assert_eq!(input.len(), (w * h) as usize);
+        let data = self.device.create_buffer::<Float2>(input.len())?;
+        input.copy_to_buffer(&data.view(..));
+        self.inverse(&data.view(..), &[w, h])?;
+        let device = &self.device;
+        let kernel = tex.tex.handle.cached::<TexStoreKernel<T>>([0; 3], || {
+            device.create_kernel::<(Buffer<Float2>, Tex2d<T>)>(&|data, tex| {
+                let p = dispatch_id();
+                let v = data.read(p.y() * dispatch_size().x() + p.x());
+                tex.write(
+                    p.xy(),
+                    T::from_float4(make_float4(v.x(), v.y(), 0.0f32, 0.0f32)),
+                );
+            })
+        })?;
+        kernel.dispatch([w, h, 1], &data, tex)
+    }
+}
+
+// Direct O(n^2) transforms in double precision, for testing
+pub mod host {
+    use crate::*;
+
+    pub fn fft(data: &[Float2], shape: &[u32], inverse: bool) -> Vec<Float2> {
+        assert_eq!(data.len(), shape.iter().product::<u32>() as usize);
+        let sign = if inverse { 1.0 } else { -1.0 };
+        let mut values: Vec<(f64, f64)> = data.iter().map(|v| (v.x as f64, v.y as f64)).collect();
+        for (axis, n) in shape.iter().enumerate() {
+            let n = *n as usize;
+            let stride = shape[..axis].iter().product::<u32>() as usize;
+            let mut out = vec![(0.0, 0.0); values.len()];
+            for (i, o) in out.iter_mut().enumerate() {
+                let k = i / stride % n;
+                let base = i - k * stride;
+                for t in 0..n {
+                    let angle = sign * 2.0 * std::f64::consts::PI * (k * t % n) as f64 / n as f64;
+                    let (c, s) = (angle.cos(), angle.sin());
+                    let (re, im) = values[base + t * stride];
+                    o.0 += re * c - im * s;
+                    o.1 += re * s + im * c;
+                }
+                if inverse {
+                    o.0 /= n as f64;
+                    o.1 /= n as f64;
+                }
+            }
+            values = out;
+        }
+        values
+            .into_iter()
+            .map(|(re, im)| Float2::new(re as f32, im as f32))
+            .collect()
+    }
+    // The half spectrum computed by Fft::forward_real
+    pub fn rfft(data: &[f32], shape: &[u32]) -> Vec<Float2> {
+        let complex: Vec<_> = data.iter().map(|v| Float2::new(*v, 0.0)).collect();
+        let full = fft(&complex, shape, false);
+        let (w, hw) = (shape[0] as usize, shape[0] as usize / 2 + 1);
+        full.chunks(w)
+            .flat_map(|row| row[..hw].iter().copied())
+            .collect()
+    }
+}
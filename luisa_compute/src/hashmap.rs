@@ -0,0 +1,272 @@
+// Open addressing hash map living in device memory.
+// Slots are claimed with atomic_compare_exchange on the key buffer and probed linearly;
+// entries can be inserted but never removed, except by clearing the whole map.
+// Keys are limited to the integer types implementing HashMapKey (u32, i32, u64 and i64),
+// since a slot is claimed by a single compare-exchange on the key itself. The maximum
+// value of the key type (e.g. u32::MAX) marks free slots and is reserved: inserting it
+// from a kernel fails and DeviceHashMap::from_hash_map panics on it.
+use std::collections::HashMap;
+
+use crate::*;
+
+pub trait HashMapKey: Value + Eq + std::hash::Hash + 'static {
+    // Marks a free slot, this key cannot be stored in the map
+    // The implementations use the maximum value of the type
+    const EMPTY: Self;
+    fn hash(key: Expr<Self>) -> Expr<u32>;
+    fn host_hash(key: Self) -> u32;
+    fn equal(a: Expr<Self>, b: Expr<Self>) -> Expr<bool>;
+    fn compare_exchange(
+        keys: &BufferVar<Self>,
+        i: Expr<u32>,
+        expected: Expr<Self>,
+        desired: Expr<Self>,
+    ) -> Expr<Self>;
+}
+// Values accepted by DeviceHashMapVar::insert_or_add
+pub trait HashMapAddValue: Value + 'static {
+    fn atomic_add(values: &BufferVar<Self>, i: Expr<u32>, v: Expr<Self>);
+}
+
+// pcg hash
+fn hash_u32(v: Expr<u32>) -> Expr<u32> {
+    let state = v * 747796405u32 + 2891336453u32;
+    let word = ((state >> ((state >> 28u32) + 4u32)) ^ state) * 277803737u32;
+    (word >> 22u32) ^ word
+}
+fn host_hash_u32(v: u32) -> u32 {
+    let state = v.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+macro_rules! impl_hash_map_key {
+    ($t:ty, $fold:expr, $host_fold:expr) => {
+        impl HashMapKey for $t {
+            const EMPTY: Self = <$t>::MAX;
+            fn hash(key: Expr<Self>) -> Expr<u32> {
+                hash_u32($fold(key))
+            }
+            fn host_hash(key: Self) -> u32 {
+                host_hash_u32($host_fold(key))
+            }
+            fn equal(a: Expr<Self>, b: Expr<Self>) -> Expr<bool> {
+                a.cmpeq(b)
+            }
+            fn compare_exchange(
+                keys: &BufferVar<Self>,
+                i: Expr<u32>,
+                expected: Expr<Self>,
+                desired: Expr<Self>,
+            ) -> Expr<Self> {
+                keys.atomic_compare_exchange(i, expected, desired)
+            }
+        }
+    };
+}
+impl_hash_map_key!(u32, |k: Expr<u32>| k, |k: u32| k);
+impl_hash_map_key!(i32, |k: Expr<i32>| k.uint(), |k: i32| k as u32);
+impl_hash_map_key!(
+    u64,
+    |k: Expr<u64>| ((k >> 32u64) ^ k).uint(),
+    |k: u64| ((k >> 32) ^ k) as u32
+);
+impl_hash_map_key!(
+    i64,
+    |k: Expr<i64>| {
+        let k = k.ulong();
+        ((k >> 32u64) ^ k).uint()
+    },
+    |k: i64| ((k as u64 >> 32) ^ k as u64) as u32
+);
+
+macro_rules! impl_hash_map_add_value {
+    ($t:ty) => {
+        impl HashMapAddValue for $t {
+            fn atomic_add(values: &BufferVar<Self>, i: Expr<u32>, v: Expr<Self>) {
+                values.atomic_fetch_add(i, v);
+            }
+        }
+    };
+}
+impl_hash_map_add_value!(u32);
+impl_hash_map_add_value!(i32);
+impl_hash_map_add_value!(u64);
+impl_hash_map_add_value!(i64);
+impl_hash_map_add_value!(f32);
+
+pub struct DeviceHashMap<K: HashMapKey, V: Value + 'static> {
+    keys: Buffer<K>,
+    values: Buffer<V>,
+}
+impl<K: HashMapKey, V: Value + 'static> DeviceHashMap<K, V> {
+    // The capacity is rounded up to a power of two
+    pub fn new(device: &Device, capacity: usize) -> backend::Result<Self> {
+        let capacity = capacity.max(1).next_power_of_two();
+        let map = Self {
+            keys: device.create_buffer(capacity)?,
+            values: device.create_buffer(capacity)?,
+        };
+        map.clear();
+        Ok(map)
+    }
+    pub fn from_hash_map(
+        device: &Device,
+        entries: &HashMap<K, V>,
+        capacity: usize,
+    ) -> backend::Result<Self> {
+        let capacity = capacity.max(entries.len()).max(1).next_power_of_two();
+        let mut keys = vec![K::EMPTY; capacity];
+        let mut values = vec![unsafe { std::mem::zeroed::<V>() }; capacity];
+        for (k, v) in entries {
+            assert!(*k != K::EMPTY, "the empty key cannot be stored");
+            let mut slot = K::host_hash(*k) as usize & (capacity - 1);
+            while keys[slot] != K::EMPTY {
+                slot = (slot + 1) & (capacity - 1);
+            }
+            keys[slot] = *k;
+            values[slot] = *v;
+        }
+        Ok(Self {
+            keys: device.create_buffer_from_slice(&keys)?,
+            values: device.create_buffer_from_slice(&values)?,
+        })
+    }
+    pub fn capacity(&self) -> usize {
+        self.keys.len()
+    }
+    pub fn keys(&self) -> &Buffer<K> {
+        &self.keys
+    }
+    pub fn values(&self) -> &Buffer<V> {
+        &self.values
+    }
+    // Removes every entry; values are reset to zero so insert_or_add starts from zero
+    pub fn clear(&self) {
+        self.keys.fill(K::EMPTY);
+        self.values.fill(unsafe { std::mem::zeroed() });
+    }
+    pub fn len(&self) -> usize {
+        self.keys
+            .copy_to_vec()
+            .into_iter()
+            .filter(|k| *k != K::EMPTY)
+            .count()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn to_hash_map(&self) -> HashMap<K, V> {
+        let keys = self.keys.copy_to_vec();
+        let values = self.values.copy_to_vec();
+        keys.into_iter()
+            .zip(values)
+            .filter(|(k, _)| *k != K::EMPTY)
+            .collect()
+    }
+    pub fn var(&self) -> DeviceHashMapVar<K, V> {
+        DeviceHashMapVar {
+            keys: self.keys.var(),
+            values: self.values.var(),
+        }
+    }
+}
+impl<K: HashMapKey, V: Value + 'static> KernelArg for DeviceHashMap<K, V> {
+    type Parameter = DeviceHashMapVar<K, V>;
+    fn encode(&self, encoder: &mut ArgEncoder) {
+        encoder.buffer(&self.keys);
+        encoder.buffer(&self.values);
+    }
+}
+impl<K: HashMapKey, V: Value + 'static> AsKernelArg<DeviceHashMap<K, V>> for DeviceHashMap<K, V> {}
+
+pub struct DeviceHashMapVar<K: HashMapKey, V: Value + 'static> {
+    keys: BufferVar<K>,
+    values: BufferVar<V>,
+}
+impl<K: HashMapKey, V: Value + 'static> KernelParameter for DeviceHashMapVar<K, V> {
+    fn def_param(builder: &mut KernelBuilder) -> Self {
+        Self {
+            keys: builder.buffer(),
+            values: builder.buffer(),
+        }
+    }
+}
+impl<K: HashMapKey, V: Value + 'static> DeviceHashMapVar<K, V> {
+    pub fn capacity(&self) -> Expr<u32> {
+        self.keys.len()
+    }
+    // Probes from the home slot of `key`; `visit` returns whether to stop at the slot and
+    // the returned slot is u32::MAX if every slot was visited without stopping
+    fn probe(&self, key: Expr<K>, visit: impl Fn(Expr<u32>) -> Expr<bool>) -> Expr<u32> {
+        let mask = self.capacity() - 1u32;
+        let slot = var!(u32, K::hash(key) & mask);
+        let found = var!(u32, u32::MAX);
+        let i = var!(u32, 0u32);
+        while_!(i.load().cmplt(self.capacity()), {
+            if_!(visit(slot.load()), {
+                found.store(slot.load());
+                break_();
+            });
+            slot.store((slot.load() + 1u32) & mask);
+            i.store(i.load() + 1u32);
+        });
+        found.load()
+    }
+    // Slot holding `key`, claiming a free one if needed; u32::MAX if the map is full
+    // or `key` is the reserved empty key
+    fn find_or_insert(&self, key: Expr<K>) -> Expr<u32> {
+        let slot = self.probe(key, |slot| {
+            let prev = K::compare_exchange(&self.keys, slot, const_(K::EMPTY), key);
+            K::equal(prev, const_(K::EMPTY)) | K::equal(prev, key)
+        });
+        select(K::equal(key, const_(K::EMPTY)), const_(u32::MAX), slot)
+    }
+    // Slot holding `key`, u32::MAX if the key is absent
+    pub fn find(&self, key: impl Into<Expr<K>>) -> Expr<u32> {
+        let key = key.into();
+        let stop = var!(bool, false);
+        let slot = self.probe(key, |slot| {
+            let k = self.keys.read(slot);
+            stop.store(K::equal(k, const_(K::EMPTY)));
+            stop.load() | K::equal(k, key)
+        });
+        select(stop.load(), const_(u32::MAX), slot)
+    }
+    pub fn contains(&self, key: impl Into<Expr<K>>) -> Expr<bool> {
+        self.find(key).cmpne(u32::MAX)
+    }
+    // Returns whether the key was found, the value is zero otherwise
+    pub fn get(&self, key: impl Into<Expr<K>>) -> (Expr<bool>, Expr<V>) {
+        let slot = self.find(key);
+        let found = slot.cmpne(u32::MAX);
+        let value = var!(V, zeroed::<V>());
+        if_!(found, {
+            value.store(self.values.read(slot));
+        });
+        (found, value.load())
+    }
+    // Inserts or overwrites the entry, returns false if the map is full or `key` is K::EMPTY
+    pub fn insert(&self, key: impl Into<Expr<K>>, value: impl Into<Expr<V>>) -> Expr<bool> {
+        let value = value.into();
+        let slot = self.find_or_insert(key.into());
+        let inserted = slot.cmpne(u32::MAX);
+        if_!(inserted, {
+            self.values.write(slot, value);
+        });
+        inserted
+    }
+}
+impl<K: HashMapKey, V: HashMapAddValue> DeviceHashMapVar<K, V> {
+    // Atomically adds `value` to the entry, which starts from zero when the key is new;
+    // returns false if the map is full or `key` is K::EMPTY
+    pub fn insert_or_add(&self, key: impl Into<Expr<K>>, value: impl Into<Expr<V>>) -> Expr<bool> {
+        let value = value.into();
+        let slot = self.find_or_insert(key.into());
+        let inserted = slot.cmpne(u32::MAX);
+        if_!(inserted, {
+            V::atomic_add(&self.values, slot, value);
+        });
+        inserted
+    }
+}
@@ -0,0 +1,309 @@
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use crate::*;
+use half::f16;
+
+#[derive(Debug)]
+pub enum ImageIoError {
+    Image(image::ImageError),
+    Io(std::io::Error),
+    Backend(backend::BackendError),
+    UnsupportedStorage(PixelStorage),
+}
+impl fmt::Display for ImageIoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageIoError::Image(e) => write!(f, "{}", e),
+            ImageIoError::Io(e) => write!(f, "{}", e),
+            ImageIoError::Backend(e) => write!(f, "{:?}", e),
+            ImageIoError::UnsupportedStorage(s) => {
+                write!(f, "pixel storage {:?} can't be converted to an image", s)
+            }
+        }
+    }
+}
+impl std::error::Error for ImageIoError {}
+impl From<image::ImageError> for ImageIoError {
+    fn from(e: image::ImageError) -> Self {
+        ImageIoError::Image(e)
+    }
+}
+impl From<std::io::Error> for ImageIoError {
+    fn from(e: std::io::Error) -> Self {
+        ImageIoError::Io(e)
+    }
+}
+impl From<backend::BackendError> for ImageIoError {
+    fn from(e: backend::BackendError) -> Self {
+        ImageIoError::Backend(e)
+    }
+}
+pub type Result<T> = std::result::Result<T, ImageIoError>;
+
+#[derive(Clone, Copy)]
+enum Channel {
+    Unorm8,
+    Unorm16,
+    Half,
+    Float,
+}
+impl Channel {
+    fn size(self) -> usize {
+        match self {
+            Channel::Unorm8 => 1,
+            Channel::Unorm16 | Channel::Half => 2,
+            Channel::Float => 4,
+        }
+    }
+    fn decode(self, bytes: &[u8]) -> f32 {
+        match self {
+            Channel::Unorm8 => bytes[0] as f32 / 255.0,
+            Channel::Unorm16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 65535.0,
+            Channel::Half => f16::from_le_bytes([bytes[0], bytes[1]]).to_f32(),
+            Channel::Float => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+    fn encode(self, v: f32, out: &mut Vec<u8>) {
+        match self {
+            Channel::Unorm8 => out.push((v.clamp(0.0, 1.0) * 255.0).round() as u8),
+            Channel::Unorm16 => {
+                out.extend_from_slice(&((v.clamp(0.0, 1.0) * 65535.0).round() as u16).to_le_bytes())
+            }
+            Channel::Half => out.extend_from_slice(&f16::from_f32(v).to_le_bytes()),
+            Channel::Float => out.extend_from_slice(&v.to_le_bytes()),
+        }
+    }
+}
+// Integer storages hold unnormalized values and are rejected
+fn storage_layout(storage: PixelStorage) -> Result<(Channel, usize)> {
+    Ok(match storage {
+        PixelStorage::Byte1 => (Channel::Unorm8, 1),
+        PixelStorage::Byte2 => (Channel::Unorm8, 2),
+        PixelStorage::Byte4 => (Channel::Unorm8, 4),
+        PixelStorage::Short1 => (Channel::Unorm16, 1),
+        PixelStorage::Short2 => (Channel::Unorm16, 2),
+        PixelStorage::Short4 => (Channel::Unorm16, 4),
+        PixelStorage::Half1 => (Channel::Half, 1),
+        PixelStorage::Half2 => (Channel::Half, 2),
+        PixelStorage::Half4 => (Channel::Half, 4),
+        PixelStorage::Float1 => (Channel::Float, 1),
+        PixelStorage::Float2 => (Channel::Float, 2),
+        PixelStorage::Float4 => (Channel::Float, 4),
+        _ => return Err(ImageIoError::UnsupportedStorage(storage)),
+    })
+}
+// Expands texels to rgba, single channel data is treated as grayscale
+fn decode_texels(storage: PixelStorage, data: &[u8]) -> Result<Vec<[f32; 4]>> {
+    let (channel, n) = storage_layout(storage)?;
+    Ok(data
+        .chunks_exact(channel.size() * n)
+        .map(|texel| {
+            let c = |i: usize| channel.decode(&texel[i * channel.size()..]);
+            match n {
+                1 => [c(0), c(0), c(0), 1.0],
+                2 => [c(0), c(1), 0.0, 1.0],
+                _ => [c(0), c(1), c(2), c(3)],
+            }
+        })
+        .collect())
+}
+fn encode_texels(storage: PixelStorage, texels: &[[f32; 4]]) -> Result<Vec<u8>> {
+    let (channel, n) = storage_layout(storage)?;
+    let mut data = Vec::with_capacity(texels.len() * channel.size() * n);
+    for texel in texels {
+        for i in 0..n {
+            channel.encode(texel[i], &mut data);
+        }
+    }
+    Ok(data)
+}
+fn load_rgba(path: &Path) -> Result<(Vec<[f32; 4]>, u32, u32)> {
+    let image = image::open(path)?.into_rgba32f();
+    let (width, height) = image.dimensions();
+    let texels = image.pixels().map(|p| p.0).collect();
+    Ok((texels, width, height))
+}
+fn save_png(
+    path: &Path,
+    storage: PixelStorage,
+    texels: &[[f32; 4]],
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    let (channel, _) = storage_layout(storage)?;
+    match channel {
+        Channel::Unorm16 => {
+            let data = texels
+                .iter()
+                .flat_map(|t| t.map(|v| (v.clamp(0.0, 1.0) * 65535.0).round() as u16))
+                .collect::<Vec<_>>();
+            image::ImageBuffer::<image::Rgba<u16>, _>::from_raw(width, height, data)
+                .unwrap()
+                .save_with_format(path, image::ImageFormat::Png)?;
+        }
+        _ => {
+            let data = texels
+                .iter()
+                .flat_map(|t| t.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8))
+                .collect::<Vec<_>>();
+            image::RgbaImage::from_raw(width, height, data)
+                .unwrap()
+                .save_with_format(path, image::ImageFormat::Png)?;
+        }
+    }
+    Ok(())
+}
+fn save_exr(path: &Path, texels: &[[f32; 4]], width: u32, height: u32) -> Result<()> {
+    let data = texels.iter().flatten().copied().collect::<Vec<_>>();
+    image::Rgba32FImage::from_raw(width, height, data)
+        .unwrap()
+        .save_with_format(path, image::ImageFormat::OpenExr)?;
+    Ok(())
+}
+// PFM stores rgb scanlines bottom to top, a negative scale means little endian
+fn save_pfm(path: &Path, texels: &[[f32; 4]], width: u32, height: u32) -> Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write!(file, "PF\n{} {}\n-1.0\n", width, height)?;
+    for row in texels.chunks_exact(width as usize).rev() {
+        for texel in row {
+            for v in &texel[..3] {
+                file.write_all(&v.to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()?;
+    Ok(())
+}
+
+impl<T: IoTexel> Tex2d<T> {
+    // Loads an image into level 0, converting it to `storage`
+    pub fn from_image_file(
+        device: &Device,
+        path: impl AsRef<Path>,
+        storage: PixelStorage,
+        mips: u32,
+    ) -> Result<Self> {
+        let (texels, width, height) = load_rgba(path.as_ref())?;
+        let tex = device.create_tex2d::<T>(storage, width, height, mips)?;
+        let data = encode_texels(tex.handle.storage, &texels)?;
+        let view = tex.view(0);
+        let mut rt = ResourceTracker::new();
+        rt.add(tex.handle.clone());
+        let cmd = Command {
+            inner: api::Command::TextureUpload(api::TextureUploadCommand {
+                texture: view.handle(),
+                storage: tex.handle.storage,
+                level: 0,
+                size: view.size(),
+                data: data.as_ptr(),
+            }),
+            marker: std::marker::PhantomData,
+            resource_tracker: rt,
+        };
+        submit_default_stream_and_sync(device, [cmd])?;
+        Ok(tex)
+    }
+}
+impl<'a, T: IoTexel> Tex2dView<'a, T> {
+    fn download_rgba(&self) -> Result<Vec<[f32; 4]>> {
+        let storage = self.tex.handle.storage;
+        let (channel, n) = storage_layout(storage)?;
+        let mut data = vec![0u8; self.texel_count() as usize * channel.size() * n];
+        let mut rt = ResourceTracker::new();
+        rt.add(self.tex.handle.clone());
+        let cmd = Command {
+            inner: api::Command::TextureDownload(api::TextureDownloadCommand {
+                texture: self.handle(),
+                storage,
+                level: self.level,
+                size: self.size(),
+                data: data.as_mut_ptr(),
+            }),
+            marker: std::marker::PhantomData,
+            resource_tracker: rt,
+        };
+        submit_default_stream_and_sync(&self.tex.handle.device, [cmd])?;
+        decode_texels(storage, &data)
+    }
+    // 16-bit storages are saved as 16-bit pngs, everything else as 8-bit
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<()> {
+        let [w, h, _] = self.size();
+        save_png(
+            path.as_ref(),
+            self.tex.handle.storage,
+            &self.download_rgba()?,
+            w,
+            h,
+        )
+    }
+    pub fn save_exr(&self, path: impl AsRef<Path>) -> Result<()> {
+        let [w, h, _] = self.size();
+        save_exr(path.as_ref(), &self.download_rgba()?, w, h)
+    }
+    pub fn save_pfm(&self, path: impl AsRef<Path>) -> Result<()> {
+        let [w, h, _] = self.size();
+        save_pfm(path.as_ref(), &self.download_rgba()?, w, h)
+    }
+}
+// Buffers hold row-major texels of any type that can back a float texture,
+// e.g. `Buffer<Float4>` or `Buffer<f32>`
+impl<T: Value + StorageTexel<Float4>> Buffer<T> {
+    pub fn from_image_file(device: &Device, path: impl AsRef<Path>) -> Result<(Self, u32, u32)> {
+        let (texels, width, height) = load_rgba(path.as_ref())?;
+        let storage = T::pixel_storage();
+        let data = encode_texels(storage, &texels)?;
+        assert_eq!(data.len(), texels.len() * std::mem::size_of::<T>());
+        let buffer = device.create_buffer::<T>(texels.len())?;
+        let mut typed = Vec::<T>::with_capacity(texels.len());
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), typed.as_mut_ptr() as *mut u8, data.len());
+            typed.set_len(texels.len());
+        }
+        buffer.view(..).copy_from(&typed);
+        Ok((buffer, width, height))
+    }
+    pub fn save_png(&self, path: impl AsRef<Path>, width: u32, height: u32) -> Result<()> {
+        self.view(..).save_png(path, width, height)
+    }
+    pub fn save_exr(&self, path: impl AsRef<Path>, width: u32, height: u32) -> Result<()> {
+        self.view(..).save_exr(path, width, height)
+    }
+    pub fn save_pfm(&self, path: impl AsRef<Path>, width: u32, height: u32) -> Result<()> {
+        self.view(..).save_pfm(path, width, height)
+    }
+}
+impl<'a, T: Value + StorageTexel<Float4>> BufferView<'a, T> {
+    fn download_rgba(&self, width: u32, height: u32) -> Result<Vec<[f32; 4]>> {
+        assert_eq!(self.len, (width * height) as usize);
+        let data = self.copy_to_vec();
+        let data = unsafe {
+            std::slice::from_raw_parts(
+                data.as_ptr() as *const u8,
+                data.len() * std::mem::size_of::<T>(),
+            )
+        };
+        decode_texels(T::pixel_storage(), data)
+    }
+    pub fn save_png(&self, path: impl AsRef<Path>, width: u32, height: u32) -> Result<()> {
+        let texels = self.download_rgba(width, height)?;
+        save_png(path.as_ref(), T::pixel_storage(), &texels, width, height)
+    }
+    pub fn save_exr(&self, path: impl AsRef<Path>, width: u32, height: u32) -> Result<()> {
+        save_exr(
+            path.as_ref(),
+            &self.download_rgba(width, height)?,
+            width,
+            height,
+        )
+    }
+    pub fn save_pfm(&self, path: impl AsRef<Path>, width: u32, height: u32) -> Result<()> {
+        save_pfm(
+            path.as_ref(),
+            &self.download_rgba(width, height)?,
+            width,
+            height,
+        )
+    }
+}
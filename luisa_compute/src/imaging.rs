@@ -0,0 +1,344 @@
+// Image processing on Tex2d: separable convolution, Gaussian blur, resampling, tone mapping
+// and sRGB conversion. Texels are processed as Float4 through IoTexel, so the kernels work
+// on Tex2d<Float4>, Tex2d<f32> and the other texel types; color operations only touch rgb.
+// Reads outside the image are clamped to the edge. Separable operations keep the
+// intermediate result in a Float4 buffer so no precision is lost between the two passes.
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResizeFilter {
+    // nearest neighbour when upsampling, area average when downsampling
+    Box,
+    // bilinear
+    Triangle,
+    // Mitchell-Netravali with B = C = 1/3
+    Mitchell,
+    Lanczos3,
+}
+const RESIZE_FILTERS: [ResizeFilter; 4] = [
+    ResizeFilter::Box,
+    ResizeFilter::Triangle,
+    ResizeFilter::Mitchell,
+    ResizeFilter::Lanczos3,
+];
+impl ResizeFilter {
+    fn radius(&self) -> f32 {
+        match self {
+            ResizeFilter::Box => 0.5,
+            ResizeFilter::Triangle => 1.0,
+            ResizeFilter::Mitchell => 2.0,
+            ResizeFilter::Lanczos3 => 3.0,
+        }
+    }
+    fn weight(&self, x: Expr<f32>) -> Expr<f32> {
+        let x = x.abs();
+        match self {
+            ResizeFilter::Box => select(x.cmplt(0.5f32), const_(1.0f32), const_(0.0f32)),
+            ResizeFilter::Triangle => (1.0f32 - x).max(0.0f32),
+            ResizeFilter::Mitchell => {
+                let x2 = x * x;
+                let x3 = x2 * x;
+                let near = (7.0f32 * x3 - 12.0f32 * x2 + 16.0f32 / 3.0f32) / 6.0f32;
+                let far = (-7.0f32 / 3.0f32 * x3 + 12.0f32 * x2 - 20.0f32 * x + 32.0f32 / 3.0f32)
+                    / 6.0f32;
+                select(
+                    x.cmplt(1.0f32),
+                    near,
+                    select(x.cmplt(2.0f32), far, const_(0.0f32)),
+                )
+            }
+            ResizeFilter::Lanczos3 => {
+                let sinc = |x: Expr<f32>| {
+                    let px = x * std::f32::consts::PI;
+                    select(px.cmplt(1e-4f32), const_(1.0f32), px.sin() / px)
+                };
+                select(x.cmplt(3.0f32), sinc(x) * sinc(x / 3.0f32), const_(0.0f32))
+            }
+        }
+    }
+}
+// Radius and weight of the filter selected at runtime by its index in RESIZE_FILTERS
+fn resize_filter(filter: Expr<u32>, x: Expr<f32>) -> (Expr<f32>, Expr<f32>) {
+    RESIZE_FILTERS.iter().enumerate().fold(
+        (const_(0.0f32), const_(0.0f32)),
+        |(radius, weight), (i, f)| {
+            let used = filter.cmpeq(i as u32);
+            (
+                select(used, const_(f.radius()), radius),
+                select(used, f.weight(x), weight),
+            )
+        },
+    )
+}
+// Resamples texel `i` of a line of `dst` texels from a line of `src` texels; the filter is
+// stretched by the scale when downsampling so that it averages over the whole footprint
+fn resample(
+    i: Expr<u32>,
+    src: Expr<u32>,
+    dst: Expr<u32>,
+    filter: Expr<u32>,
+    read: impl Fn(Expr<u32>) -> Expr<Float4>,
+) -> Expr<Float4> {
+    let scale = src.float() / dst.float();
+    let support = scale.max(1.0f32);
+    let center = (i.float() + 0.5f32) * scale;
+    let (radius, _) = resize_filter(filter, const_(0.0f32));
+    let radius = radius * support;
+    let begin = (center - radius).floor().int().max(0i32);
+    let end = (center + radius).ceil().int().min(src.int());
+    let sum = var!(Float4);
+    let weight_sum = var!(f32);
+    let j = var!(i32, begin);
+    while_!(j.load().cmplt(end), {
+        let (_, w) = resize_filter(filter, (j.load().float() + 0.5f32 - center) / support);
+        sum.store(sum.load() + read(j.load().uint()) * w);
+        weight_sum.store(weight_sum.load() + w);
+        j.store(j.load() + 1i32);
+    });
+    let nearest = center.floor().int().clamp(0i32, src.int() - 1i32).uint();
+    select(
+        weight_sum.load().cmpgt(0.0f32),
+        sum.load() / weight_sum.load(),
+        read(nearest),
+    )
+}
+
+// Taps of a normalized Gaussian with radius ceil(3 * sigma)
+pub fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    assert!(sigma > 0.0);
+    let radius = (3.0 * sigma).ceil() as i32;
+    let taps: Vec<f32> = (-radius..=radius)
+        .map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = taps.iter().sum();
+    taps.iter().map(|w| w / sum).collect()
+}
+
+fn srgb_encode_channel(x: Expr<f32>) -> Expr<f32> {
+    let x = x.max(0.0f32);
+    select(
+        x.cmple(0.0031308f32),
+        x * 12.92f32,
+        1.055f32 * x.powf(1.0f32 / 2.4f32) - 0.055f32,
+    )
+}
+fn srgb_decode_channel(x: Expr<f32>) -> Expr<f32> {
+    let x = x.max(0.0f32);
+    select(
+        x.cmple(0.04045f32),
+        x / 12.92f32,
+        ((x + 0.055f32) / 1.055f32).powf(2.4f32),
+    )
+}
+pub fn linear_to_srgb(c: Expr<Float3>) -> Expr<Float3> {
+    make_float3(
+        srgb_encode_channel(c.x()),
+        srgb_encode_channel(c.y()),
+        srgb_encode_channel(c.z()),
+    )
+}
+pub fn srgb_to_linear(c: Expr<Float3>) -> Expr<Float3> {
+    make_float3(
+        srgb_decode_channel(c.x()),
+        srgb_decode_channel(c.y()),
+        srgb_decode_channel(c.z()),
+    )
+}
+pub fn reinhard(c: Expr<Float3>) -> Expr<Float3> {
+    c / (c + 1.0f32)
+}
+// Narkowicz's fit of the ACES filmic curve
+pub fn aces(c: Expr<Float3>) -> Expr<Float3> {
+    let (a, b, c2, d, e) = (2.51f32, 0.03f32, 2.43f32, 0.59f32, 0.14f32);
+    ((c * (c * a + b)) / (c * (c * c2 + d) + e)).clamp(0.0f32, 1.0f32)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping {
+    Reinhard,
+    Aces,
+}
+
+type RowsKernel<T, P> = Kernel<(Tex2d<T>, Buffer<Float4>, Buffer<P>)>;
+type ColumnsKernel<T, P> = Kernel<(Buffer<Float4>, Tex2d<T>, Buffer<P>)>;
+type PointKernel<T> = Kernel<(Tex2d<T>, Tex2d<T>, Buffer<f32>)>;
+
+fn point_kernel<T: IoTexel>(
+    device: &Device,
+    f: impl Fn(Expr<Float3>, Expr<f32>) -> Expr<Float3>,
+) -> backend::Result<PointKernel<T>> {
+    device.create_kernel::<(Tex2d<T>, Tex2d<T>, Buffer<f32>)>(&|src, dst, params| {
+        let p = dispatch_id().xy();
+        let v = T::to_float4(src.read(p));
+        let rgb = f(v.xyz(), params.read(0u32));
+        dst.write(
+            p,
+            T::from_float4(make_float4(rgb.x(), rgb.y(), rgb.z(), v.w())),
+        );
+    })
+}
+
+pub struct Imaging<T: IoTexel> {
+    device: Device,
+    // the weights buffer holds the taps, centered on the texel
+    convolve_rows: RowsKernel<T, f32>,
+    convolve_columns: ColumnsKernel<T, f32>,
+    // params: [src_extent, filter]
+    resize_rows: RowsKernel<T, u32>,
+    resize_columns: ColumnsKernel<T, u32>,
+    // params: [exposure]
+    reinhard: PointKernel<T>,
+    aces: PointKernel<T>,
+    srgb_encode: PointKernel<T>,
+    srgb_decode: PointKernel<T>,
+}
+impl<T: IoTexel> Imaging<T> {
+    pub fn new(device: &Device) -> backend::Result<Self> {
+        let convolve_rows = device.create_kernel::<(Tex2d<T>, Buffer<Float4>, Buffer<f32>)>(
+            &|src, rows, weights| {
+                let p = dispatch_id().xy();
+                let w = dispatch_size().x();
+                let radius = (weights.len() / 2u32).int();
+                let sum = var!(Float4);
+                let i = var!(i32, -radius);
+                while_!(i.load().cmple(radius), {
+                    let x = (p.x().int() + i.load()).clamp(0i32, w.int() - 1i32).uint();
+                    let texel = T::to_float4(src.read(make_uint2(x, p.y())));
+                    sum.store(sum.load() + texel * weights.read((i.load() + radius).uint()));
+                    i.store(i.load() + 1i32);
+                });
+                rows.write(p.y() * w + p.x(), sum.load());
+            },
+        )?;
+        let convolve_columns = device.create_kernel::<(Buffer<Float4>, Tex2d<T>, Buffer<f32>)>(
+            &|rows, dst, weights| {
+                let p = dispatch_id().xy();
+                let w = dispatch_size().x();
+                let h = dispatch_size().y();
+                let radius = (weights.len() / 2u32).int();
+                let sum = var!(Float4);
+                let i = var!(i32, -radius);
+                while_!(i.load().cmple(radius), {
+                    let y = (p.y().int() + i.load()).clamp(0i32, h.int() - 1i32).uint();
+                    let v = rows.read(y * w + p.x());
+                    sum.store(sum.load() + v * weights.read((i.load() + radius).uint()));
+                    i.store(i.load() + 1i32);
+                });
+                dst.write(p, T::from_float4(sum.load()));
+            },
+        )?;
+        let resize_rows = device.create_kernel::<(Tex2d<T>, Buffer<Float4>, Buffer<u32>)>(
+            &|src, rows, params| {
+                let p = dispatch_id().xy();
+                let w = dispatch_size().x();
+                let v = resample(p.x(), params.read(0u32), w, params.read(1u32), |x| {
+                    T::to_float4(src.read(make_uint2(x, p.y())))
+                });
+                rows.write(p.y() * w + p.x(), v);
+            },
+        )?;
+        let resize_columns = device.create_kernel::<(Buffer<Float4>, Tex2d<T>, Buffer<u32>)>(
+            &|rows, dst, params| {
+                let p = dispatch_id().xy();
+                let w = dispatch_size().x();
+                let h = dispatch_size().y();
+                let v = resample(p.y(), params.read(0u32), h, params.read(1u32), |y| {
+                    rows.read(y * w + p.x())
+                });
+                dst.write(p, T::from_float4(v));
+            },
+        )?;
+        Ok(Self {
+            device: device.clone(),
+            convolve_rows,
+            convolve_columns,
+            resize_rows,
+            resize_columns,
+            reinhard: point_kernel(device, |c, exposure| reinhard(c * exposure))?,
+            aces: point_kernel(device, |c, exposure| aces(c * exposure))?,
+            srgb_encode: point_kernel(device, |c, _| linear_to_srgb(c))?,
+            srgb_decode: point_kernel(device, |c, _| srgb_to_linear(c))?,
+        })
+    }
+    // Convolves the rows with `kernel_x` and then the columns with `kernel_y`, both of odd
+    // length and centered. `src` and `dst` must have the same size and may be the same texture
+    pub fn convolve_separable(
+        &self,
+        src: &Tex2dView<T>,
+        dst: &Tex2dView<T>,
+        kernel_x: &[f32],
+        kernel_y: &[f32],
+    ) -> backend::Result<()> {
+        assert!(kernel_x.len() % 2 == 1 && kernel_y.len() % 2 == 1);
+        let [w, h, _] = src.size();
+        assert_eq!(dst.size(), src.size());
+        let rows = self.device.create_buffer::<Float4>((w * h) as usize)?;
+        let kernel_x = self.device.create_buffer_from_slice(kernel_x)?;
+        let kernel_y = self.device.create_buffer_from_slice(kernel_y)?;
+        self.convolve_rows
+            .dispatch([w, h, 1], src, &rows, &kernel_x)?;
+        self.convolve_columns
+            .dispatch([w, h, 1], &rows, dst, &kernel_y)
+    }
+    pub fn gaussian_blur(
+        &self,
+        src: &Tex2dView<T>,
+        dst: &Tex2dView<T>,
+        sigma: f32,
+    ) -> backend::Result<()> {
+        let kernel = gaussian_kernel(sigma);
+        self.convolve_separable(src, dst, &kernel, &kernel)
+    }
+    // Resamples `src` to the size of `dst`
+    pub fn resize(
+        &self,
+        src: &Tex2dView<T>,
+        dst: &Tex2dView<T>,
+        filter: ResizeFilter,
+    ) -> backend::Result<()> {
+        let [src_w, src_h, _] = src.size();
+        let [dst_w, dst_h, _] = dst.size();
+        let filter = RESIZE_FILTERS.iter().position(|f| *f == filter).unwrap() as u32;
+        let rows = self
+            .device
+            .create_buffer::<Float4>((dst_w * src_h) as usize)?;
+        let params = self.device.create_buffer_from_slice(&[src_w, filter])?;
+        self.resize_rows
+            .dispatch([dst_w, src_h, 1], src, &rows, &params)?;
+        let params = self.device.create_buffer_from_slice(&[src_h, filter])?;
+        self.resize_columns
+            .dispatch([dst_w, dst_h, 1], &rows, dst, &params)
+    }
+    // Scales the color by `exposure` before applying `op`
+    pub fn tone_map(
+        &self,
+        src: &Tex2dView<T>,
+        dst: &Tex2dView<T>,
+        op: ToneMapping,
+        exposure: f32,
+    ) -> backend::Result<()> {
+        let kernel = match op {
+            ToneMapping::Reinhard => &self.reinhard,
+            ToneMapping::Aces => &self.aces,
+        };
+        self.dispatch_point(kernel, src, dst, exposure)
+    }
+    pub fn srgb_encode(&self, src: &Tex2dView<T>, dst: &Tex2dView<T>) -> backend::Result<()> {
+        self.dispatch_point(&self.srgb_encode, src, dst, 1.0)
+    }
+    pub fn srgb_decode(&self, src: &Tex2dView<T>, dst: &Tex2dView<T>) -> backend::Result<()> {
+        self.dispatch_point(&self.srgb_decode, src, dst, 1.0)
+    }
+    fn dispatch_point(
+        &self,
+        kernel: &PointKernel<T>,
+        src: &Tex2dView<T>,
+        dst: &Tex2dView<T>,
+        param: f32,
+    ) -> backend::Result<()> {
+        let [w, h, _] = src.size();
+        assert_eq!(dst.size(), src.size());
+        let params = self.device.create_buffer_from_slice(&[param])?;
+        kernel.dispatch([w, h, 1], src, dst, &params)
+    }
+}
@@ -1,12 +1,14 @@
 use std::ops::Mul;
 
 pub use super::swizzle::*;
-use super::{Aggregate, ExprProxy, Value, VarProxy, __extract, traits::*, Float};
+use super::{Aggregate, ExprProxy, Value, VarProxy, __extract, traits::*};
 use crate::*;
 use half::f16;
+use luisa_compute_derive::__Value;
 use luisa_compute_ir::{
     context::register_type,
-    ir::{Func, MatrixType, NodeRef, Primitive, Type, VectorElementType, VectorType},
+    ffi::CBoxedSlice,
+    ir::{Func, MatrixType, NodeRef, Primitive, StructType, Type, VectorElementType, VectorType},
     TypeOf,
 };
 macro_rules! def_vec {
@@ -107,6 +109,10 @@ def_vec_no_glam!(Byte2, u8, 2, x, y);
 def_vec_no_glam!(Byte3, u8, 4, x, y, z);
 def_vec_no_glam!(Byte4, u8, 4, x, y, z, w);
 
+def_vec_no_glam!(Char2, i8, 2, x, y);
+def_vec_no_glam!(Char3, i8, 4, x, y, z);
+def_vec_no_glam!(Char4, i8, 4, x, y, z, w);
+
 #[derive(Clone, Copy, Debug, Default)]
 #[repr(C, align(8))]
 pub struct Mat2 {
@@ -122,6 +128,21 @@ pub struct Mat3 {
 pub struct Mat4 {
     pub cols: [Float4; 4],
 }
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C, align(16))]
+pub struct DMat2 {
+    pub cols: [Double2; 2],
+}
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C, align(32))]
+pub struct DMat3 {
+    pub cols: [Double3; 3],
+}
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C, align(32))]
+pub struct DMat4 {
+    pub cols: [Double4; 4],
+}
 impl Mat2 {
     pub const fn from_cols(c0: Float2, c1: Float2) -> Self {
         Self { cols: [c0, c1] }
@@ -178,6 +199,43 @@ impl Mat4 {
         ]
     }
 }
+impl DMat2 {
+    pub const fn from_cols(c0: Double2, c1: Double2) -> Self {
+        Self { cols: [c0, c1] }
+    }
+    pub const fn identity() -> Self {
+        Self::from_cols(Double2::new(1.0, 0.0), Double2::new(0.0, 1.0))
+    }
+}
+impl DMat3 {
+    pub const fn from_cols(c0: Double3, c1: Double3, c2: Double3) -> Self {
+        Self {
+            cols: [c0, c1, c2],
+        }
+    }
+    pub const fn identity() -> Self {
+        Self::from_cols(
+            Double3::new(1.0, 0.0, 0.0),
+            Double3::new(0.0, 1.0, 0.0),
+            Double3::new(0.0, 0.0, 1.0),
+        )
+    }
+}
+impl DMat4 {
+    pub const fn from_cols(c0: Double4, c1: Double4, c2: Double4, c3: Double4) -> Self {
+        Self {
+            cols: [c0, c1, c2, c3],
+        }
+    }
+    pub const fn identity() -> Self {
+        Self::from_cols(
+            Double4::new(1.0, 0.0, 0.0, 0.0),
+            Double4::new(0.0, 1.0, 0.0, 0.0),
+            Double4::new(0.0, 0.0, 1.0, 0.0),
+            Double4::new(0.0, 0.0, 0.0, 1.0),
+        )
+    }
+}
 impl From<Mat2> for glam::Mat2 {
     #[inline]
     fn from(m: Mat2) -> Self {
@@ -230,6 +288,180 @@ impl From<glam::Mat4> for Mat4 {
         }
     }
 }
+impl From<DMat2> for glam::DMat2 {
+    #[inline]
+    fn from(m: DMat2) -> Self {
+        Self::from_cols(m.cols[0].into(), m.cols[1].into())
+    }
+}
+impl From<DMat3> for glam::DMat3 {
+    #[inline]
+    fn from(m: DMat3) -> Self {
+        Self::from_cols(m.cols[0].into(), m.cols[1].into(), m.cols[2].into())
+    }
+}
+impl From<DMat4> for glam::DMat4 {
+    #[inline]
+    fn from(m: DMat4) -> Self {
+        Self::from_cols(
+            m.cols[0].into(),
+            m.cols[1].into(),
+            m.cols[2].into(),
+            m.cols[3].into(),
+        )
+    }
+}
+impl From<glam::DMat2> for DMat2 {
+    #[inline]
+    fn from(m: glam::DMat2) -> Self {
+        Self {
+            cols: [m.x_axis.into(), m.y_axis.into()],
+        }
+    }
+}
+impl From<glam::DMat3> for DMat3 {
+    #[inline]
+    fn from(m: glam::DMat3) -> Self {
+        Self {
+            cols: [m.x_axis.into(), m.y_axis.into(), m.z_axis.into()],
+        }
+    }
+}
+impl From<glam::DMat4> for DMat4 {
+    #[inline]
+    fn from(m: glam::DMat4) -> Self {
+        Self {
+            cols: [
+                m.x_axis.into(),
+                m.y_axis.into(),
+                m.z_axis.into(),
+                m.w_axis.into(),
+            ],
+        }
+    }
+}
+// Non-square matrices are named rows x columns and stored row-major,
+// so an affine Mat3x4 packs into three float4s
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C, align(16))]
+pub struct Mat3x4 {
+    pub rows: [Float4; 3],
+}
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C, align(16))]
+pub struct Mat4x3 {
+    pub rows: [Float3; 4],
+}
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C, align(16))]
+pub struct Mat2x3 {
+    pub rows: [Float3; 2],
+}
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C, align(8))]
+pub struct Mat3x2 {
+    pub rows: [Float2; 3],
+}
+impl Mat3x4 {
+    pub const fn from_rows(r0: Float4, r1: Float4, r2: Float4) -> Self {
+        Self { rows: [r0, r1, r2] }
+    }
+    pub const fn identity() -> Self {
+        Self::from_rows(
+            Float4::new(1.0, 0.0, 0.0, 0.0),
+            Float4::new(0.0, 1.0, 0.0, 0.0),
+            Float4::new(0.0, 0.0, 1.0, 0.0),
+        )
+    }
+    pub fn transpose(&self) -> Mat4x3 {
+        let r = &self.rows;
+        Mat4x3::from_rows(
+            Float3::new(r[0].x, r[1].x, r[2].x),
+            Float3::new(r[0].y, r[1].y, r[2].y),
+            Float3::new(r[0].z, r[1].z, r[2].z),
+            Float3::new(r[0].w, r[1].w, r[2].w),
+        )
+    }
+    pub fn into_affine3x4(&self) -> [f32; 12] {
+        Mat4::from(*self).into_affine3x4()
+    }
+}
+impl Mat4x3 {
+    pub const fn from_rows(r0: Float3, r1: Float3, r2: Float3, r3: Float3) -> Self {
+        Self {
+            rows: [r0, r1, r2, r3],
+        }
+    }
+    pub fn transpose(&self) -> Mat3x4 {
+        let r = &self.rows;
+        Mat3x4::from_rows(
+            Float4::new(r[0].x, r[1].x, r[2].x, r[3].x),
+            Float4::new(r[0].y, r[1].y, r[2].y, r[3].y),
+            Float4::new(r[0].z, r[1].z, r[2].z, r[3].z),
+        )
+    }
+}
+impl Mat2x3 {
+    pub const fn from_rows(r0: Float3, r1: Float3) -> Self {
+        Self { rows: [r0, r1] }
+    }
+    pub fn transpose(&self) -> Mat3x2 {
+        let r = &self.rows;
+        Mat3x2::from_rows(
+            Float2::new(r[0].x, r[1].x),
+            Float2::new(r[0].y, r[1].y),
+            Float2::new(r[0].z, r[1].z),
+        )
+    }
+}
+impl Mat3x2 {
+    pub const fn from_rows(r0: Float2, r1: Float2, r2: Float2) -> Self {
+        Self { rows: [r0, r1, r2] }
+    }
+    pub fn transpose(&self) -> Mat2x3 {
+        let r = &self.rows;
+        Mat2x3::from_rows(
+            Float3::new(r[0].x, r[1].x, r[2].x),
+            Float3::new(r[0].y, r[1].y, r[2].y),
+        )
+    }
+}
+// Drops the last row, which is (0, 0, 0, 1) for an affine transform
+impl From<Mat4> for Mat3x4 {
+    #[inline]
+    fn from(m: Mat4) -> Self {
+        let c = &m.cols;
+        Self::from_rows(
+            Float4::new(c[0].x, c[1].x, c[2].x, c[3].x),
+            Float4::new(c[0].y, c[1].y, c[2].y, c[3].y),
+            Float4::new(c[0].z, c[1].z, c[2].z, c[3].z),
+        )
+    }
+}
+impl From<Mat3x4> for Mat4 {
+    #[inline]
+    fn from(m: Mat3x4) -> Self {
+        let r = &m.rows;
+        Self::from_cols(
+            Float4::new(r[0].x, r[1].x, r[2].x, 0.0),
+            Float4::new(r[0].y, r[1].y, r[2].y, 0.0),
+            Float4::new(r[0].z, r[1].z, r[2].z, 0.0),
+            Float4::new(r[0].w, r[1].w, r[2].w, 1.0),
+        )
+    }
+}
+impl From<glam::Affine3A> for Mat3x4 {
+    #[inline]
+    fn from(m: glam::Affine3A) -> Self {
+        Mat4::from(glam::Mat4::from(m)).into()
+    }
+}
+impl From<Mat3x4> for glam::Affine3A {
+    #[inline]
+    fn from(m: Mat3x4) -> Self {
+        Self::from_mat4(Mat4::from(m).into())
+    }
+}
 
 macro_rules! impl_proxy_fields {
     ($vec:ident, $proxy:ident, $scalar:ty, x) => {
@@ -496,6 +728,26 @@ impl_vec_proxy!(Float2, Float2Expr, Float2Var, f32, Float32, 2, x, y);
 impl_vec_proxy!(Float3, Float3Expr, Float3Var, f32, Float32, 3, x, y, z);
 impl_vec_proxy!(Float4, Float4Expr, Float4Var, f32, Float32, 4, x, y, z, w);
 
+impl_vec_proxy!(Double2, Double2Expr, Double2Var, f64, Float64, 2, x, y);
+impl_vec_proxy!(Double3, Double3Expr, Double3Var, f64, Float64, 3, x, y, z);
+impl_vec_proxy!(Double4, Double4Expr, Double4Var, f64, Float64, 4, x, y, z, w);
+
+impl_vec_proxy!(Half2, Half2Expr, Half2Var, f16, Float16, 2, x, y);
+impl_vec_proxy!(Half3, Half3Expr, Half3Var, f16, Float16, 3, x, y, z);
+impl_vec_proxy!(Half4, Half4Expr, Half4Var, f16, Float16, 4, x, y, z, w);
+
+impl_vec_proxy!(Ubyte2, Ubyte2Expr, Ubyte2Var, u8, Uint8, 2, x, y);
+impl_vec_proxy!(Ubyte3, Ubyte3Expr, Ubyte3Var, u8, Uint8, 3, x, y, z);
+impl_vec_proxy!(Ubyte4, Ubyte4Expr, Ubyte4Var, u8, Uint8, 4, x, y, z, w);
+
+impl_vec_proxy!(Byte2, Byte2Expr, Byte2Var, u8, Uint8, 2, x, y);
+impl_vec_proxy!(Byte3, Byte3Expr, Byte3Var, u8, Uint8, 3, x, y, z);
+impl_vec_proxy!(Byte4, Byte4Expr, Byte4Var, u8, Uint8, 4, x, y, z, w);
+
+impl_vec_proxy!(Char2, Char2Expr, Char2Var, i8, Int8, 2, x, y);
+impl_vec_proxy!(Char3, Char3Expr, Char3Var, i8, Int8, 3, x, y, z);
+impl_vec_proxy!(Char4, Char4Expr, Char4Var, i8, Int8, 4, x, y, z, w);
+
 impl_vec_proxy!(Ushort2, Ushort2Expr, Ushort2Var, u16, Uint16, 2, x, y);
 impl_vec_proxy!(Ushort3, Ushort3Expr, Ushort3Var, u16, Uint16, 3, x, y, z);
 impl_vec_proxy!(Ushort4, Ushort4Expr, Ushort4Var, u16, Uint16, 4, x, y, z, w);
@@ -524,6 +776,10 @@ impl_mat_proxy!(Mat2, Mat2Expr, Mat2Var, Float2, Float32, 2, x, y);
 impl_mat_proxy!(Mat3, Mat3Expr, Mat3Var, Float3, Float32, 3, x, y, z);
 impl_mat_proxy!(Mat4, Mat4Expr, Mat4Var, Float4, Float32, 4, x, y, z, w);
 
+impl_mat_proxy!(DMat2, DMat2Expr, DMat2Var, Double2, Float64, 2, x, y);
+impl_mat_proxy!(DMat3, DMat3Expr, DMat3Var, Double3, Float64, 3, x, y, z);
+impl_mat_proxy!(DMat4, DMat4Expr, DMat4Var, Double4, Float64, 4, x, y, z, w);
+
 macro_rules! impl_binop {
     ($t:ty, $scalar:ty, $proxy:ty, $tr:ident, $m:ident, $tr_assign:ident, $m_assign:ident) => {
         impl std::ops::$tr_assign<$proxy> for $proxy {
@@ -580,7 +836,10 @@ macro_rules! impl_binop {
 }
 macro_rules! impl_arith_binop {
     ($t:ty, $scalar:ty, $proxy:ty) => {
-        impl_common_op!($t, $scalar, $proxy);
+        impl_arith_binop!($t, $scalar, $proxy, 0.0 as $scalar, 1.0 as $scalar);
+    };
+    ($t:ty, $scalar:ty, $proxy:ty, $zero:expr, $one:expr) => {
+        impl_common_op!($t, $scalar, $proxy, $zero, $one);
         impl_binop!($t, $scalar, $proxy, Add, add, AddAssign, add_assign);
         impl_binop!($t, $scalar, $proxy, Sub, sub, SubAssign, sub_assign);
         impl_binop!($t, $scalar, $proxy, Mul, mul, MulAssign, mul_assign);
@@ -729,7 +988,7 @@ macro_rules! impl_reduce {
     };
 }
 macro_rules! impl_common_op {
-    ($t:ty, $scalar:ty, $proxy:ty) => {
+    ($t:ty, $scalar:ty, $proxy:ty, $zero:expr, $one:expr) => {
         impl $proxy {
             pub fn splat<V: Into<PrimExpr<$scalar>>>(value: V) -> Self {
                 let value = value.into();
@@ -738,10 +997,10 @@ macro_rules! impl_common_op {
                 }))
             }
             pub fn zero() -> Self {
-                Self::splat(0.0 as $scalar)
+                Self::splat($zero)
             }
             pub fn one() -> Self {
-                Self::splat(1.0 as $scalar)
+                Self::splat($one)
             }
         }
     };
@@ -789,6 +1048,30 @@ macro_rules! impl_vec_op {
                     )
                 }))
             }
+            // reflects self about the plane with normal `n`
+            #[inline]
+            pub fn reflect(&self, n: $proxy) -> Self {
+                let d = self.dot(n);
+                *self - n * (d + d)
+            }
+            // refracts the incident direction self through the surface with normal `n`;
+            // returns zero on total internal reflection
+            #[inline]
+            pub fn refract(&self, n: $proxy, eta: Expr<$scalar>) -> Self {
+                let zero = <Expr<$scalar> as FloatVarTrait>::zero();
+                let one = <Expr<$scalar> as FloatVarTrait>::one();
+                let d = n.dot(*self);
+                let k = one - eta * eta * (one - d * d);
+                let r = *self * eta - n * (eta * d + k.max(zero).sqrt());
+                r * select(k.cmplt(zero), zero, one)
+            }
+            // flips self so that it faces against the incident direction `i`
+            #[inline]
+            pub fn faceforward(&self, i: $proxy, nref: $proxy) -> Self {
+                let zero = <Expr<$scalar> as FloatVarTrait>::zero();
+                let one = <Expr<$scalar> as FloatVarTrait>::one();
+                *self * select(nref.dot(i).cmplt(zero), one, -one)
+            }
             #[inline]
             pub fn outer_product(&self, rhs: $proxy) -> Expr<$mat> {
                 Expr::<$mat>::from_node(__current_scope(|s| {
@@ -806,6 +1089,26 @@ impl_arith_binop!(Float2, f32, Float2Expr);
 impl_arith_binop!(Float3, f32, Float3Expr);
 impl_arith_binop!(Float4, f32, Float4Expr);
 
+impl_arith_binop!(Double2, f64, Double2Expr);
+impl_arith_binop!(Double3, f64, Double3Expr);
+impl_arith_binop!(Double4, f64, Double4Expr);
+
+impl_arith_binop!(Half2, f16, Half2Expr, f16::ZERO, f16::ONE);
+impl_arith_binop!(Half3, f16, Half3Expr, f16::ZERO, f16::ONE);
+impl_arith_binop!(Half4, f16, Half4Expr, f16::ZERO, f16::ONE);
+
+impl_arith_binop!(Byte2, u8, Byte2Expr);
+impl_arith_binop!(Byte3, u8, Byte3Expr);
+impl_arith_binop!(Byte4, u8, Byte4Expr);
+
+impl_arith_binop!(Char2, i8, Char2Expr);
+impl_arith_binop!(Char3, i8, Char3Expr);
+impl_arith_binop!(Char4, i8, Char4Expr);
+
+impl_arith_binop!(Ubyte2, u8, Ubyte2Expr);
+impl_arith_binop!(Ubyte3, u8, Ubyte3Expr);
+impl_arith_binop!(Ubyte4, u8, Ubyte4Expr);
+
 impl_arith_binop!(Short2, i16, Short2Expr);
 impl_arith_binop!(Short3, i16, Short3Expr);
 impl_arith_binop!(Short4, i16, Short4Expr);
@@ -830,6 +1133,18 @@ impl_arith_binop!(Ulong2, u64, Ulong2Expr);
 impl_arith_binop!(Ulong3, u64, Ulong3Expr);
 impl_arith_binop!(Ulong4, u64, Ulong4Expr);
 
+impl_int_binop!(Byte2, u8, Byte2Expr);
+impl_int_binop!(Byte3, u8, Byte3Expr);
+impl_int_binop!(Byte4, u8, Byte4Expr);
+
+impl_int_binop!(Char2, i8, Char2Expr);
+impl_int_binop!(Char3, i8, Char3Expr);
+impl_int_binop!(Char4, i8, Char4Expr);
+
+impl_int_binop!(Ubyte2, u8, Ubyte2Expr);
+impl_int_binop!(Ubyte3, u8, Ubyte3Expr);
+impl_int_binop!(Ubyte4, u8, Ubyte4Expr);
+
 impl_int_binop!(Short2, i16, Short2Expr);
 impl_int_binop!(Short3, i16, Short3Expr);
 impl_int_binop!(Short4, i16, Short4Expr);
@@ -890,6 +1205,26 @@ impl_select!(Bool2, Uint2, Uint2Expr);
 impl_select!(Bool3, Uint3, Uint3Expr);
 impl_select!(Bool4, Uint4, Uint4Expr);
 
+impl_select!(Bool2, Double2, Double2Expr);
+impl_select!(Bool3, Double3, Double3Expr);
+impl_select!(Bool4, Double4, Double4Expr);
+
+impl_select!(Bool2, Half2, Half2Expr);
+impl_select!(Bool3, Half3, Half3Expr);
+impl_select!(Bool4, Half4, Half4Expr);
+
+impl_select!(Bool2, Byte2, Byte2Expr);
+impl_select!(Bool3, Byte3, Byte3Expr);
+impl_select!(Bool4, Byte4, Byte4Expr);
+
+impl_select!(Bool2, Char2, Char2Expr);
+impl_select!(Bool3, Char3, Char3Expr);
+impl_select!(Bool4, Char4, Char4Expr);
+
+impl_select!(Bool2, Ubyte2, Ubyte2Expr);
+impl_select!(Bool3, Ubyte3, Ubyte3Expr);
+impl_select!(Bool4, Ubyte4, Ubyte4Expr);
+
 impl_select!(Bool2, Short2, Short2Expr);
 impl_select!(Bool3, Short3, Short3Expr);
 impl_select!(Bool4, Short4, Short4Expr);
@@ -975,6 +1310,26 @@ impl_permute!(Vec2Swizzle, Float2Expr, 2, Float2, Float3, Float4);
 impl_permute!(Vec3Swizzle, Float3Expr, 3, Float2, Float3, Float4);
 impl_permute!(Vec4Swizzle, Float4Expr, 4, Float2, Float3, Float4);
 
+impl_permute!(Vec2Swizzle, Double2Expr, 2, Double2, Double3, Double4);
+impl_permute!(Vec3Swizzle, Double3Expr, 3, Double2, Double3, Double4);
+impl_permute!(Vec4Swizzle, Double4Expr, 4, Double2, Double3, Double4);
+
+impl_permute!(Vec2Swizzle, Half2Expr, 2, Half2, Half3, Half4);
+impl_permute!(Vec3Swizzle, Half3Expr, 3, Half2, Half3, Half4);
+impl_permute!(Vec4Swizzle, Half4Expr, 4, Half2, Half3, Half4);
+
+impl_permute!(Vec2Swizzle, Byte2Expr, 2, Byte2, Byte3, Byte4);
+impl_permute!(Vec3Swizzle, Byte3Expr, 3, Byte2, Byte3, Byte4);
+impl_permute!(Vec4Swizzle, Byte4Expr, 4, Byte2, Byte3, Byte4);
+
+impl_permute!(Vec2Swizzle, Char2Expr, 2, Char2, Char3, Char4);
+impl_permute!(Vec3Swizzle, Char3Expr, 3, Char2, Char3, Char4);
+impl_permute!(Vec4Swizzle, Char4Expr, 4, Char2, Char3, Char4);
+
+impl_permute!(Vec2Swizzle, Ubyte2Expr, 2, Ubyte2, Ubyte3, Ubyte4);
+impl_permute!(Vec3Swizzle, Ubyte3Expr, 3, Ubyte2, Ubyte3, Ubyte4);
+impl_permute!(Vec4Swizzle, Ubyte4Expr, 4, Ubyte2, Ubyte3, Ubyte4);
+
 impl_permute!(Vec2Swizzle, Short2Expr, 2, Short2, Short3, Short4);
 impl_permute!(Vec3Swizzle, Short3Expr, 3, Short2, Short3, Short4);
 impl_permute!(Vec4Swizzle, Short4Expr, 4, Short2, Short3, Short4);
@@ -999,33 +1354,41 @@ impl_permute!(Vec2Swizzle, Ulong2Expr, 2, Ulong2, Ulong3, Ulong4);
 impl_permute!(Vec3Swizzle, Ulong3Expr, 3, Ulong2, Ulong3, Ulong4);
 impl_permute!(Vec4Swizzle, Ulong4Expr, 4, Ulong2, Ulong3, Ulong4);
 
-impl Float3Expr {
-    #[inline]
-    pub fn cross(&self, rhs: Float3Expr) -> Self {
-        Float3Expr::from_node(__current_scope(|s| {
-            s.call(
-                Func::Cross,
-                &[self.node, rhs.node],
-                <Float3 as TypeOf>::type_(),
-            )
-        }))
-    }
+macro_rules! impl_cross {
+    ($t:ty, $proxy:ty) => {
+        impl $proxy {
+            #[inline]
+            pub fn cross(&self, rhs: $proxy) -> Self {
+                <$proxy>::from_node(__current_scope(|s| {
+                    s.call(Func::Cross, &[self.node, rhs.node], <$t as TypeOf>::type_())
+                }))
+            }
+        }
+    };
 }
+impl_cross!(Float3, Float3Expr);
+impl_cross!(Double3, Double3Expr);
 impl_vec_op!(Float2, f32, Float2Expr, Mat2);
 impl_vec_op!(Float3, f32, Float3Expr, Mat3);
 impl_vec_op!(Float4, f32, Float4Expr, Mat4);
+impl_vec_op!(Double2, f64, Double2Expr, DMat2);
+impl_vec_op!(Double3, f64, Double3Expr, DMat3);
+impl_vec_op!(Double4, f64, Double4Expr, DMat4);
 
 macro_rules! impl_var_trait2 {
     ($t:ty, $v:ty) => {
         impl VarTrait for $t {
             type Value = $v;
+            type Char = Char2Expr;
+            type Ubyte = Ubyte2Expr;
+            type Half = Half2Expr;
             type Short = Short2Expr;
             type Ushort = Ushort2Expr;
             type Int = Int2Expr;
             type Uint = Uint2Expr;
             type Float = Float2Expr;
+            type Double = Double2Expr;
             type Bool = Bool2Expr;
-            // type Double = Double2Expr;
             type Long = Long2Expr;
             type Ulong = Ulong2Expr;
         }
@@ -1043,11 +1406,15 @@ macro_rules! impl_var_trait3 {
     ($t:ty, $v:ty) => {
         impl VarTrait for $t {
             type Value = $v;
+            type Char = Char3Expr;
+            type Ubyte = Ubyte3Expr;
+            type Half = Half3Expr;
             type Short = Short3Expr;
             type Ushort = Ushort3Expr;
             type Int = Int3Expr;
             type Uint = Uint3Expr;
             type Float = Float3Expr;
+            type Double = Double3Expr;
             type Bool = Bool3Expr;
             type Long = Long3Expr;
             type Ulong = Ulong3Expr;
@@ -1066,14 +1433,18 @@ macro_rules! impl_var_trait4 {
     ($t:ty, $v:ty) => {
         impl VarTrait for $t {
             type Value = $v;
+            type Char = Char4Expr;
+            type Ubyte = Ubyte4Expr;
+            type Half = Half4Expr;
             type Short = Short4Expr;
             type Ushort = Ushort4Expr;
-            type Int = Int2Expr;
-            type Uint = Uint2Expr;
-            type Float = Float2Expr;
-            type Bool = Bool2Expr;
-            type Long = Long2Expr;
-            type Ulong = Ulong2Expr;
+            type Int = Int4Expr;
+            type Uint = Uint4Expr;
+            type Float = Float4Expr;
+            type Double = Double4Expr;
+            type Bool = Bool4Expr;
+            type Long = Long4Expr;
+            type Ulong = Ulong4Expr;
         }
         impl CommonVarOp for $t {}
         impl VarCmp for $t {}
@@ -1085,7 +1456,12 @@ macro_rules! impl_var_trait4 {
         }
     };
 }
+impl_var_trait2!(Half2Expr, Half2);
 impl_var_trait2!(Float2Expr, Float2);
+impl_var_trait2!(Double2Expr, Double2);
+impl_var_trait2!(Byte2Expr, Byte2);
+impl_var_trait2!(Char2Expr, Char2);
+impl_var_trait2!(Ubyte2Expr, Ubyte2);
 impl_var_trait2!(Short2Expr, Short2);
 impl_var_trait2!(Ushort2Expr, Ushort2);
 impl_var_trait2!(Int2Expr, Int2);
@@ -1094,7 +1470,12 @@ impl_var_trait2!(Bool2Expr, Bool2);
 impl_var_trait2!(Long2Expr, Long2);
 impl_var_trait2!(Ulong2Expr, Ulong2);
 
+impl_var_trait3!(Half3Expr, Half3);
 impl_var_trait3!(Float3Expr, Float3);
+impl_var_trait3!(Double3Expr, Double3);
+impl_var_trait3!(Byte3Expr, Byte3);
+impl_var_trait3!(Char3Expr, Char3);
+impl_var_trait3!(Ubyte3Expr, Ubyte3);
 impl_var_trait3!(Short3Expr, Short3);
 impl_var_trait3!(Ushort3Expr, Ushort3);
 impl_var_trait3!(Int3Expr, Int3);
@@ -1103,7 +1484,12 @@ impl_var_trait3!(Bool3Expr, Bool3);
 impl_var_trait3!(Long3Expr, Long3);
 impl_var_trait3!(Ulong3Expr, Ulong3);
 
+impl_var_trait4!(Half4Expr, Half4);
 impl_var_trait4!(Float4Expr, Float4);
+impl_var_trait4!(Double4Expr, Double4);
+impl_var_trait4!(Byte4Expr, Byte4);
+impl_var_trait4!(Char4Expr, Char4);
+impl_var_trait4!(Ubyte4Expr, Ubyte4);
 impl_var_trait4!(Short4Expr, Short4);
 impl_var_trait4!(Ushort4Expr, Ushort4);
 impl_var_trait4!(Int4Expr, Int4);
@@ -1113,18 +1499,28 @@ impl_var_trait4!(Long4Expr, Long4);
 impl_var_trait4!(Ulong4Expr, Ulong4);
 
 macro_rules! impl_float_trait {
-    ($t:ty) => {
+    ($t:ty, $scalar:ty) => {
         impl From<f32> for $t {
             fn from(v: f32) -> Self {
                 Self::splat(v)
             }
         }
-        impl FloatVarTrait for $t {}
+        impl FloatVarTrait for $t {
+            fn from_f64(v: f64) -> Self {
+                Self::splat(PrimExpr::<$scalar>::from_f64(v))
+            }
+        }
     };
 }
-impl_float_trait!(Float2Expr);
-impl_float_trait!(Float3Expr);
-impl_float_trait!(Float4Expr);
+impl_float_trait!(Half2Expr, f16);
+impl_float_trait!(Half3Expr, f16);
+impl_float_trait!(Half4Expr, f16);
+impl_float_trait!(Float2Expr, f32);
+impl_float_trait!(Float3Expr, f32);
+impl_float_trait!(Float4Expr, f32);
+impl_float_trait!(Double2Expr, f64);
+impl_float_trait!(Double3Expr, f64);
+impl_float_trait!(Double4Expr, f64);
 macro_rules! impl_int_trait {
     ($t:ty) => {
         impl From<i64> for $t {
@@ -1135,6 +1531,15 @@ macro_rules! impl_int_trait {
         impl IntVarTrait for $t {}
     };
 }
+impl_int_trait!(Byte2Expr);
+impl_int_trait!(Byte3Expr);
+impl_int_trait!(Byte4Expr);
+impl_int_trait!(Char2Expr);
+impl_int_trait!(Char3Expr);
+impl_int_trait!(Char4Expr);
+impl_int_trait!(Ubyte2Expr);
+impl_int_trait!(Ubyte3Expr);
+impl_int_trait!(Ubyte4Expr);
 impl_int_trait!(Int2Expr);
 impl_int_trait!(Int3Expr);
 impl_int_trait!(Int4Expr);
@@ -1148,148 +1553,1016 @@ impl_int_trait!(Ulong2Expr);
 impl_int_trait!(Ulong3Expr);
 impl_int_trait!(Ulong4Expr);
 
-impl Mul<Float2Expr> for Mat2Expr {
-    type Output = Float2Expr;
-    #[inline]
-    fn mul(self, rhs: Float2Expr) -> Self::Output {
-        Float2Expr::from_node(__current_scope(|s| {
-            s.call(
-                Func::Mul,
-                &[self.node, rhs.node],
-                <Float2 as TypeOf>::type_(),
-            )
-        }))
-    }
-}
-impl Mat2Expr {
-    pub fn inverse(&self) -> Self {
-        Mat2Expr::from_node(__current_scope(|s| {
-            s.call(Func::Inverse, &[self.node], <Mat2 as TypeOf>::type_())
-        }))
-    }
-    pub fn transpose(&self) -> Self {
-        Mat2Expr::from_node(__current_scope(|s| {
-            s.call(Func::Transpose, &[self.node], <Mat2 as TypeOf>::type_())
-        }))
-    }
-    pub fn determinant(&self) -> Float {
-        FromNode::from_node(__current_scope(|s| {
-            s.call(Func::Determinant, &[self.node], <f32 as TypeOf>::type_())
-        }))
-    }
-}
-impl Mul<Float3Expr> for Mat3Expr {
-    type Output = Float3Expr;
-    #[inline]
-    fn mul(self, rhs: Float3Expr) -> Self::Output {
-        Float3Expr::from_node(__current_scope(|s| {
-            s.call(
-                Func::Mul,
-                &[self.node, rhs.node],
-                <Float3 as TypeOf>::type_(),
-            )
-        }))
-    }
-}
-impl Mat3Expr {
-    pub fn inverse(&self) -> Self {
-        Self::from_node(__current_scope(|s| {
-            s.call(Func::Inverse, &[self.node], <Mat3 as TypeOf>::type_())
-        }))
-    }
-    pub fn transpose(&self) -> Self {
-        Self::from_node(__current_scope(|s| {
-            s.call(Func::Transpose, &[self.node], <Mat3 as TypeOf>::type_())
-        }))
-    }
-    pub fn determinant(&self) -> Float {
-        FromNode::from_node(__current_scope(|s| {
-            s.call(Func::Determinant, &[self.node], <f32 as TypeOf>::type_())
-        }))
-    }
-}
-impl Mul<Float4Expr> for Mat4Expr {
-    type Output = Float4Expr;
-    #[inline]
-    fn mul(self, rhs: Float4Expr) -> Self::Output {
-        Float4Expr::from_node(__current_scope(|s| {
-            s.call(
-                Func::Mul,
-                &[self.node, rhs.node],
-                <Float4 as TypeOf>::type_(),
-            )
-        }))
-    }
-}
-impl Mul for Mat2Expr {
-    type Output = Self;
-    #[inline]
-    fn mul(self, rhs: Self) -> Self::Output {
-        Self::from_node(__current_scope(|s| {
-            s.call(Func::Mul, &[self.node, rhs.node], <Mat2 as TypeOf>::type_())
-        }))
-    }
-}
-impl Mul for Mat3Expr {
-    type Output = Self;
-    #[inline]
-    fn mul(self, rhs: Self) -> Self::Output {
-        Self::from_node(__current_scope(|s| {
-            s.call(Func::Mul, &[self.node, rhs.node], <Mat3 as TypeOf>::type_())
-        }))
-    }
-}
-impl Mul for Mat4Expr {
-    type Output = Self;
-    #[inline]
-    fn mul(self, rhs: Self) -> Self::Output {
-        Self::from_node(__current_scope(|s| {
-            s.call(Func::Mul, &[self.node, rhs.node], <Mat4 as TypeOf>::type_())
-        }))
-    }
-}
-impl Mat4Expr {
-    pub fn inverse(&self) -> Self {
-        Self::from_node(__current_scope(|s| {
-            s.call(Func::Inverse, &[self.node], <Mat4 as TypeOf>::type_())
-        }))
-    }
-    pub fn transpose(&self) -> Self {
-        Self::from_node(__current_scope(|s| {
-            s.call(Func::Transpose, &[self.node], <Mat4 as TypeOf>::type_())
-        }))
-    }
-    pub fn determinant(&self) -> Float {
-        FromNode::from_node(__current_scope(|s| {
-            s.call(Func::Determinant, &[self.node], <f32 as TypeOf>::type_())
-        }))
-    }
-}
-#[inline]
-pub fn make_float2<X: Into<PrimExpr<f32>>, Y: Into<PrimExpr<f32>>>(x: X, y: Y) -> Expr<Float2> {
-    Expr::<Float2>::new(x.into(), y.into())
-}
-#[inline]
-pub fn make_float3<X: Into<PrimExpr<f32>>, Y: Into<PrimExpr<f32>>, Z: Into<PrimExpr<f32>>>(
-    x: X,
-    y: Y,
-    z: Z,
-) -> Expr<Float3> {
-    Expr::<Float3>::new(x.into(), y.into(), z.into())
+macro_rules! impl_mat_op {
+    ($mat:ty, $proxy:ty, $vec:ty, $vec_proxy:ty, $scalar:ty) => {
+        impl Mul<$vec_proxy> for $proxy {
+            type Output = $vec_proxy;
+            #[inline]
+            fn mul(self, rhs: $vec_proxy) -> Self::Output {
+                <$vec_proxy>::from_node(__current_scope(|s| {
+                    s.call(Func::Mul, &[self.node, rhs.node], <$vec as TypeOf>::type_())
+                }))
+            }
+        }
+        impl Mul for $proxy {
+            type Output = Self;
+            #[inline]
+            fn mul(self, rhs: Self) -> Self::Output {
+                Self::from_node(__current_scope(|s| {
+                    s.call(Func::Mul, &[self.node, rhs.node], <$mat as TypeOf>::type_())
+                }))
+            }
+        }
+        impl $proxy {
+            pub fn inverse(&self) -> Self {
+                Self::from_node(__current_scope(|s| {
+                    s.call(Func::Inverse, &[self.node], <$mat as TypeOf>::type_())
+                }))
+            }
+            pub fn transpose(&self) -> Self {
+                Self::from_node(__current_scope(|s| {
+                    s.call(Func::Transpose, &[self.node], <$mat as TypeOf>::type_())
+                }))
+            }
+            pub fn row(&self, index: usize) -> Expr<$vec> {
+                self.transpose().col(index)
+            }
+            pub fn determinant(&self) -> Expr<$scalar> {
+                FromNode::from_node(__current_scope(|s| {
+                    s.call(Func::Determinant, &[self.node], <$scalar as TypeOf>::type_())
+                }))
+            }
+        }
+    };
 }
-#[inline]
-pub fn make_float4<
-    X: Into<PrimExpr<f32>>,
-    Y: Into<PrimExpr<f32>>,
-    Z: Into<PrimExpr<f32>>,
-    W: Into<PrimExpr<f32>>,
->(
-    x: X,
-    y: Y,
-    z: Z,
-    w: W,
-) -> Expr<Float4> {
-    Expr::<Float4>::new(x.into(), y.into(), z.into(), w.into())
+impl_mat_op!(Mat2, Mat2Expr, Float2, Float2Expr, f32);
+impl_mat_op!(Mat3, Mat3Expr, Float3, Float3Expr, f32);
+impl_mat_op!(Mat4, Mat4Expr, Float4, Float4Expr, f32);
+impl_mat_op!(DMat2, DMat2Expr, Double2, Double2Expr, f64);
+impl_mat_op!(DMat3, DMat3Expr, Double3, Double3Expr, f64);
+impl_mat_op!(DMat4, DMat4Expr, Double4, Double4Expr, f64);
+
+macro_rules! impl_rect_mat_proxy {
+    ($mat:ident, $expr_proxy:ident, $var_proxy:ident, $row:ty, $col:ty, $transposed:ty, $n_rows:literal, $n_cols:literal) => {
+        #[derive(Clone, Copy)]
+        pub struct $expr_proxy {
+            node: NodeRef,
+        }
+        #[derive(Clone, Copy)]
+        pub struct $var_proxy {
+            node: NodeRef,
+        }
+        impl Value for $mat {
+            type Expr = $expr_proxy;
+            type Var = $var_proxy;
+            fn fields() -> Vec<String> {
+                (0..$n_rows).map(|i| format!("row{}", i)).collect()
+            }
+        }
+        // the IR only has square matrices, so the rows are lowered as struct fields
+        impl TypeOf for $mat {
+            fn type_() -> luisa_compute_ir::CArc<luisa_compute_ir::ir::Type> {
+                let type_ = Type::Struct(StructType {
+                    fields: CBoxedSlice::new(vec![<$row as TypeOf>::type_(); $n_rows]),
+                    size: std::mem::size_of::<$mat>(),
+                    alignment: std::mem::align_of::<$mat>(),
+                });
+                register_type(type_)
+            }
+        }
+        impl Aggregate for $expr_proxy {
+            fn to_nodes(&self, nodes: &mut Vec<NodeRef>) {
+                nodes.push(self.node);
+            }
+            fn from_nodes<I: Iterator<Item = NodeRef>>(iter: &mut I) -> Self {
+                Self {
+                    node: iter.next().unwrap(),
+                }
+            }
+        }
+        impl Aggregate for $var_proxy {
+            fn to_nodes(&self, nodes: &mut Vec<NodeRef>) {
+                nodes.push(self.node);
+            }
+            fn from_nodes<I: Iterator<Item = NodeRef>>(iter: &mut I) -> Self {
+                Self {
+                    node: iter.next().unwrap(),
+                }
+            }
+        }
+        impl FromNode for $expr_proxy {
+            fn from_node(node: NodeRef) -> Self {
+                Self { node }
+            }
+            fn node(&self) -> NodeRef {
+                self.node
+            }
+        }
+        impl ExprProxy for $expr_proxy {
+            type Value = $mat;
+        }
+        impl FromNode for $var_proxy {
+            fn from_node(node: NodeRef) -> Self {
+                Self { node }
+            }
+            fn node(&self) -> NodeRef {
+                self.node
+            }
+        }
+        impl VarProxy for $var_proxy {
+            type Value = $mat;
+        }
+        impl From<$var_proxy> for $expr_proxy {
+            fn from(var: $var_proxy) -> Self {
+                var.load()
+            }
+        }
+        impl From<$mat> for $expr_proxy {
+            fn from(m: $mat) -> Self {
+                const_(m)
+            }
+        }
+        impl Mul<Expr<$row>> for $expr_proxy {
+            type Output = Expr<$col>;
+            #[inline]
+            fn mul(self, rhs: Expr<$row>) -> Self::Output {
+                let nodes: Vec<_> = (0..$n_rows)
+                    .map(|i| FromNode::node(&self.row(i).dot(rhs)))
+                    .collect();
+                Expr::<$col>::from_node(__compose::<$col>(&nodes))
+            }
+        }
+        impl $expr_proxy {
+            #[inline]
+            pub fn from_rows(rows: [Expr<$row>; $n_rows]) -> Self {
+                let nodes: Vec<_> = rows.iter().map(|r| FromNode::node(r)).collect();
+                Self {
+                    node: __compose::<$mat>(&nodes),
+                }
+            }
+            pub fn row(&self, index: usize) -> Expr<$row> {
+                Expr::<$row>::from_node(__extract::<$row>(self.node, index))
+            }
+            pub fn col(&self, index: usize) -> Expr<$col> {
+                let nodes: Vec<_> = (0..$n_rows)
+                    .map(|i| __extract::<f32>(self.row(i).node, index))
+                    .collect();
+                Expr::<$col>::from_node(__compose::<$col>(&nodes))
+            }
+            pub fn transpose(&self) -> Expr<$transposed> {
+                let nodes: Vec<_> = (0..$n_cols).map(|j| FromNode::node(&self.col(j))).collect();
+                Expr::<$transposed>::from_node(__compose::<$transposed>(&nodes))
+            }
+        }
+    };
+}
+impl_rect_mat_proxy!(Mat3x4, Mat3x4Expr, Mat3x4Var, Float4, Float3, Mat4x3, 3, 4);
+impl_rect_mat_proxy!(Mat4x3, Mat4x3Expr, Mat4x3Var, Float3, Float4, Mat3x4, 4, 3);
+impl_rect_mat_proxy!(Mat2x3, Mat2x3Expr, Mat2x3Var, Float3, Float2, Mat3x2, 2, 3);
+impl_rect_mat_proxy!(Mat3x2, Mat3x2Expr, Mat3x2Var, Float2, Float3, Mat2x3, 3, 2);
+
+// (A * B).row(i) = B^T * A.row(i) and (A * B).col(j) = A * B.col(j); a product is
+// assembled from rows when the result is non-square and from columns otherwise
+macro_rules! impl_rect_mat_mul {
+    (rows, $lhs:ty, $rhs:ty, $out:ty, $n:literal) => {
+        impl Mul<$rhs> for $lhs {
+            type Output = Expr<$out>;
+            #[inline]
+            fn mul(self, rhs: $rhs) -> Self::Output {
+                let rhs_t = rhs.transpose();
+                let nodes: Vec<_> = (0..$n)
+                    .map(|i| FromNode::node(&(rhs_t * self.row(i))))
+                    .collect();
+                Expr::<$out>::from_node(__compose::<$out>(&nodes))
+            }
+        }
+    };
+    (cols, $lhs:ty, $rhs:ty, $out:ty, $n:literal) => {
+        impl Mul<$rhs> for $lhs {
+            type Output = Expr<$out>;
+            #[inline]
+            fn mul(self, rhs: $rhs) -> Self::Output {
+                let nodes: Vec<_> = (0..$n)
+                    .map(|j| FromNode::node(&(self * rhs.col(j))))
+                    .collect();
+                Expr::<$out>::from_node(__compose::<$out>(&nodes))
+            }
+        }
+    };
+}
+impl_rect_mat_mul!(rows, Mat3x4Expr, Mat4Expr, Mat3x4, 3);
+impl_rect_mat_mul!(rows, Mat3Expr, Mat3x4Expr, Mat3x4, 3);
+impl_rect_mat_mul!(rows, Mat4x3Expr, Mat3Expr, Mat4x3, 4);
+impl_rect_mat_mul!(rows, Mat4Expr, Mat4x3Expr, Mat4x3, 4);
+impl_rect_mat_mul!(rows, Mat2x3Expr, Mat3Expr, Mat2x3, 2);
+impl_rect_mat_mul!(rows, Mat2Expr, Mat2x3Expr, Mat2x3, 2);
+impl_rect_mat_mul!(rows, Mat3x2Expr, Mat2Expr, Mat3x2, 3);
+impl_rect_mat_mul!(rows, Mat3Expr, Mat3x2Expr, Mat3x2, 3);
+impl_rect_mat_mul!(cols, Mat3x4Expr, Mat4x3Expr, Mat3, 3);
+impl_rect_mat_mul!(cols, Mat4x3Expr, Mat3x4Expr, Mat4, 4);
+impl_rect_mat_mul!(cols, Mat2x3Expr, Mat3x2Expr, Mat2, 2);
+impl_rect_mat_mul!(cols, Mat3x2Expr, Mat2x3Expr, Mat3, 3);
+
+impl Mat3x4Expr {
+    pub fn from_mat4(m: Expr<Mat4>) -> Self {
+        Self::from_rows([m.row(0), m.row(1), m.row(2)])
+    }
+    pub fn to_mat4(&self) -> Expr<Mat4> {
+        let c = |j: usize| {
+            let v = self.col(j);
+            make_float4(v.x(), v.y(), v.z(), if j == 3 { 1.0f32 } else { 0.0f32 })
+        };
+        Mat4Expr::new(c(0), c(1), c(2), c(3))
+    }
+    pub fn linear(&self) -> Expr<Mat3> {
+        Mat3Expr::new(self.col(0), self.col(1), self.col(2))
+    }
+    pub fn translation(&self) -> Expr<Float3> {
+        self.col(3)
+    }
+    pub fn transform_point(&self, p: Expr<Float3>) -> Expr<Float3> {
+        *self * make_float4(p.x(), p.y(), p.z(), 1.0f32)
+    }
+    pub fn transform_vector(&self, v: Expr<Float3>) -> Expr<Float3> {
+        *self * make_float4(v.x(), v.y(), v.z(), 0.0f32)
+    }
+    // Applies `rhs` first, then `self`
+    pub fn compose(&self, rhs: Mat3x4Expr) -> Self {
+        *self * rhs.to_mat4()
+    }
+}
+// Stored as a float4 with the vector part in xyz and the scalar part in w, as in glam
+#[derive(Clone, Copy, Debug)]
+#[repr(C, align(16))]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+impl Quat {
+    pub const fn from_xyzw(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+    pub const fn identity() -> Self {
+        Self::from_xyzw(0.0, 0.0, 0.0, 1.0)
+    }
+}
+impl Default for Quat {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+impl From<Quat> for glam::Quat {
+    #[inline]
+    fn from(q: Quat) -> Self {
+        Self::from_xyzw(q.x, q.y, q.z, q.w)
+    }
+}
+impl From<glam::Quat> for Quat {
+    #[inline]
+    fn from(q: glam::Quat) -> Self {
+        Self::from_xyzw(q.x, q.y, q.z, q.w)
+    }
+}
+#[derive(Clone, Copy)]
+pub struct QuatExpr {
+    node: NodeRef,
+}
+#[derive(Clone, Copy)]
+pub struct QuatVar {
+    node: NodeRef,
+}
+impl Value for Quat {
+    type Expr = QuatExpr;
+    type Var = QuatVar;
+    fn fields() -> Vec<String> {
+        vec!["x", "y", "z", "w"]
+            .into_iter()
+            .map(|f| f.to_string())
+            .collect()
+    }
+}
+impl TypeOf for Quat {
+    fn type_() -> luisa_compute_ir::CArc<luisa_compute_ir::ir::Type> {
+        <Float4 as TypeOf>::type_()
+    }
+}
+impl Aggregate for QuatExpr {
+    fn to_nodes(&self, nodes: &mut Vec<NodeRef>) {
+        nodes.push(self.node);
+    }
+    fn from_nodes<I: Iterator<Item = NodeRef>>(iter: &mut I) -> Self {
+        Self {
+            node: iter.next().unwrap(),
+        }
+    }
+}
+impl Aggregate for QuatVar {
+    fn to_nodes(&self, nodes: &mut Vec<NodeRef>) {
+        nodes.push(self.node);
+    }
+    fn from_nodes<I: Iterator<Item = NodeRef>>(iter: &mut I) -> Self {
+        Self {
+            node: iter.next().unwrap(),
+        }
+    }
+}
+impl FromNode for QuatExpr {
+    fn from_node(node: NodeRef) -> Self {
+        Self { node }
+    }
+    fn node(&self) -> NodeRef {
+        self.node
+    }
+}
+impl FromNode for QuatVar {
+    fn from_node(node: NodeRef) -> Self {
+        Self { node }
+    }
+    fn node(&self) -> NodeRef {
+        self.node
+    }
+}
+impl ExprProxy for QuatExpr {
+    type Value = Quat;
+}
+impl VarProxy for QuatVar {
+    type Value = Quat;
+}
+impl From<QuatVar> for QuatExpr {
+    fn from(var: QuatVar) -> Self {
+        var.load()
+    }
+}
+impl From<Quat> for QuatExpr {
+    fn from(q: Quat) -> Self {
+        const_(q)
+    }
+}
+impl QuatExpr {
+    #[inline]
+    pub fn new(x: Expr<f32>, y: Expr<f32>, z: Expr<f32>, w: Expr<f32>) -> Self {
+        Self::from_float4(Float4Expr::new(x, y, z, w))
+    }
+    #[inline]
+    pub fn from_float4(v: Expr<Float4>) -> Self {
+        Self { node: v.node }
+    }
+    #[inline]
+    pub fn float4(&self) -> Expr<Float4> {
+        Float4Expr::from_node(self.node)
+    }
+    pub fn identity() -> Self {
+        Quat::identity().into()
+    }
+    // `axis` must be normalized
+    pub fn from_axis_angle(axis: Expr<Float3>, angle: Expr<f32>) -> Self {
+        let (s, c) = (angle * 0.5f32).sin_cos();
+        let v = axis * s;
+        Self::new(v.x(), v.y(), v.z(), c)
+    }
+    pub fn x(&self) -> Expr<f32> {
+        self.float4().x()
+    }
+    pub fn y(&self) -> Expr<f32> {
+        self.float4().y()
+    }
+    pub fn z(&self) -> Expr<f32> {
+        self.float4().z()
+    }
+    pub fn w(&self) -> Expr<f32> {
+        self.float4().w()
+    }
+    pub fn xyz(&self) -> Expr<Float3> {
+        let v = self.float4();
+        Float3Expr::new(v.x(), v.y(), v.z())
+    }
+    pub fn dot(&self, rhs: QuatExpr) -> Expr<f32> {
+        self.float4().dot(rhs.float4())
+    }
+    pub fn length(&self) -> Expr<f32> {
+        self.float4().length()
+    }
+    pub fn normalize(&self) -> Self {
+        Self::from_float4(self.float4().normalize())
+    }
+    pub fn conjugate(&self) -> Self {
+        let v = -self.xyz();
+        Self::new(v.x(), v.y(), v.z(), self.w())
+    }
+    pub fn inverse(&self) -> Self {
+        Self::from_float4(self.conjugate().float4() / self.float4().length_squared())
+    }
+    // `self` must be normalized
+    pub fn rotate(&self, v: Expr<Float3>) -> Expr<Float3> {
+        let u = self.xyz();
+        let t = u.cross(v) * 2.0f32;
+        v + t * self.w() + u.cross(t)
+    }
+    // Takes the shorter arc, falling back to normalized lerp when the two are nearly parallel
+    pub fn slerp(&self, rhs: QuatExpr, t: impl Into<Expr<f32>>) -> Self {
+        let t: Expr<f32> = t.into();
+        let cos = self.dot(rhs);
+        let a = self.float4();
+        let b = select(cos.cmplt(0.0f32), -rhs.float4(), rhs.float4());
+        let cos = cos.abs();
+        let theta = cos.min(1.0f32).acos();
+        let sin = theta.sin().max(1e-6f32);
+        let slerp =
+            (a * ((1.0f32 - t) * theta).sin() + b * (t * theta).sin()) / sin;
+        let nlerp = a * (1.0f32 - t) + b * t;
+        Self::from_float4(select(cos.cmpgt(0.9995f32), nlerp, slerp)).normalize()
+    }
+    // `self` must be normalized
+    pub fn to_mat3(&self) -> Expr<Mat3> {
+        let (x, y, z, w) = (self.x(), self.y(), self.z(), self.w());
+        let (x2, y2, z2) = (x + x, y + y, z + z);
+        let (xx, xy, xz) = (x * x2, x * y2, x * z2);
+        let (yy, yz, zz) = (y * y2, y * z2, z * z2);
+        let (wx, wy, wz) = (w * x2, w * y2, w * z2);
+        Mat3Expr::new(
+            make_float3(1.0f32 - (yy + zz), xy + wz, xz - wy),
+            make_float3(xy - wz, 1.0f32 - (xx + zz), yz + wx),
+            make_float3(xz + wy, yz - wx, 1.0f32 - (xx + yy)),
+        )
+    }
+}
+// Hamilton product, `a * b` applies `b` first
+impl Mul for QuatExpr {
+    type Output = QuatExpr;
+    fn mul(self, rhs: QuatExpr) -> Self::Output {
+        let (u, s) = (self.xyz(), self.w());
+        let (v, t) = (rhs.xyz(), rhs.w());
+        let xyz = v * s + u * t + u.cross(v);
+        Self::new(xyz.x(), xyz.y(), xyz.z(), s * t - u.dot(v))
+    }
+}
+impl Mul<Float3Expr> for QuatExpr {
+    type Output = Float3Expr;
+    fn mul(self, rhs: Float3Expr) -> Self::Output {
+        self.rotate(rhs)
+    }
+}
+
+// 3x3 eigen and singular value decompositions.
+// The forward pass runs a fixed number of cyclic Jacobi sweeps, so the generated code is
+// straight-line and can be used inside `autodiff`. It is evaluated on detached inputs and the
+// result is then re-attached with first order perturbation terms that are zero in value, so
+// gradients follow the analytic derivatives. Those terms divide by differences of eigen or
+// singular values; for (nearly) repeated values the basis of the eigenspace is arbitrary and
+// the term is dropped, which gives the correct gradient for any function that does not
+// depend on that choice, e.g. the polar decomposition or a function of the singular values.
+const JACOBI_SWEEPS: usize = 6;
+// relative gap under which two eigen or singular values are treated as repeated
+const DEGENERATE_GAP: f32 = 1e-6;
+type Mat3Elements = [[Expr<f32>; 3]; 3];
+
+fn mat3_elements(m: Expr<Mat3>) -> Mat3Elements {
+    let cols = [m.col(0), m.col(1), m.col(2)];
+    [
+        cols.map(|c| c.x()),
+        cols.map(|c| c.y()),
+        cols.map(|c| c.z()),
+    ]
+}
+fn mat3_from_elements(m: Mat3Elements) -> Expr<Mat3> {
+    let col = |j: usize| make_float3(m[0][j], m[1][j], m[2][j]);
+    Mat3Expr::new(col(0), col(1), col(2))
+}
+fn mat3_identity_elements() -> Mat3Elements {
+    std::array::from_fn(|i| std::array::from_fn(|j| const_(if i == j { 1.0f32 } else { 0.0f32 })))
+}
+// Divides `x` by `gap`, or returns 0 when the gap is below `tolerance`
+fn div_gap(x: Expr<f32>, gap: Expr<f32>, tolerance: Expr<f32>) -> Expr<f32> {
+    let nondegenerate = gap.abs().cmpgt(tolerance);
+    select(
+        nondegenerate,
+        x / select(nondegenerate, gap, const_(1.0f32)),
+        const_(0.0f32),
+    )
+}
+// Applies the rotation [c s; -s c] to columns p and q of `m`
+fn rotate_columns(m: &mut Mat3Elements, p: usize, q: usize, c: Expr<f32>, s: Expr<f32>) {
+    for row in m.iter_mut() {
+        let (mp, mq) = (row[p], row[q]);
+        row[p] = c * mp - s * mq;
+        row[q] = s * mp + c * mq;
+    }
+}
+// Eigenvectors (columns of a rotation) and eigenvalues in decreasing order of a symmetric matrix
+fn jacobi_eigen(mut a: Mat3Elements) -> (Mat3Elements, [Expr<f32>; 3]) {
+    let mut v = mat3_identity_elements();
+    for _ in 0..JACOBI_SWEEPS {
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            // t = tan(theta) of the smaller rotation angle that zeroes a[p][q]
+            let d = a[q][q] - a[p][p];
+            let off = a[p][q] * 2.0f32;
+            let r = (d * d + off * off).sqrt();
+            let off = select(d.cmplt(0.0f32), -off, off);
+            let t = (off / (d.abs() + r).max(f32::MIN_POSITIVE)).clamp(-1.0f32, 1.0f32);
+            let c = (t * t + 1.0f32).rsqrt();
+            let s = t * c;
+            rotate_columns(&mut a, p, q, c, s);
+            let (ap, aq) = (a[p], a[q]);
+            a[p] = std::array::from_fn(|k| c * ap[k] - s * aq[k]);
+            a[q] = std::array::from_fn(|k| s * ap[k] + c * aq[k]);
+            a[p][q] = const_(0.0f32);
+            a[q][p] = const_(0.0f32);
+            rotate_columns(&mut v, p, q, c, s);
+        }
+    }
+    let mut lambda = [a[0][0], a[1][1], a[2][2]];
+    for (i, j) in [(0, 1), (1, 2), (0, 1)] {
+        let swap = lambda[i].cmplt(lambda[j]);
+        let (li, lj) = (lambda[i], lambda[j]);
+        lambda[i] = select(swap, lj, li);
+        lambda[j] = select(swap, li, lj);
+        for row in v.iter_mut() {
+            let (vi, vj) = (row[i], row[j]);
+            row[i] = select(swap, vj, vi);
+            row[j] = select(swap, vi, vj);
+        }
+    }
+    // swaps may have turned v into a reflection
+    let v_mat = mat3_from_elements(v);
+    let flip = v_mat.determinant().cmplt(0.0f32);
+    for row in v.iter_mut() {
+        row[2] = select(flip, -row[2], row[2]);
+    }
+    (v, lambda)
+}
+// Largest absolute entry, or 1 for the zero matrix
+fn mat3_scale(m: &Mat3Elements) -> Expr<f32> {
+    let scale = m
+        .iter()
+        .flatten()
+        .fold(const_(0.0f32), |scale, x| scale.max(x.abs()));
+    select(scale.cmpgt(0.0f32), scale, const_(1.0f32))
+}
+fn svd_forward(a: Mat3Elements) -> (Mat3Elements, [Expr<f32>; 3], Mat3Elements) {
+    let scale = mat3_scale(&a);
+    let a = a.map(|row| row.map(|x| x / scale));
+    let ata: Mat3Elements = std::array::from_fn(|i| {
+        std::array::from_fn(|j| (0..3).fold(const_(0.0f32), |sum, k| sum + a[k][i] * a[k][j]))
+    });
+    let (v, _) = jacobi_eigen(ata);
+    // QR decomposition of A V with Givens rotations, so U is a rotation and only the last
+    // singular value can be negative
+    let mut b: Mat3Elements = std::array::from_fn(|i| {
+        std::array::from_fn(|j| (0..3).fold(const_(0.0f32), |sum, k| sum + a[i][k] * v[k][j]))
+    });
+    let mut u = mat3_identity_elements();
+    for (j, i) in [(0, 1), (0, 2), (1, 2)] {
+        let (x, y) = (b[j][j], b[i][j]);
+        let r = (x * x + y * y).sqrt();
+        let valid = r.cmpgt(1e-30f32);
+        let r = select(valid, r, const_(1.0f32));
+        let c = select(valid, x / r, const_(1.0f32));
+        let s = select(valid, y / r, const_(0.0f32));
+        let (bj, bi) = (b[j], b[i]);
+        b[j] = std::array::from_fn(|k| c * bj[k] + s * bi[k]);
+        b[i] = std::array::from_fn(|k| c * bi[k] - s * bj[k]);
+        rotate_columns(&mut u, j, i, c, -s);
+    }
+    let sigma = [b[0][0] * scale, b[1][1] * scale, b[2][2] * scale];
+    (u, sigma, v)
+}
+impl Mat3Expr {
+    // Eigen-decomposition of the symmetric part of `self`: returns (Q, lambda) with
+    // self = Q diag(lambda) Q^T, Q a rotation and lambda in decreasing order
+    pub fn eigen_symmetric(&self) -> (Expr<Mat3>, Expr<Float3>) {
+        let m = mat3_elements(*self);
+        let s: Mat3Elements =
+            std::array::from_fn(|i| std::array::from_fn(|j| (m[i][j] + m[j][i]) * 0.5f32));
+        let detached = s.map(|row| row.map(detach));
+        let scale = mat3_scale(&detached);
+        let (q, lambda) = jacobi_eigen(detached.map(|row| row.map(|x| x / scale)));
+        let lambda = lambda.map(|l| l * scale);
+        let q = mat3_from_elements(q);
+        let c = mat3_elements(q.transpose() * mat3_from_elements(s) * q);
+        let dc = c.map(|row| row.map(|x| x - detach(x)));
+        let tolerance = lambda[0].abs().max(lambda[2].abs()) * DEGENERATE_GAP;
+        let mut omega = mat3_identity_elements();
+        for (i, j) in [(0, 1), (0, 2), (1, 2)] {
+            let w = div_gap(dc[i][j], lambda[j] - lambda[i], tolerance);
+            omega[i][j] = w;
+            omega[j][i] = -w;
+        }
+        (
+            q * mat3_from_elements(omega),
+            make_float3(
+                lambda[0] + dc[0][0],
+                lambda[1] + dc[1][1],
+                lambda[2] + dc[2][2],
+            ),
+        )
+    }
+    // Returns (U, sigma, V) with self = U diag(sigma) V^T where U and V are rotations and
+    // sigma[0] >= sigma[1] >= |sigma[2]|; sigma[2] is negative when det(self) < 0
+    pub fn svd(&self) -> (Expr<Mat3>, Expr<Float3>, Expr<Mat3>) {
+        let (u, sigma, v) = svd_forward(mat3_elements(detach(*self)));
+        let (u, v) = (mat3_from_elements(u), mat3_from_elements(v));
+        let c = mat3_elements(u.transpose() * *self * v);
+        let dc = c.map(|row| row.map(|x| x - detach(x)));
+        let tolerance = sigma[0] * DEGENERATE_GAP;
+        let mut omega_u = mat3_identity_elements();
+        let mut omega_v = mat3_identity_elements();
+        for (i, j) in [(0, 1), (0, 2), (1, 2)] {
+            let (sum, diff) = (dc[i][j] + dc[j][i], dc[i][j] - dc[j][i]);
+            let sym = div_gap(sum * 0.5f32, sigma[j] - sigma[i], tolerance);
+            let anti = div_gap(diff * 0.5f32, sigma[j] + sigma[i], tolerance);
+            omega_u[i][j] = sym + anti;
+            omega_u[j][i] = -(sym + anti);
+            omega_v[i][j] = sym - anti;
+            omega_v[j][i] = -(sym - anti);
+        }
+        (
+            u * mat3_from_elements(omega_u),
+            make_float3(
+                sigma[0] + dc[0][0],
+                sigma[1] + dc[1][1],
+                sigma[2] + dc[2][2],
+            ),
+            v * mat3_from_elements(omega_v),
+        )
+    }
+    // Returns (R, S) with self = R S, R a rotation and S symmetric. S has a negative
+    // eigenvalue when det(self) < 0, which keeps R a rotation for inverted elements
+    pub fn polar(&self) -> (Expr<Mat3>, Expr<Mat3>) {
+        let (u, sigma, v) = self.svd();
+        let v_sigma = Mat3Expr::new(
+            v.col(0) * sigma.x(),
+            v.col(1) * sigma.y(),
+            v.col(2) * sigma.z(),
+        );
+        (u * v.transpose(), v_sigma * v.transpose())
+    }
+}
+
+// Complex numbers, stored as a float2/double2 with the real part in x and the imaginary part in y
+macro_rules! def_complex {
+    ($name:ident, $expr_proxy:ident, $var_proxy:ident, $vec:ident, $scalar:ty, $align:literal) => {
+        #[derive(Clone, Copy, Debug, Default, PartialEq)]
+        #[repr(C, align($align))]
+        pub struct $name {
+            pub re: $scalar,
+            pub im: $scalar,
+        }
+        impl $name {
+            pub const fn new(re: $scalar, im: $scalar) -> Self {
+                Self { re, im }
+            }
+            pub fn from_polar(r: $scalar, theta: $scalar) -> Self {
+                Self::new(r * theta.cos(), r * theta.sin())
+            }
+            pub fn conj(&self) -> Self {
+                Self::new(self.re, -self.im)
+            }
+            pub fn abs(&self) -> $scalar {
+                self.re.hypot(self.im)
+            }
+            pub fn arg(&self) -> $scalar {
+                self.im.atan2(self.re)
+            }
+        }
+        impl From<$name> for $vec {
+            #[inline]
+            fn from(c: $name) -> Self {
+                Self::new(c.re, c.im)
+            }
+        }
+        impl From<$vec> for $name {
+            #[inline]
+            fn from(v: $vec) -> Self {
+                Self::new(v.x, v.y)
+            }
+        }
+        #[derive(Clone, Copy)]
+        pub struct $expr_proxy {
+            node: NodeRef,
+        }
+        #[derive(Clone, Copy)]
+        pub struct $var_proxy {
+            node: NodeRef,
+        }
+        impl Value for $name {
+            type Expr = $expr_proxy;
+            type Var = $var_proxy;
+            fn fields() -> Vec<String> {
+                vec!["re", "im"]
+                    .into_iter()
+                    .map(|f| f.to_string())
+                    .collect()
+            }
+        }
+        impl TypeOf for $name {
+            fn type_() -> luisa_compute_ir::CArc<luisa_compute_ir::ir::Type> {
+                <$vec as TypeOf>::type_()
+            }
+        }
+        impl Aggregate for $expr_proxy {
+            fn to_nodes(&self, nodes: &mut Vec<NodeRef>) {
+                nodes.push(self.node);
+            }
+            fn from_nodes<I: Iterator<Item = NodeRef>>(iter: &mut I) -> Self {
+                Self {
+                    node: iter.next().unwrap(),
+                }
+            }
+        }
+        impl Aggregate for $var_proxy {
+            fn to_nodes(&self, nodes: &mut Vec<NodeRef>) {
+                nodes.push(self.node);
+            }
+            fn from_nodes<I: Iterator<Item = NodeRef>>(iter: &mut I) -> Self {
+                Self {
+                    node: iter.next().unwrap(),
+                }
+            }
+        }
+        impl FromNode for $expr_proxy {
+            fn from_node(node: NodeRef) -> Self {
+                Self { node }
+            }
+            fn node(&self) -> NodeRef {
+                self.node
+            }
+        }
+        impl FromNode for $var_proxy {
+            fn from_node(node: NodeRef) -> Self {
+                Self { node }
+            }
+            fn node(&self) -> NodeRef {
+                self.node
+            }
+        }
+        impl ExprProxy for $expr_proxy {
+            type Value = $name;
+        }
+        impl VarProxy for $var_proxy {
+            type Value = $name;
+        }
+        impl From<$var_proxy> for $expr_proxy {
+            fn from(var: $var_proxy) -> Self {
+                var.load()
+            }
+        }
+        impl From<$name> for $expr_proxy {
+            fn from(c: $name) -> Self {
+                const_(c)
+            }
+        }
+        impl From<$scalar> for $expr_proxy {
+            fn from(re: $scalar) -> Self {
+                const_($name::new(re, 0.0))
+            }
+        }
+        impl $expr_proxy {
+            #[inline]
+            pub fn new(re: Expr<$scalar>, im: Expr<$scalar>) -> Self {
+                Self::from_vec(Expr::<$vec>::new(re, im))
+            }
+            #[inline]
+            pub fn from_vec(v: Expr<$vec>) -> Self {
+                Self { node: v.node }
+            }
+            #[inline]
+            pub fn vec(&self) -> Expr<$vec> {
+                Expr::<$vec>::from_node(self.node)
+            }
+            pub fn from_real(re: Expr<$scalar>) -> Self {
+                Self::new(re, const_(0.0 as $scalar))
+            }
+            pub fn from_polar(r: Expr<$scalar>, theta: Expr<$scalar>) -> Self {
+                let (s, c) = theta.sin_cos();
+                Self::new(r * c, r * s)
+            }
+            pub fn re(&self) -> Expr<$scalar> {
+                self.vec().x()
+            }
+            pub fn im(&self) -> Expr<$scalar> {
+                self.vec().y()
+            }
+            pub fn conj(&self) -> Self {
+                Self::new(self.re(), -self.im())
+            }
+            pub fn norm_sqr(&self) -> Expr<$scalar> {
+                self.vec().length_squared()
+            }
+            pub fn abs(&self) -> Expr<$scalar> {
+                self.vec().length()
+            }
+            // In (-pi, pi]
+            pub fn arg(&self) -> Expr<$scalar> {
+                self.im().atan2(self.re())
+            }
+            pub fn recip(&self) -> Self {
+                Self::from_vec(self.conj().vec() / self.norm_sqr())
+            }
+            pub fn exp(&self) -> Self {
+                Self::from_polar(self.re().exp(), self.im())
+            }
+            // Principal branch, the result has a non-negative real part. Written without
+            // `(|z| - re) / 2` so small imaginary parts do not cancel
+            pub fn sqrt(&self) -> Self {
+                let (x, y) = (self.re(), self.im());
+                let t = ((x.abs() + self.abs()) * (0.5 as $scalar)).sqrt();
+                let nonzero = t.cmpgt(0.0 as $scalar);
+                let other = select(
+                    nonzero,
+                    y.abs() / select(nonzero, t, const_(1.0 as $scalar)),
+                    const_(0.0 as $scalar),
+                ) * (0.5 as $scalar);
+                select(
+                    x.cmpge(0.0 as $scalar),
+                    Self::new(t, other.copysign(y)),
+                    Self::new(other, t.copysign(y)),
+                )
+            }
+        }
+        impl std::ops::Neg for $expr_proxy {
+            type Output = Self;
+            fn neg(self) -> Self {
+                Self::from_vec(-self.vec())
+            }
+        }
+        impl std::ops::Add for $expr_proxy {
+            type Output = Self;
+            fn add(self, rhs: Self) -> Self {
+                Self::from_vec(self.vec() + rhs.vec())
+            }
+        }
+        impl std::ops::Sub for $expr_proxy {
+            type Output = Self;
+            fn sub(self, rhs: Self) -> Self {
+                Self::from_vec(self.vec() - rhs.vec())
+            }
+        }
+        impl std::ops::Mul for $expr_proxy {
+            type Output = Self;
+            fn mul(self, rhs: Self) -> Self {
+                let (a, b) = (self.re(), self.im());
+                let (c, d) = (rhs.re(), rhs.im());
+                Self::new(a * c - b * d, a * d + b * c)
+            }
+        }
+        impl std::ops::Div for $expr_proxy {
+            type Output = Self;
+            fn div(self, rhs: Self) -> Self {
+                Self::from_vec((self * rhs.conj()).vec() / rhs.norm_sqr())
+            }
+        }
+        impl std::ops::Mul<Expr<$scalar>> for $expr_proxy {
+            type Output = Self;
+            fn mul(self, rhs: Expr<$scalar>) -> Self {
+                Self::from_vec(self.vec() * rhs)
+            }
+        }
+        impl std::ops::Div<Expr<$scalar>> for $expr_proxy {
+            type Output = Self;
+            fn div(self, rhs: Expr<$scalar>) -> Self {
+                Self::from_vec(self.vec() / rhs)
+            }
+        }
+        impl std::ops::Mul<$scalar> for $expr_proxy {
+            type Output = Self;
+            fn mul(self, rhs: $scalar) -> Self {
+                Self::from_vec(self.vec() * rhs)
+            }
+        }
+        impl std::ops::Div<$scalar> for $expr_proxy {
+            type Output = Self;
+            fn div(self, rhs: $scalar) -> Self {
+                Self::from_vec(self.vec() / rhs)
+            }
+        }
+    };
+}
+def_complex!(Complex32, Complex32Expr, Complex32Var, Float2, f32, 8);
+def_complex!(Complex64, Complex64Expr, Complex64Var, Double2, f64, 16);
+
+// Tightly packed 12 byte vectors for storage, e.g. vertex data read from files;
+// convert them to the aligned vector types for arithmetic
+macro_rules! def_packed_vec3 {
+    ($name:ident, $expr_proxy:ident, $vec:ident, $vec_proxy:ident, $scalar:ty) => {
+        #[repr(C)]
+        #[derive(Copy, Clone, Debug, Default, __Value)]
+        pub struct $name {
+            pub x: $scalar,
+            pub y: $scalar,
+            pub z: $scalar,
+        }
+        impl $name {
+            #[inline]
+            pub const fn new(x: $scalar, y: $scalar, z: $scalar) -> Self {
+                Self { x, y, z }
+            }
+        }
+        impl From<$vec> for $name {
+            #[inline]
+            fn from(v: $vec) -> Self {
+                Self::new(v.x, v.y, v.z)
+            }
+        }
+        impl From<$name> for $vec {
+            #[inline]
+            fn from(v: $name) -> Self {
+                Self::new(v.x, v.y, v.z)
+            }
+        }
+        impl $expr_proxy {
+            #[inline]
+            pub fn unpack(&self) -> Expr<$vec> {
+                <$vec_proxy>::new(self.x(), self.y(), self.z())
+            }
+        }
+        impl $vec_proxy {
+            #[inline]
+            pub fn pack(&self) -> Expr<$name> {
+                <$expr_proxy>::new(self.x(), self.y(), self.z())
+            }
+        }
+        impl From<$vec_proxy> for $expr_proxy {
+            #[inline]
+            fn from(v: $vec_proxy) -> Self {
+                v.pack()
+            }
+        }
+        impl From<$expr_proxy> for $vec_proxy {
+            #[inline]
+            fn from(v: $expr_proxy) -> Self {
+                v.unpack()
+            }
+        }
+    };
+}
+def_packed_vec3!(PackedFloat3, PackedFloat3Expr, Float3, Float3Expr, f32);
+def_packed_vec3!(PackedInt3, PackedInt3Expr, Int3, Int3Expr, i32);
+def_packed_vec3!(PackedUint3, PackedUint3Expr, Uint3, Uint3Expr, u32);
+impl From<glam::Vec3> for PackedFloat3 {
+    #[inline]
+    fn from(v: glam::Vec3) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+impl From<PackedFloat3> for glam::Vec3 {
+    #[inline]
+    fn from(v: PackedFloat3) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+#[inline]
+pub fn make_float2<X: Into<PrimExpr<f32>>, Y: Into<PrimExpr<f32>>>(x: X, y: Y) -> Expr<Float2> {
+    Expr::<Float2>::new(x.into(), y.into())
+}
+#[inline]
+pub fn make_float3<X: Into<PrimExpr<f32>>, Y: Into<PrimExpr<f32>>, Z: Into<PrimExpr<f32>>>(
+    x: X,
+    y: Y,
+    z: Z,
+) -> Expr<Float3> {
+    Expr::<Float3>::new(x.into(), y.into(), z.into())
+}
+#[inline]
+pub fn make_float4<
+    X: Into<PrimExpr<f32>>,
+    Y: Into<PrimExpr<f32>>,
+    Z: Into<PrimExpr<f32>>,
+    W: Into<PrimExpr<f32>>,
+>(
+    x: X,
+    y: Y,
+    z: Z,
+    w: W,
+) -> Expr<Float4> {
+    Expr::<Float4>::new(x.into(), y.into(), z.into(), w.into())
+}
+#[inline]
+pub fn make_double2<X: Into<PrimExpr<f64>>, Y: Into<PrimExpr<f64>>>(x: X, y: Y) -> Expr<Double2> {
+    Expr::<Double2>::new(x.into(), y.into())
+}
+#[inline]
+pub fn make_double3<X: Into<PrimExpr<f64>>, Y: Into<PrimExpr<f64>>, Z: Into<PrimExpr<f64>>>(
+    x: X,
+    y: Y,
+    z: Z,
+) -> Expr<Double3> {
+    Expr::<Double3>::new(x.into(), y.into(), z.into())
+}
+#[inline]
+pub fn make_double4<
+    X: Into<PrimExpr<f64>>,
+    Y: Into<PrimExpr<f64>>,
+    Z: Into<PrimExpr<f64>>,
+    W: Into<PrimExpr<f64>>,
+>(
+    x: X,
+    y: Y,
+    z: Z,
+    w: W,
+) -> Expr<Double4> {
+    Expr::<Double4>::new(x.into(), y.into(), z.into(), w.into())
 }
 
 #[inline]
@@ -1360,10 +2633,17 @@ mod test {
                 assert_size!($($rest),*);
             };
         }
-        assert_size!(f32, f64, bool, u16, u32, u64, i16, i32, i64);
+        assert_size!(f16, f32, f64, bool, u8, u16, u32, u64, i8, i16, i32, i64);
+        assert_size!(Half2, Half3, Half4, Byte2, Byte3, Byte4, Ubyte2, Ubyte3, Ubyte4);
+        assert_size!(Char2, Char3, Char4);
         assert_size!(Float2, Float3, Float4, Int2, Int3, Int4, Uint2, Uint3, Uint4);
         assert_size!(Short2, Short3, Short4, Ushort2, Ushort3, Ushort4);
         assert_size!(Long2, Long3, Long4, Ulong2, Ulong3, Ulong4);
         assert_size!(Mat2, Mat3, Mat4);
+        assert_size!(Double2, Double3, Double4, DMat2, DMat3, DMat4);
+        assert_size!(Quat, Complex32, Complex64);
+        assert_size!(Mat3x4, Mat4x3, Mat2x3, Mat3x2);
+        assert_size!(PackedFloat3, PackedInt3, PackedUint3);
+        assert_eq!(std::mem::size_of::<PackedFloat3>(), 12);
     }
 }
@@ -50,6 +50,7 @@ use self::traits::VarCmpEq;
 // use self::math::Uint3;
 pub mod math;
 pub mod poly;
+pub mod rng;
 pub mod swizzle;
 pub mod traits;
 pub use poly::*;
@@ -245,50 +246,65 @@ macro_rules! impl_prim {
 }
 
 impl_prim!(bool);
+impl_prim!(u8);
+impl_prim!(i8);
 impl_prim!(u32);
 impl_prim!(u64);
 impl_prim!(i32);
 impl_prim!(i64);
 impl_prim!(i16);
 impl_prim!(u16);
+impl_prim!(f16);
 impl_prim!(f32);
 impl_prim!(f64);
 
 pub type Bool = PrimExpr<bool>;
+pub type F16 = PrimExpr<f16>;
 pub type F32 = PrimExpr<f32>;
 pub type F64 = PrimExpr<f64>;
+pub type I8 = PrimExpr<i8>;
 pub type I16 = PrimExpr<i16>;
 pub type I32 = PrimExpr<i32>;
 pub type I64 = PrimExpr<i64>;
+pub type U8 = PrimExpr<u8>;
 pub type U16 = PrimExpr<u16>;
 pub type U32 = PrimExpr<u32>;
 pub type U64 = PrimExpr<u64>;
 
+pub type F16Var = PrimVar<f16>;
 pub type F32Var = PrimVar<f32>;
 pub type F64Var = PrimVar<f64>;
+pub type I8Var = PrimVar<i8>;
 pub type I16Var = PrimVar<i16>;
 pub type I32Var = PrimVar<i32>;
 pub type I64Var = PrimVar<i64>;
+pub type U8Var = PrimVar<u8>;
 pub type U16Var = PrimVar<u16>;
 pub type U32Var = PrimVar<u32>;
 pub type U64Var = PrimVar<u64>;
 
+pub type Half = PrimExpr<f16>;
 pub type Float = PrimExpr<f32>;
 pub type Double = PrimExpr<f64>;
 pub type Int = PrimExpr<i32>;
 pub type Long = PrimExpr<i64>;
 pub type Uint = PrimExpr<u32>;
 pub type Ulong = PrimExpr<u64>;
+pub type Char = PrimExpr<i8>;
+pub type Ubyte = PrimExpr<u8>;
 pub type Short = PrimExpr<i16>;
 pub type Ushort = PrimExpr<u16>;
 
 pub type BoolVar = PrimVar<bool>;
+pub type HalfVar = PrimVar<f16>;
 pub type FloatVar = PrimVar<f32>;
 pub type DoubleVar = PrimVar<f64>;
 pub type IntVar = PrimVar<i32>;
 pub type LongVar = PrimVar<i64>;
 pub type UintVar = PrimVar<u32>;
 pub type UlongVar = PrimVar<u64>;
+pub type CharVar = PrimVar<i8>;
+pub type UbyteVar = PrimVar<u8>;
 pub type ShortVar = PrimVar<i16>;
 pub type UshortVar = PrimVar<u16>;
 
@@ -1059,6 +1075,66 @@ impl BindlessTex3dVar {
         }))
     }
 }
+pub struct BindlessTex1dVar {
+    tex: BindlessTex2dVar,
+}
+impl BindlessTex1dVar {
+    pub fn sample(&self, u: Expr<f32>) -> Expr<Float4> {
+        self.tex.sample(make_float2(u, 0.5f32))
+    }
+    pub fn sample_level(&self, u: Expr<f32>, level: Expr<f32>) -> Expr<Float4> {
+        self.tex.sample_level(make_float2(u, 0.5f32), level)
+    }
+    pub fn read(&self, x: Expr<u32>) -> Expr<Float4> {
+        self.tex.read(make_uint2(x, 0u32))
+    }
+    pub fn read_level(&self, x: Expr<u32>, level: Expr<u32>) -> Expr<Float4> {
+        self.tex.read_level(make_uint2(x, 0u32), level)
+    }
+    pub fn size(&self) -> Expr<u32> {
+        self.tex.size().x()
+    }
+    pub fn size_level(&self, level: Expr<u32>) -> Expr<u32> {
+        self.tex.size_level(level).x()
+    }
+}
+pub struct BindlessTex2dArrayVar {
+    tex: BindlessTex3dVar,
+}
+impl BindlessTex2dArrayVar {
+    // Samples at the center of the layer so that filtering never blends two layers
+    pub fn sample(&self, uv: Expr<Float2>, layer: Expr<u32>) -> Expr<Float4> {
+        let w = (layer.float() + 0.5f32) / self.layers().float();
+        self.tex.sample(make_float3(uv.x(), uv.y(), w))
+    }
+    pub fn read(&self, coord: Expr<Uint2>, layer: Expr<u32>) -> Expr<Float4> {
+        self.tex.read(make_uint3(coord.x(), coord.y(), layer))
+    }
+    pub fn size(&self) -> Expr<Uint2> {
+        let size = self.tex.size();
+        make_uint2(size.x(), size.y())
+    }
+    pub fn layers(&self) -> Expr<u32> {
+        self.tex.size().z()
+    }
+}
+pub struct BindlessTexCubeVar {
+    tex: BindlessTex3dVar,
+}
+impl BindlessTexCubeVar {
+    // Filtering is done within a face, texels are not blended across face edges
+    pub fn sample(&self, dir: Expr<Float3>) -> Expr<Float4> {
+        let (face, uv) = cube_face_uv(dir);
+        let w = (face.float() + 0.5f32) / 6.0f32;
+        self.tex.sample(make_float3(uv.x(), uv.y(), w))
+    }
+    pub fn read(&self, coord: Expr<Uint2>, face: Expr<u32>) -> Expr<Float4> {
+        self.tex.read(make_uint3(coord.x(), coord.y(), face))
+    }
+    pub fn size(&self) -> Expr<u32> {
+        self.tex.size().x()
+    }
+}
 impl BindlessArrayVar {
     pub fn tex2d(&self, tex2d_index: impl Into<Expr<u32>>) -> BindlessTex2dVar {
         let v = BindlessTex2dVar {
@@ -1074,6 +1150,21 @@ impl BindlessArrayVar {
         };
         v
     }
+    pub fn tex1d(&self, tex1d_index: impl Into<Expr<u32>>) -> BindlessTex1dVar {
+        BindlessTex1dVar {
+            tex: self.tex2d(tex1d_index),
+        }
+    }
+    pub fn tex2d_array(&self, tex2d_array_index: impl Into<Expr<u32>>) -> BindlessTex2dArrayVar {
+        BindlessTex2dArrayVar {
+            tex: self.tex3d(tex2d_array_index),
+        }
+    }
+    pub fn texcube(&self, texcube_index: impl Into<Expr<u32>>) -> BindlessTexCubeVar {
+        BindlessTexCubeVar {
+            tex: self.tex3d(texcube_index),
+        }
+    }
     pub fn buffer<T: Value>(&self, buffer_index: impl Into<Expr<u32>>) -> BindlessBufferVar<T> {
         let v = BindlessBufferVar {
             array: self.node,
@@ -1416,6 +1507,32 @@ impl_atomic_bit!(u32);
 impl_atomic_bit!(u64);
 impl_atomic_bit!(i32);
 impl_atomic_bit!(i64);
+pub struct AppendBufferVar<T: Value> {
+    data: BufferVar<T>,
+    counter: BufferVar<u32>,
+}
+impl<T: Value> AppendBufferVar<T> {
+    pub fn new(buffer: &AppendBuffer<T>) -> Self {
+        Self {
+            data: BufferVar::new(&buffer.data),
+            counter: BufferVar::new(&buffer.counter),
+        }
+    }
+    pub fn capacity(&self) -> Expr<u32> {
+        self.data.len()
+    }
+    // Returns the slot of the value, which is dropped if the slot is not below the capacity
+    pub fn push<V: Into<Expr<T>>>(&self, v: V) -> Expr<u32> {
+        let v = v.into();
+        let slot = self.counter.atomic_fetch_add(0u32, 1u32);
+        if_then_else(
+            slot.cmplt(self.capacity()),
+            || self.data.write(slot, v),
+            || {},
+        );
+        slot
+    }
+}
 pub struct Tex2dVar<T: IoTexel> {
     node: NodeRef,
     #[allow(dead_code)]
@@ -1541,6 +1658,114 @@ pub struct Tex3dVar<T: IoTexel> {
     #[allow(dead_code)]
     level: Option<u32>,
 }
+pub struct Tex1dVar<T: IoTexel> {
+    tex: Tex2dVar<T>,
+}
+impl<T: IoTexel> Tex1dVar<T> {
+    pub fn new(view: Tex2dView<'_, T>) -> Self {
+        assert_eq!(view.size()[1], 1, "Tex1dVar<T> requires a texture of height 1");
+        Self {
+            tex: Tex2dVar::new(view),
+        }
+    }
+    pub fn read(&self, x: impl Into<Expr<u32>>) -> Expr<T> {
+        let x: Expr<u32> = x.into();
+        self.tex.read(make_uint2(x, 0u32))
+    }
+    pub fn write(&self, x: impl Into<Expr<u32>>, v: impl Into<Expr<T>>) {
+        let x: Expr<u32> = x.into();
+        self.tex.write(make_uint2(x, 0u32), v)
+    }
+}
+pub struct Tex2dArrayVar<T: IoTexel> {
+    tex: Tex3dVar<T>,
+}
+impl<T: IoTexel> Tex2dArrayVar<T> {
+    pub fn new(view: Tex3dView<'_, T>) -> Self {
+        Self {
+            tex: Tex3dVar::new(view),
+        }
+    }
+    pub fn read(&self, uv: impl Into<Expr<Uint2>>, layer: impl Into<Expr<u32>>) -> Expr<T> {
+        let uv = uv.into();
+        let layer: Expr<u32> = layer.into();
+        self.tex.read(make_uint3(uv.x(), uv.y(), layer))
+    }
+    pub fn write(
+        &self,
+        uv: impl Into<Expr<Uint2>>,
+        layer: impl Into<Expr<u32>>,
+        v: impl Into<Expr<T>>,
+    ) {
+        let uv = uv.into();
+        let layer: Expr<u32> = layer.into();
+        self.tex.write(make_uint3(uv.x(), uv.y(), layer), v)
+    }
+}
+// Reads and writes a single level without filtering, sample through `BindlessTexCubeVar`
+pub struct TexCubeVar<T: IoTexel> {
+    tex: Tex3dVar<T>,
+    size: BufferVar<u32>,
+}
+impl<T: IoTexel> TexCubeVar<T> {
+    pub fn new(cube: &TexCube<T>) -> Self {
+        Self {
+            tex: Tex3dVar::new(cube.view()),
+            size: cube.size.var(),
+        }
+    }
+    // Edge length of a face
+    pub fn size(&self) -> Expr<u32> {
+        self.size.read(0u32)
+    }
+    pub fn read(&self, uv: impl Into<Expr<Uint2>>, face: impl Into<Expr<u32>>) -> Expr<T> {
+        let uv = uv.into();
+        let face: Expr<u32> = face.into();
+        self.tex.read(make_uint3(uv.x(), uv.y(), face))
+    }
+    pub fn write(
+        &self,
+        uv: impl Into<Expr<Uint2>>,
+        face: impl Into<Expr<u32>>,
+        v: impl Into<Expr<T>>,
+    ) {
+        let uv = uv.into();
+        let face: Expr<u32> = face.into();
+        self.tex.write(make_uint3(uv.x(), uv.y(), face), v)
+    }
+    // Nearest texel in direction `dir`
+    pub fn read_dir(&self, dir: Expr<Float3>) -> Expr<T> {
+        let size = self.size();
+        let (face, uv) = cube_face_uv(dir);
+        let max = size - 1u32;
+        let x = (uv.x() * size.float()).uint().min(max);
+        let y = (uv.y() * size.float()).uint().min(max);
+        self.read(make_uint2(x, y), face)
+    }
+}
+// Maps a direction to a cube face and the uv in [0, 1]^2 on that face,
+// faces are ordered +X, -X, +Y, -Y, +Z, -Z and oriented as in D3D/OpenGL
+pub fn cube_face_uv(dir: Expr<Float3>) -> (Expr<u32>, Expr<Float2>) {
+    let (x, y, z) = (dir.x(), dir.y(), dir.z());
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    let x_major = ax.cmpge(ay) & ax.cmpge(az);
+    let y_major = ay.cmpge(az);
+    let face_x = select(x.cmpge(0.0f32), const_(0u32), const_(1u32));
+    let face_y = select(y.cmpge(0.0f32), const_(2u32), const_(3u32));
+    let face_z = select(z.cmpge(0.0f32), const_(4u32), const_(5u32));
+    let face = select(x_major, face_x, select(y_major, face_y, face_z));
+    let sc_x = select(x.cmpge(0.0f32), -z, z);
+    let tc_y = select(y.cmpge(0.0f32), z, -z);
+    let sc_z = select(z.cmpge(0.0f32), x, -x);
+    let sc = select(x_major, sc_x, select(y_major, x, sc_z));
+    let tc = select(x_major, -y, select(y_major, tc_y, -y));
+    let ma = select(x_major, ax, select(y_major, ay, az));
+    let uv = make_float2(
+        (sc / ma + 1.0f32) * 0.5f32,
+        (tc / ma + 1.0f32) * 0.5f32,
+    );
+    (face, uv)
+}
 pub struct AccelVar {
     node: NodeRef,
     #[allow(dead_code)]
@@ -1669,6 +1894,29 @@ impl<T: IoTexel> KernelParameter for Tex3dVar<T> {
         builder.tex3d()
     }
 }
+impl<T: IoTexel> KernelParameter for Tex1dVar<T> {
+    fn def_param(builder: &mut KernelBuilder) -> Self {
+        builder.tex1d()
+    }
+}
+impl<T: IoTexel> KernelParameter for Tex2dArrayVar<T> {
+    fn def_param(builder: &mut KernelBuilder) -> Self {
+        builder.tex2d_array()
+    }
+}
+impl<T: IoTexel> KernelParameter for TexCubeVar<T> {
+    fn def_param(builder: &mut KernelBuilder) -> Self {
+        builder.texcube()
+    }
+}
+impl<T: Value> KernelParameter for AppendBufferVar<T> {
+    fn def_param(builder: &mut KernelBuilder) -> Self {
+        Self {
+            data: builder.buffer(),
+            counter: builder.buffer(),
+        }
+    }
+}
 impl KernelParameter for BindlessArrayVar {
     fn def_param(builder: &mut KernelBuilder) -> Self {
         builder.bindless_array()
@@ -1756,6 +2004,18 @@ impl KernelBuilder {
             level: None,
         }
     }
+    pub fn tex1d<T: IoTexel>(&mut self) -> Tex1dVar<T> {
+        Tex1dVar { tex: self.tex2d() }
+    }
+    pub fn tex2d_array<T: IoTexel>(&mut self) -> Tex2dArrayVar<T> {
+        Tex2dArrayVar { tex: self.tex3d() }
+    }
+    pub fn texcube<T: IoTexel>(&mut self) -> TexCubeVar<T> {
+        TexCubeVar {
+            tex: self.tex3d(),
+            size: self.buffer(),
+        }
+    }
     pub fn bindless_array(&mut self) -> BindlessArrayVar {
         let node = new_node(
             __module_pools(),
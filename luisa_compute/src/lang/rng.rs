@@ -0,0 +1,359 @@
+// Random number generators and low-discrepancy sequences.
+// Every generator has a host-side reference that produces the same stream as
+// the kernel version, so results can be checked on the host.
+// (named `rng` so that it does not shadow the `rand` crate under `use luisa_compute::*`)
+use crate::*;
+use luisa_compute_derive::__Value;
+
+use super::traits::CommonVarOp;
+
+const PCG32_MULT: u64 = 6364136223846793005;
+const PHILOX_M0: u32 = 0xD2511F53;
+const PHILOX_M1: u32 = 0xCD9E8D57;
+const PHILOX_W0: u32 = 0x9E3779B9;
+const PHILOX_W1: u32 = 0xBB67AE85;
+const XORSHIFT_DEFAULT_SEED: u32 = 0x9E3779B9;
+const ONE_MINUS_EPSILON: f32 = 0.99999994;
+// Number of dimensions supported by `sobol` and `halton`
+pub const SOBOL_DIMENSIONS: usize = 8;
+pub const HALTON_DIMENSIONS: usize = 16;
+const HALTON_PRIMES: [u32; HALTON_DIMENSIONS] =
+    [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+// (s, a, m) from the Joe-Kuo table for dimensions 2..=8, dimension 1 is van der Corput
+const SOBOL_PARAMS: [(u32, u32, [u32; 5]); SOBOL_DIMENSIONS - 1] = [
+    (1, 0, [1, 0, 0, 0, 0]),
+    (2, 1, [1, 3, 0, 0, 0]),
+    (3, 1, [1, 3, 1, 0, 0]),
+    (3, 2, [1, 1, 1, 0, 0]),
+    (4, 1, [1, 1, 3, 3, 0]),
+    (4, 4, [1, 3, 5, 13, 0]),
+    (5, 2, [1, 1, 5, 5, 17]),
+];
+
+#[inline]
+fn u32_to_unit_f32(x: u32) -> f32 {
+    (x >> 8) as f32 * (1.0 / 16777216.0)
+}
+fn u32_to_unit_f32_expr(x: Expr<u32>) -> Expr<f32> {
+    (x >> 8u32).float() * (1.0f32 / 16777216.0f32)
+}
+
+// PCG32 (XSH-RR) with a 64 bit state, as in the reference pcg32_random_r
+#[repr(C)]
+#[derive(Clone, Copy, Debug, __Value)]
+pub struct Pcg32 {
+    pub state: u64,
+    pub inc: u64,
+}
+impl Pcg32 {
+    pub fn new(seed: u64, seq: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (seq << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(PCG32_MULT).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+    pub fn next_f32(&mut self) -> f32 {
+        u32_to_unit_f32(self.next_u32())
+    }
+    pub fn next_float2(&mut self) -> Float2 {
+        let x = self.next_f32();
+        let y = self.next_f32();
+        Float2::new(x, y)
+    }
+}
+impl Pcg32Expr {
+    pub fn new_seeded(seed: Expr<u64>, seq: Expr<u64>) -> Self {
+        let inc = (seq << 1u64) | 1u64;
+        let state = (inc + seed) * PCG32_MULT + inc;
+        Self::new(state, inc)
+    }
+}
+impl Pcg32Var {
+    pub fn next_u32(&self) -> Expr<u32> {
+        let old = self.state().load();
+        self.set_state(old * PCG32_MULT + self.inc().load());
+        let xorshifted = (((old >> 18u64) ^ old) >> 27u64).uint();
+        let rot = (old >> 59u64).uint();
+        (xorshifted >> rot) | (xorshifted << ((32u32 - rot) & 31u32))
+    }
+    pub fn next_f32(&self) -> Expr<f32> {
+        u32_to_unit_f32_expr(self.next_u32())
+    }
+    pub fn next_float2(&self) -> Expr<Float2> {
+        let x = self.next_f32();
+        let y = self.next_f32();
+        make_float2(x, y)
+    }
+}
+
+// Marsaglia's xorshift32, cheap but low quality; the state must never be zero
+#[repr(C)]
+#[derive(Clone, Copy, Debug, __Value)]
+pub struct Xorshift32 {
+    pub state: u32,
+}
+impl Xorshift32 {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            state: if seed == 0 {
+                XORSHIFT_DEFAULT_SEED
+            } else {
+                seed
+            },
+        }
+    }
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+    pub fn next_f32(&mut self) -> f32 {
+        u32_to_unit_f32(self.next_u32())
+    }
+    pub fn next_float2(&mut self) -> Float2 {
+        let x = self.next_f32();
+        let y = self.next_f32();
+        Float2::new(x, y)
+    }
+}
+impl Xorshift32Expr {
+    pub fn new_seeded(seed: Expr<u32>) -> Self {
+        Self::new(select(
+            seed.cmpeq(0u32),
+            const_(XORSHIFT_DEFAULT_SEED),
+            seed,
+        ))
+    }
+}
+impl Xorshift32Var {
+    pub fn next_u32(&self) -> Expr<u32> {
+        let x = self.state().load();
+        let x = x ^ (x << 13u32);
+        let x = x ^ (x >> 17u32);
+        let x = x ^ (x << 5u32);
+        self.set_state(x);
+        x
+    }
+    pub fn next_f32(&self) -> Expr<f32> {
+        u32_to_unit_f32_expr(self.next_u32())
+    }
+    pub fn next_float2(&self) -> Expr<Float2> {
+        let x = self.next_f32();
+        let y = self.next_f32();
+        make_float2(x, y)
+    }
+}
+
+// Counter-based Philox4x32-10; every call to `next_uint4` consumes one counter value
+#[repr(C)]
+#[derive(Clone, Copy, Debug, __Value)]
+pub struct Philox4x32 {
+    pub counter: Uint4,
+    pub key: Uint2,
+}
+impl Philox4x32 {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        Self {
+            counter: Uint4::new(0, 0, stream as u32, (stream >> 32) as u32),
+            key: Uint2::new(seed as u32, (seed >> 32) as u32),
+        }
+    }
+    pub fn next_uint4(&mut self) -> Uint4 {
+        let ret = host::philox4x32(self.counter, self.key);
+        self.counter.x = self.counter.x.wrapping_add(1);
+        if self.counter.x == 0 {
+            self.counter.y = self.counter.y.wrapping_add(1);
+        }
+        ret
+    }
+    pub fn next_u32(&mut self) -> u32 {
+        self.next_uint4().x
+    }
+    pub fn next_f32(&mut self) -> f32 {
+        u32_to_unit_f32(self.next_u32())
+    }
+    pub fn next_float2(&mut self) -> Float2 {
+        let r = self.next_uint4();
+        Float2::new(u32_to_unit_f32(r.x), u32_to_unit_f32(r.y))
+    }
+}
+impl Philox4x32Expr {
+    pub fn new_seeded(seed: Expr<u64>, stream: Expr<u64>) -> Self {
+        Self::new(
+            make_uint4(0u32, 0u32, stream.uint(), (stream >> 32u64).uint()),
+            make_uint2(seed.uint(), (seed >> 32u64).uint()),
+        )
+    }
+}
+impl Philox4x32Var {
+    pub fn next_uint4(&self) -> Expr<Uint4> {
+        let counter = self.counter().load();
+        let ret = philox4x32(counter, self.key().load());
+        let x = counter.x() + 1u32;
+        let y = counter.y() + select(x.cmpeq(0u32), const_(1u32), const_(0u32));
+        self.set_counter(make_uint4(x, y, counter.z(), counter.w()));
+        ret
+    }
+    pub fn next_u32(&self) -> Expr<u32> {
+        self.next_uint4().x()
+    }
+    pub fn next_f32(&self) -> Expr<f32> {
+        u32_to_unit_f32_expr(self.next_u32())
+    }
+    pub fn next_float2(&self) -> Expr<Float2> {
+        let r = self.next_uint4();
+        make_float2(u32_to_unit_f32_expr(r.x()), u32_to_unit_f32_expr(r.y()))
+    }
+}
+fn philox_round(counter: Expr<Uint4>, key: Expr<Uint2>) -> Expr<Uint4> {
+    let p0 = counter.x().ulong() * PHILOX_M0 as u64;
+    let p1 = counter.z().ulong() * PHILOX_M1 as u64;
+    make_uint4(
+        (p1 >> 32u64).uint() ^ counter.y() ^ key.x(),
+        p1.uint(),
+        (p0 >> 32u64).uint() ^ counter.w() ^ key.y(),
+        p0.uint(),
+    )
+}
+pub fn philox4x32(counter: Expr<Uint4>, key: Expr<Uint2>) -> Expr<Uint4> {
+    let mut counter = philox_round(counter, key);
+    let mut key = key;
+    for _ in 1..10 {
+        key = make_uint2(key.x() + PHILOX_W0, key.y() + PHILOX_W1);
+        counter = philox_round(counter, key);
+    }
+    counter
+}
+
+fn sobol_u32(index: Expr<u32>, dim: usize) -> Expr<u32> {
+    let v = host::sobol_directions(dim);
+    let mut r = const_(0u32);
+    for (k, d) in v.iter().enumerate() {
+        r ^= ((index >> k as u32) & 1u32) * *d;
+    }
+    r
+}
+// `dim` selects the (unscrambled) Sobol dimension and must be below SOBOL_DIMENSIONS
+pub fn sobol(index: Expr<u32>, dim: usize) -> Expr<f32> {
+    u32_to_unit_f32_expr(sobol_u32(index, dim))
+}
+// Random digit scrambling by xor, which keeps the sequence stratified
+pub fn sobol_scrambled(index: Expr<u32>, dim: usize, scramble: Expr<u32>) -> Expr<f32> {
+    u32_to_unit_f32_expr(sobol_u32(index, dim) ^ scramble)
+}
+// Radical inverse of `index` in the `dim`-th prime base
+pub fn halton(index: Expr<u32>, dim: usize) -> Expr<f32> {
+    let base = HALTON_PRIMES[dim];
+    let inv_base = 1.0f32 / base as f32;
+    let mut i = index;
+    let mut f = inv_base;
+    let mut r = const_(0.0f32);
+    for _ in 0..host::halton_digits(base) {
+        let next = i / base;
+        let digit = i - next * base;
+        r += digit.float() * f;
+        f *= inv_base;
+        i = next;
+    }
+    r.min(ONE_MINUS_EPSILON)
+}
+
+pub mod host {
+    use super::*;
+    pub fn philox4x32(counter: Uint4, key: Uint2) -> Uint4 {
+        let round = |c: Uint4, k: Uint2| {
+            let p0 = c.x as u64 * PHILOX_M0 as u64;
+            let p1 = c.z as u64 * PHILOX_M1 as u64;
+            Uint4::new(
+                (p1 >> 32) as u32 ^ c.y ^ k.x,
+                p1 as u32,
+                (p0 >> 32) as u32 ^ c.w ^ k.y,
+                p0 as u32,
+            )
+        };
+        let mut counter = round(counter, key);
+        let mut key = key;
+        for _ in 1..10 {
+            key = Uint2::new(key.x.wrapping_add(PHILOX_W0), key.y.wrapping_add(PHILOX_W1));
+            counter = round(counter, key);
+        }
+        counter
+    }
+    pub fn sobol_directions(dim: usize) -> [u32; 32] {
+        assert!(
+            dim < SOBOL_DIMENSIONS,
+            "sobol dimension {} is out of range",
+            dim
+        );
+        let mut v = [0u32; 32];
+        if dim == 0 {
+            for (k, d) in v.iter_mut().enumerate() {
+                *d = 1 << (31 - k);
+            }
+            return v;
+        }
+        let (s, a, m) = SOBOL_PARAMS[dim - 1];
+        let s = s as usize;
+        for (k, d) in v.iter_mut().take(s).enumerate() {
+            *d = m[k] << (31 - k);
+        }
+        for k in s..32 {
+            v[k] = v[k - s] ^ (v[k - s] >> s);
+            for j in 1..s {
+                v[k] ^= ((a >> (s - 1 - j)) & 1) * v[k - j];
+            }
+        }
+        v
+    }
+    pub fn sobol_u32(index: u32, dim: usize) -> u32 {
+        let v = sobol_directions(dim);
+        v.iter()
+            .enumerate()
+            .fold(0, |r, (k, d)| r ^ (((index >> k) & 1) * d))
+    }
+    pub fn sobol(index: u32, dim: usize) -> f32 {
+        u32_to_unit_f32(sobol_u32(index, dim))
+    }
+    pub fn sobol_scrambled(index: u32, dim: usize, scramble: u32) -> f32 {
+        u32_to_unit_f32(sobol_u32(index, dim) ^ scramble)
+    }
+    // Number of base `base` digits needed to represent any u32
+    pub(crate) fn halton_digits(base: u32) -> usize {
+        let mut n = u32::MAX;
+        let mut digits = 0;
+        while n > 0 {
+            n /= base;
+            digits += 1;
+        }
+        digits
+    }
+    pub fn halton(index: u32, dim: usize) -> f32 {
+        let base = HALTON_PRIMES[dim];
+        let inv_base = 1.0f32 / base as f32;
+        let mut i = index;
+        let mut f = inv_base;
+        let mut r = 0.0f32;
+        for _ in 0..halton_digits(base) {
+            let next = i / base;
+            let digit = i - next * base;
+            r += digit as f32 * f;
+            f *= inv_base;
+            i = next;
+        }
+        r.min(ONE_MINUS_EPSILON)
+    }
+}
@@ -9,6 +9,9 @@ use std::ops::*;
 use super::Expr;
 pub trait VarTrait: Copy + Clone + 'static + FromNode {
     type Value: Value;
+    type Char: VarTrait;
+    type Ubyte: VarTrait;
+    type Half: VarTrait;
     type Short: VarTrait;
     type Ushort: VarTrait;
     type Int: VarTrait;
@@ -16,7 +19,7 @@ pub trait VarTrait: Copy + Clone + 'static + FromNode {
     type Long: VarTrait;
     type Ulong: VarTrait;
     type Float: VarTrait;
-    // type Double: VarTrait;
+    type Double: VarTrait;
     type Bool: VarTrait + Not<Output = Self::Bool>;
     fn type_() -> CArc<Type> {
         <Self::Value as TypeOf>::type_()
@@ -26,6 +29,9 @@ macro_rules! impl_var_trait {
     ($t:ty) => {
         impl VarTrait for PrimExpr<$t> {
             type Value = $t;
+            type Char = Expr<i8>;
+            type Ubyte = Expr<u8>;
+            type Half = Expr<f16>;
             type Short = Expr<i16>;
             type Ushort = Expr<u16>;
             type Int = Expr<i32>;
@@ -33,13 +39,16 @@ macro_rules! impl_var_trait {
             type Long = Expr<i64>;
             type Ulong = Expr<u64>;
             type Float = Expr<f32>;
-            // type Double = Expr<f64>;
+            type Double = Expr<f64>;
             type Bool = Expr<bool>;
         }
     };
 }
+impl_var_trait!(f16);
 impl_var_trait!(f32);
 impl_var_trait!(f64);
+impl_var_trait!(i8);
+impl_var_trait!(u8);
 impl_var_trait!(i16);
 impl_var_trait!(u16);
 impl_var_trait!(i32);
@@ -116,15 +125,24 @@ pub trait CommonVarOp: VarTrait {
     fn float(&self) -> Self::Float {
         self._cast()
     }
+    fn half(&self) -> Self::Half {
+        self._cast()
+    }
+    fn char(&self) -> Self::Char {
+        self._cast()
+    }
+    fn ubyte(&self) -> Self::Ubyte {
+        self._cast()
+    }
     fn short(&self) -> Self::Short {
         self._cast()
     }
     fn ushort(&self) -> Self::Ushort {
         self._cast()
     }
-    // fn double(&self) -> Self::Double {
-    //     self._cast()
-    // }
+    fn double(&self) -> Self::Double {
+        self._cast()
+    }
     fn bool_(&self) -> Self::Bool {
         self._cast()
     }
@@ -233,6 +251,30 @@ pub trait IntVarTrait:
             Self::from_node(ret)
         })
     }
+    fn popcount(&self) -> Self {
+        __current_scope(|s| {
+            let ret = s.call(Func::PopCount, &[self.node()], Self::type_());
+            Self::from_node(ret)
+        })
+    }
+    fn clz(&self) -> Self {
+        __current_scope(|s| {
+            let ret = s.call(Func::Clz, &[self.node()], Self::type_());
+            Self::from_node(ret)
+        })
+    }
+    fn ctz(&self) -> Self {
+        __current_scope(|s| {
+            let ret = s.call(Func::Ctz, &[self.node()], Self::type_());
+            Self::from_node(ret)
+        })
+    }
+    fn reverse_bits(&self) -> Self {
+        __current_scope(|s| {
+            let ret = s.call(Func::Reverse, &[self.node()], Self::type_());
+            Self::from_node(ret)
+        })
+    }
 }
 pub trait FloatVarTrait:
     VarTrait
@@ -253,6 +295,8 @@ pub trait FloatVarTrait:
     + From<Self::Value>
     + From<f32>
 {
+    // An f64 constant rounded to the precision of Self
+    fn from_f64(v: f64) -> Self;
     fn one() -> Self {
         Self::from(1.0f32)
     }
@@ -476,6 +520,83 @@ pub trait FloatVarTrait:
     fn sin_cos(&self) -> (Self, Self) {
         (self.sin(), self.cos())
     }
+    fn saturate(&self) -> Self {
+        self.clamp(Self::zero(), Self::one())
+    }
+    // self + (other - self) * t, i.e. GLSL mix
+    fn lerp<A: Into<Self>, B: Into<Self>>(&self, other: A, t: B) -> Self {
+        let x = self.clone();
+        x.clone() + (other.into() - x) * t.into()
+    }
+    // 0 if self < edge, 1 otherwise
+    fn step<A: Into<Self>>(&self, edge: A) -> Self {
+        __select(self.cmplt(edge), Self::zero(), Self::one())
+    }
+    fn smoothstep<A: Into<Self>, B: Into<Self>>(&self, edge0: A, edge1: B) -> Self {
+        let edge0: Self = edge0.into();
+        let t = ((self.clone() - edge0.clone()) / (edge1.into() - edge0)).saturate();
+        t.clone() * t.clone() * (Self::from(3.0f32) - Self::from(2.0f32) * t)
+    }
+    fn radians(&self) -> Self {
+        self.clone() * Self::from_f64(std::f64::consts::PI / 180.0)
+    }
+    fn degrees(&self) -> Self {
+        self.clone() * Self::from_f64(180.0 / std::f64::consts::PI)
+    }
+    fn sign(&self) -> Self {
+        __select(
+            self.cmpgt(Self::zero()),
+            Self::one(),
+            __select(self.cmplt(Self::zero()), -Self::one(), Self::zero()),
+        )
+    }
+    // Remainder with the sign of self, as in C fmod
+    fn fmod<A: Into<Self>>(&self, other: A) -> Self {
+        let other: Self = other.into();
+        self.clone() - other.clone() * (self.clone() / other).trunc()
+    }
+    fn ldexp(&self, exp: Self::Int) -> Self
+    where
+        Self::Int: CommonVarOp,
+    {
+        // exp2 alone overflows past the exponent range of the type (e.g. 127 for f32),
+        // scaling in two halves covers every exponent with a finite, nonzero result
+        let exp = exp._cast::<Self>();
+        let half = (exp.clone() * Self::from(0.5f32)).floor();
+        self.clone() * half.clone().exp2() * (exp - half).exp2()
+    }
+    // Splits self into a mantissa in [0.5, 1) and a power of two exponent
+    fn frexp(&self) -> (Self, Self::Int) {
+        let x = self.clone();
+        let is_zero = x.cmpeq(Self::zero());
+        let e = x.abs().log2().floor() + Self::one();
+        let e = __select(is_zero, Self::zero(), e);
+        // for subnormals 2^-e is past the exponent range, so scale in two halves as in ldexp
+        let half = (-e.clone() * Self::from(0.5f32)).floor();
+        let m = x * half.clone().exp2() * (-e.clone() - half).exp2();
+        // log2 may round across a power of two
+        let too_big = m.abs().cmpge(Self::one());
+        let (m, e) = (
+            __select(too_big, m.clone() * Self::from(0.5f32), m),
+            __select(too_big, e.clone() + Self::one(), e),
+        );
+        let too_small = m.abs().cmplt(Self::from(0.5f32));
+        let (m, e) = (
+            __select(too_small, m.clone() * Self::from(2.0f32), m),
+            __select(too_small, e.clone() - Self::one(), e),
+        );
+        (m, __select(is_zero, Self::zero(), e)._cast())
+    }
+}
+fn __select<T: VarTrait>(mask: T::Bool, a: T, b: T) -> T {
+    __current_scope(|s| {
+        let ret = s.call(
+            Func::Select,
+            &[mask.node(), a.node(), b.node()],
+            T::type_(),
+        );
+        T::from_node(ret)
+    })
 }
 macro_rules! impl_binop {
     ($t:ty, $proxy:ty, $tr_assign:ident, $method_assign:ident, $tr:ident, $method:ident) => {
@@ -590,8 +711,11 @@ impl Not for PrimExpr<bool> {
         })
     }
 }
+impl_common_binop!(f16, PrimExpr<f16>);
 impl_common_binop!(f32, PrimExpr<f32>);
 impl_common_binop!(f64, PrimExpr<f64>);
+impl_common_binop!(i8, PrimExpr<i8>);
+impl_common_binop!(u8, PrimExpr<u8>);
 impl_common_binop!(i32, PrimExpr<i32>);
 impl_common_binop!(i64, PrimExpr<i64>);
 impl_common_binop!(u32, PrimExpr<u32>);
@@ -621,39 +745,55 @@ impl_binop!(
     BitXor,
     bitxor
 );
+impl_int_binop!(i8, PrimExpr<i8>);
+impl_int_binop!(u8, PrimExpr<u8>);
 impl_int_binop!(i32, PrimExpr<i32>);
 impl_int_binop!(i64, PrimExpr<i64>);
 impl_int_binop!(u32, PrimExpr<u32>);
 impl_int_binop!(u64, PrimExpr<u64>);
 
+impl_not!(i8, PrimExpr<i8>);
+impl_not!(u8, PrimExpr<u8>);
 impl_not!(i32, PrimExpr<i32>);
 impl_not!(i64, PrimExpr<i64>);
 impl_not!(u32, PrimExpr<u32>);
 impl_not!(u64, PrimExpr<u64>);
 
+impl_neg!(i8, PrimExpr<i8>);
+impl_neg!(u8, PrimExpr<u8>);
 impl_neg!(i32, PrimExpr<i32>);
 impl_neg!(i64, PrimExpr<i64>);
 impl_neg!(u32, PrimExpr<u32>);
 impl_neg!(u64, PrimExpr<u64>);
 
+impl_fneg!(f16, PrimExpr<f16>);
 impl_fneg!(f32, PrimExpr<f32>);
+impl VarCmpEq for PrimExpr<f16> {}
 impl VarCmpEq for PrimExpr<f32> {}
-// impl VarCmp for PrimExpr<f64> {}
+impl VarCmpEq for PrimExpr<f64> {}
+impl VarCmpEq for PrimExpr<i8> {}
+impl VarCmpEq for PrimExpr<u8> {}
 impl VarCmpEq for PrimExpr<i32> {}
 impl VarCmpEq for PrimExpr<i64> {}
 impl VarCmpEq for PrimExpr<u32> {}
 impl VarCmpEq for PrimExpr<u64> {}
 
-// impl_fneg!(f64, PrimExpr<f64>);
+impl_fneg!(f64, PrimExpr<f64>);
+impl VarCmp for PrimExpr<f16> {}
 impl VarCmp for PrimExpr<f32> {}
-// impl VarCmp for PrimExpr<f64> {}
+impl VarCmp for PrimExpr<f64> {}
+impl VarCmp for PrimExpr<i8> {}
+impl VarCmp for PrimExpr<u8> {}
 impl VarCmp for PrimExpr<i32> {}
 impl VarCmp for PrimExpr<i64> {}
 impl VarCmp for PrimExpr<u32> {}
 impl VarCmp for PrimExpr<u64> {}
 impl VarCmpEq for PrimExpr<bool> {}
+impl CommonVarOp for PrimExpr<f16> {}
 impl CommonVarOp for PrimExpr<f32> {}
-// impl CommonVarOp for PrimExpr<f64> {}
+impl CommonVarOp for PrimExpr<f64> {}
+impl CommonVarOp for PrimExpr<i8> {}
+impl CommonVarOp for PrimExpr<u8> {}
 impl CommonVarOp for PrimExpr<i32> {}
 impl CommonVarOp for PrimExpr<i64> {}
 impl CommonVarOp for PrimExpr<u32> {}
@@ -666,7 +806,36 @@ impl From<f64> for Float {
     }
 }
 
-impl FloatVarTrait for PrimExpr<f32> {}
+// Half literals are written as f32 and rounded
+impl From<f32> for PrimExpr<f16> {
+    fn from(x: f32) -> Self {
+        const_(f16::from_f32(x))
+    }
+}
+
+impl FloatVarTrait for PrimExpr<f16> {
+    fn from_f64(v: f64) -> Self {
+        const_(f16::from_f64(v))
+    }
+}
+impl From<f32> for Double {
+    fn from(x: f32) -> Self {
+        (x as f64).into()
+    }
+}
+
+impl FloatVarTrait for PrimExpr<f32> {
+    fn from_f64(v: f64) -> Self {
+        const_(v as f32)
+    }
+}
+impl FloatVarTrait for PrimExpr<f64> {
+    fn from_f64(v: f64) -> Self {
+        const_(v)
+    }
+}
+impl IntVarTrait for PrimExpr<i8> {}
+impl IntVarTrait for PrimExpr<u8> {}
 impl IntVarTrait for PrimExpr<i32> {}
 impl IntVarTrait for PrimExpr<i64> {}
 impl IntVarTrait for PrimExpr<u32> {}
@@ -681,10 +850,14 @@ macro_rules! impl_from {
         }
     };
 }
+impl_from!(i32, i8);
+impl_from!(i32, u8);
 impl_from!(i32, u32);
 impl_from!(i32, i64);
 impl_from!(i32, u64);
 
+impl_from!(i64, i8);
+impl_from!(i64, u8);
 impl_from!(i64, u64);
 impl_from!(i64, i32);
 impl_from!(i64, u32);
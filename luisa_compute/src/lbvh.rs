@@ -0,0 +1,394 @@
+// Linear BVH over points or AABBs, built on the device following Karras,
+// "Maximizing Parallelism in the Construction of BVHs, Octrees, and k-d Trees" (2012):
+// morton codes of the centroids are radix sorted and every internal node finds its
+// key range independently, then the bounds are refitted bottom-up.
+// Nodes 0..n-1 are internal with node 0 as the root, nodes n-1..2n-1 are leaves.
+use crate::algo::{RadixSortByKey, Reduce};
+use crate::*;
+use luisa_compute_derive::__Value;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, __Value)]
+pub struct Aabb {
+    pub min: PackedFloat3,
+    pub max: PackedFloat3,
+}
+impl Aabb {
+    pub fn empty() -> Self {
+        Self {
+            min: PackedFloat3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: PackedFloat3::new(-f32::INFINITY, -f32::INFINITY, -f32::INFINITY),
+        }
+    }
+}
+impl AabbExpr {
+    pub fn union(&self, rhs: AabbExpr) -> AabbExpr {
+        AabbExpr::new(
+            self.min().unpack().min(rhs.min().unpack()).pack(),
+            self.max().unpack().max(rhs.max().unpack()).pack(),
+        )
+    }
+    // Squared distance from `p` to the box, zero inside
+    pub fn distance_squared(&self, p: Expr<Float3>) -> Expr<f32> {
+        let d = (self.min().unpack() - p)
+            .max(p - self.max().unpack())
+            .max(Float3Expr::splat(0.0f32));
+        d.dot(d)
+    }
+}
+
+// Leaves store the primitive index in `left` and u32::MAX in `right`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, __Value)]
+pub struct LbvhNode {
+    pub min: PackedFloat3,
+    pub left: u32,
+    pub max: PackedFloat3,
+    pub right: u32,
+}
+impl LbvhNodeExpr {
+    pub fn aabb(&self) -> AabbExpr {
+        AabbExpr::new(self.min(), self.max())
+    }
+    pub fn is_leaf(&self) -> Expr<bool> {
+        self.right().cmpeq(u32::MAX)
+    }
+}
+
+pub const LBVH_STACK_SIZE: usize = 64;
+
+// Spreads the lower 10 bits of `v` to every third bit
+fn expand_bits(v: Expr<u32>) -> Expr<u32> {
+    let v = (v * 0x00010001u32) & 0xFF0000FFu32;
+    let v = (v * 0x00000101u32) & 0x0F00F00Fu32;
+    let v = (v * 0x00000011u32) & 0xC30C30C3u32;
+    (v * 0x00000005u32) & 0x49249249u32
+}
+
+pub struct LbvhBuilder {
+    device: Device,
+    bounds: Reduce<Aabb>,
+    sort: RadixSortByKey<u32, u32>,
+    point_aabbs: Kernel<(Buffer<Float3>, Buffer<Aabb>)>,
+    morton: Kernel<(Buffer<Aabb>, Buffer<Aabb>, Buffer<u32>, Buffer<u32>)>,
+    leaves: Kernel<(Buffer<Aabb>, Buffer<u32>, Buffer<LbvhNode>)>,
+    hierarchy: Kernel<(Buffer<u32>, Buffer<LbvhNode>, Buffer<u32>)>,
+    refit: Kernel<(Buffer<LbvhNode>, Buffer<u32>, Buffer<u32>)>,
+}
+impl LbvhBuilder {
+    pub fn new(device: &Device) -> backend::Result<Self> {
+        let bounds = Reduce::new(device, Aabb::empty(), |a, b| a.union(b))?;
+        let point_aabbs =
+            device.create_kernel::<(Buffer<Float3>, Buffer<Aabb>)>(&|points, aabbs| {
+                let i = dispatch_id().x();
+                let p = points.read(i).pack();
+                aabbs.write(i, AabbExpr::new(p, p));
+            })?;
+        let morton = device
+            .create_kernel::<(Buffer<Aabb>, Buffer<Aabb>, Buffer<u32>, Buffer<u32>)>(
+                &|aabbs, bounds, codes, indices| {
+                    let i = dispatch_id().x();
+                    let aabb = aabbs.read(i);
+                    let bounds = bounds.read(0u32);
+                    let lo = bounds.min().unpack();
+                    let extent = (bounds.max().unpack() - lo).max(Float3Expr::splat(1e-30f32));
+                    let c = (aabb.min().unpack() + aabb.max().unpack()) * 0.5f32;
+                    let q = (((c - lo) / extent)
+                        .clamp(Float3Expr::splat(0.0f32), Float3Expr::splat(1.0f32))
+                        * 1023.0f32)
+                        .uint();
+                    let code = (expand_bits(q.x()) << 2u32)
+                        | (expand_bits(q.y()) << 1u32)
+                        | expand_bits(q.z());
+                    codes.write(i, code);
+                    indices.write(i, i);
+                },
+            )?;
+        let leaves = device.create_kernel::<(Buffer<Aabb>, Buffer<u32>, Buffer<LbvhNode>)>(
+            &|aabbs, indices, nodes| {
+                let i = dispatch_id().x();
+                let primitive = indices.read(i);
+                let aabb = aabbs.read(primitive);
+                let leaf = i + nodes.len() / 2u32;
+                nodes.write(
+                    leaf,
+                    LbvhNodeExpr::new(aabb.min(), primitive, aabb.max(), u32::MAX),
+                );
+            },
+        )?;
+        let hierarchy = device.create_kernel::<(Buffer<u32>, Buffer<LbvhNode>, Buffer<u32>)>(
+            &|codes, nodes, parents| {
+                let n = codes.len().int();
+                // length of the common prefix of keys a and b, with the index appended to
+                // break ties between equal codes, -1 if b is out of range
+                let delta = |a: Expr<i32>, b: Expr<i32>| -> Expr<i32> {
+                    let in_range = b.cmpge(0i32) & b.cmplt(n);
+                    let a = a.uint();
+                    let b = b.max(0i32).min(n - 1i32).uint();
+                    let x = codes.read(a) ^ codes.read(b);
+                    let d = select(x.cmpeq(0u32), (a ^ b).clz() + 32u32, x.clz());
+                    select(in_range, d.int(), const_(-1i32))
+                };
+                let i = dispatch_id().x().int();
+                let d = select(
+                    delta(i, i + 1i32).cmpgt(delta(i, i - 1i32)),
+                    const_(1i32),
+                    const_(-1i32),
+                );
+                // upper bound of the range length, then binary search the other end
+                let delta_min = delta(i, i - d);
+                let l_max = var!(i32, 2i32);
+                while_!(delta(i, i + l_max.load() * d).cmpgt(delta_min), {
+                    l_max.store(l_max.load() * 2i32);
+                });
+                let l = var!(i32, 0i32);
+                let t = var!(i32, l_max.load() / 2i32);
+                while_!(t.load().cmpgt(0i32), {
+                    if_!(delta(i, i + (l.load() + t.load()) * d).cmpgt(delta_min), {
+                        l.store(l.load() + t.load());
+                    });
+                    t.store(t.load() / 2i32);
+                });
+                let l = l.load();
+                let j = i + l * d;
+                // binary search the split position
+                let delta_node = delta(i, j);
+                let s = var!(i32, 0i32);
+                let div = var!(i32, 2i32);
+                let done = var!(bool, false);
+                while_!(!done.load(), {
+                    let t = (l + div.load() - 1i32) / div.load();
+                    if_!(delta(i, i + (s.load() + t) * d).cmpgt(delta_node), {
+                        s.store(s.load() + t);
+                    });
+                    done.store(t.cmple(1i32));
+                    div.store(div.load() * 2i32);
+                });
+                let gamma = i + s.load() * d + d.min(0i32);
+                let left = select(i.min(j).cmpeq(gamma), gamma + n - 1i32, gamma).uint();
+                let right = select(i.max(j).cmpeq(gamma + 1i32), gamma + n, gamma + 1i32).uint();
+                let empty = Float3Expr::splat(0.0f32).pack();
+                nodes.write(i.uint(), LbvhNodeExpr::new(empty, left, empty, right));
+                parents.write(left, i.uint());
+                parents.write(right, i.uint());
+            },
+        )?;
+        // the second child to finish computes the bounds of its parent
+        let refit = device.create_kernel::<(Buffer<LbvhNode>, Buffer<u32>, Buffer<u32>)>(
+            &|nodes, parents, visits| {
+                let node = var!(u32, dispatch_id().x() + nodes.len() / 2u32);
+                while_!(node.load().cmpne(0u32), {
+                    let parent = parents.read(node.load());
+                    if_!(visits.atomic_fetch_add(parent, 1u32).cmpeq(0u32), {
+                        break_();
+                    });
+                    let p = nodes.read(parent);
+                    let aabb = nodes
+                        .read(p.left())
+                        .aabb()
+                        .union(nodes.read(p.right()).aabb());
+                    nodes.write(
+                        parent,
+                        LbvhNodeExpr::new(aabb.min(), p.left(), aabb.max(), p.right()),
+                    );
+                    node.store(parent);
+                });
+            },
+        )?;
+        Ok(Self {
+            device: device.clone(),
+            bounds,
+            sort: RadixSortByKey::new(device)?,
+            point_aabbs,
+            morton,
+            leaves,
+            hierarchy,
+            refit,
+        })
+    }
+    pub fn build_points(&self, points: &BufferView<Float3>) -> backend::Result<Lbvh> {
+        let aabbs = self.device.create_buffer::<Aabb>(points.len().max(1))?;
+        if !points.is_empty() {
+            self.point_aabbs
+                .dispatch([points.len() as u32, 1, 1], points, &aabbs)?;
+        }
+        self.build_aabbs(&aabbs.view(..points.len() as u64))
+    }
+    pub fn build_aabbs(&self, aabbs: &BufferView<Aabb>) -> backend::Result<Lbvh> {
+        let n = aabbs.len();
+        if n == 0 {
+            let nodes = self.device.create_buffer_from_slice(&[LbvhNode {
+                min: Aabb::empty().min,
+                left: 0,
+                max: Aabb::empty().max,
+                right: u32::MAX,
+            }])?;
+            return Ok(Lbvh { nodes, len: 0 });
+        }
+        let bounds = self.device.create_buffer::<Aabb>(1)?;
+        self.bounds.reduce_to(aabbs, &bounds.view(..))?;
+        let codes = self.device.create_buffer::<u32>(n)?;
+        let indices = self.device.create_buffer::<u32>(n)?;
+        self.morton
+            .dispatch([n as u32, 1, 1], aabbs, &bounds, &codes, &indices)?;
+        self.sort.sort(&codes.view(..), &indices.view(..), 0..30)?;
+
+        let nodes = self.device.create_buffer::<LbvhNode>(2 * n - 1)?;
+        self.leaves
+            .dispatch([n as u32, 1, 1], aabbs, &indices, &nodes)?;
+        if n > 1 {
+            let parents = self.device.create_buffer::<u32>(2 * n - 1)?;
+            let visits = self.device.create_buffer_from_fn(n - 1, |_| 0u32)?;
+            self.hierarchy
+                .dispatch([n as u32 - 1, 1, 1], &codes, &nodes, &parents)?;
+            self.refit
+                .dispatch([n as u32, 1, 1], &nodes, &parents, &visits)?;
+        }
+        Ok(Lbvh { nodes, len: n })
+    }
+}
+
+pub struct Lbvh {
+    nodes: Buffer<LbvhNode>,
+    len: usize,
+}
+impl Lbvh {
+    // Number of primitives
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn nodes(&self) -> &Buffer<LbvhNode> {
+        &self.nodes
+    }
+    pub fn var(&self) -> LbvhVar {
+        LbvhVar {
+            nodes: self.nodes.var(),
+        }
+    }
+}
+impl KernelArg for Lbvh {
+    type Parameter = LbvhVar;
+    fn encode(&self, encoder: &mut ArgEncoder) {
+        encoder.buffer(&self.nodes);
+    }
+}
+impl AsKernelArg<Lbvh> for Lbvh {}
+
+pub struct LbvhVar {
+    nodes: BufferVar<LbvhNode>,
+}
+impl KernelParameter for LbvhVar {
+    fn def_param(builder: &mut KernelBuilder) -> Self {
+        Self {
+            nodes: builder.buffer(),
+        }
+    }
+}
+impl LbvhVar {
+    // Visits the leaves of every subtree whose bounds satisfy `enter`
+    pub fn traverse(
+        &self,
+        enter: impl Fn(AabbExpr) -> Expr<bool>,
+        leaf: impl Fn(Expr<u32>, AabbExpr),
+    ) {
+        let stack = local_zeroed::<[u32; LBVH_STACK_SIZE]>();
+        let top = var!(u32, 1u32);
+        while_!(top.load().cmpgt(0u32), {
+            top.store(top.load() - 1u32);
+            let node = self.nodes.read(stack.read(top.load()));
+            if_!(enter(node.aabb()), {
+                if_!(node.is_leaf(), {
+                    leaf(node.left(), node.aabb());
+                }, else {
+                    // keys are 62 bits, so the depth is at most 62 and each level leaves
+                    // at most one sibling behind: the stack never holds more than 63 entries
+                    assert(top.load().cmple(LBVH_STACK_SIZE as u32 - 2u32));
+                    stack.write(top.load(), node.left());
+                    stack.write(top.load() + 1u32, node.right());
+                    top.store(top.load() + 2u32);
+                });
+            });
+        });
+    }
+    // Calls `f` with every primitive whose bounds are within `radius` of `p`
+    pub fn for_each_in_radius(
+        &self,
+        p: Expr<Float3>,
+        radius: impl Into<Expr<f32>>,
+        f: impl Fn(Expr<u32>),
+    ) {
+        let radius = radius.into();
+        let r2 = radius * radius;
+        self.traverse(
+            |aabb| aabb.distance_squared(p).cmple(r2),
+            |primitive, _| f(primitive),
+        );
+    }
+    // The (up to) K primitives nearest to `p` within `max_radius`, measured to their bounds.
+    // Returns the count and the indices and squared distances in ascending distance order.
+    pub fn knn<const K: usize>(
+        &self,
+        p: Expr<Float3>,
+        max_radius: impl Into<Expr<f32>>,
+    ) -> (Expr<u32>, ArrayExpr<u32, K>, ArrayExpr<f32, K>) {
+        assert!(K > 0, "knn needs K > 0");
+        let max_radius = max_radius.into();
+        let r2 = max_radius * max_radius;
+        let count = var!(u32, 0u32);
+        let indices = local_zeroed::<[u32; K]>();
+        let dist2 = local_zeroed::<[f32; K]>();
+        let accepts = |d2: Expr<f32>| {
+            d2.cmple(r2) & (count.load().cmplt(K as u32) | d2.cmplt(dist2.read(K as u32 - 1)))
+        };
+        self.traverse(
+            |aabb| accepts(aabb.distance_squared(p)),
+            |primitive, aabb| {
+                let d2 = aabb.distance_squared(p);
+                if_!(accepts(d2), {
+                    // insertion sort, dropping the farthest when full
+                    let j = var!(u32, count.load().min(K as u32 - 1));
+                    while_!(
+                        j.load().cmpgt(0u32) & dist2.read(j.load().max(1u32) - 1u32).cmpgt(d2),
+                        {
+                            dist2.write(j.load(), dist2.read(j.load() - 1u32));
+                            indices.write(j.load(), indices.read(j.load() - 1u32));
+                            j.store(j.load() - 1u32);
+                        }
+                    );
+                    dist2.write(j.load(), d2);
+                    indices.write(j.load(), primitive);
+                    count.store((count.load() + 1u32).min(K as u32));
+                });
+            },
+        );
+        (count.load(), indices.load(), dist2.load())
+    }
+}
+
+// Brute force references for the queries
+pub mod host {
+    use crate::*;
+
+    fn distance_squared(a: Float3, b: Float3) -> f32 {
+        let (x, y, z) = (a.x - b.x, a.y - b.y, a.z - b.z);
+        x * x + y * y + z * z
+    }
+    pub fn points_in_radius(points: &[Float3], p: Float3, radius: f32) -> Vec<u32> {
+        (0..points.len() as u32)
+            .filter(|i| distance_squared(points[*i as usize], p) <= radius * radius)
+            .collect()
+    }
+    // (index, squared distance) of the k nearest points, nearest first
+    pub fn knn(points: &[Float3], p: Float3, k: usize, max_radius: f32) -> Vec<(u32, f32)> {
+        let mut found: Vec<(u32, f32)> = points_in_radius(points, p, max_radius)
+            .into_iter()
+            .map(|i| (i, distance_squared(points[i as usize], p)))
+            .collect();
+        found.sort_by(|a, b| a.1.total_cmp(&b.1));
+        found.truncate(k);
+        found
+    }
+}
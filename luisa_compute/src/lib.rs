@@ -1,10 +1,21 @@
 #![allow(unused_unsafe)]
 use std::{any::Any, sync::Arc};
 
+pub mod algo;
+pub mod blas;
+pub mod fft;
+#[cfg(feature = "image")]
+pub mod image_io;
+pub mod hashmap;
+pub mod imaging;
 pub mod lang;
+pub mod lbvh;
 pub mod resource;
 pub mod rtx;
 pub mod runtime;
+pub mod sparse;
+pub mod sparse_grid;
+pub mod tensor;
 pub use half::f16;
 use luisa_compute_api_types as api;
 pub use luisa_compute_backend as backend;
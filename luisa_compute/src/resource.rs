@@ -1,7 +1,7 @@
-use std::any::Any;
+use std::any::{Any, TypeId};
 use std::cell::RefCell;
 use std::cell::UnsafeCell;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::ops::RangeBounds;
 use std::sync::Arc;
 
@@ -10,6 +10,7 @@ use crate::*;
 use api::BufferDownloadCommand;
 use api::BufferUploadCommand;
 use api::INVALID_RESOURCE_HANDLE;
+use lang::AppendBufferVar;
 use lang::BindlessArrayVar;
 use lang::BufferVar;
 use lang::Value;
@@ -42,6 +43,12 @@ impl<'a, T: Value> BufferView<'a, T> {
     pub(crate) fn handle(&self) -> api::Buffer {
         self.buffer.handle()
     }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
     pub fn copy_to_async(&'a self, data: &'a mut [T]) -> Command<'a> {
         assert_eq!(data.len(), self.len);
         let mut rt = ResourceTracker::new();
@@ -189,6 +196,60 @@ impl<T: Value> Clone for Buffer<T> {
         cloned
     }
 }
+// A buffer paired with a device-side counter; kernels append to it with AppendBufferVar::push.
+// Pushes past the capacity are still counted but their values are dropped.
+pub struct AppendBuffer<T: Value> {
+    pub(crate) data: Buffer<T>,
+    pub(crate) counter: Buffer<u32>,
+}
+static APPEND_BUFFER_ZERO: [u32; 1] = [0];
+impl<T: Value> AppendBuffer<T> {
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+    pub fn data(&self) -> &Buffer<T> {
+        &self.data
+    }
+    pub fn counter(&self) -> &Buffer<u32> {
+        &self.counter
+    }
+    // Number of pushes since the last reset, including the dropped ones
+    pub fn count(&self) -> usize {
+        self.counter.copy_to_vec()[0] as usize
+    }
+    pub fn len(&self) -> usize {
+        self.count().min(self.capacity())
+    }
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+    pub fn overflowed(&self) -> bool {
+        self.count() > self.capacity()
+    }
+    pub fn reset_async(&self) -> Command<'_> {
+        let mut rt = ResourceTracker::new();
+        rt.add(self.counter.handle.clone());
+        Command {
+            inner: api::Command::BufferUpload(BufferUploadCommand {
+                buffer: self.counter.handle(),
+                offset: 0,
+                size: std::mem::size_of::<u32>(),
+                data: APPEND_BUFFER_ZERO.as_ptr() as *const u8,
+            }),
+            marker: std::marker::PhantomData,
+            resource_tracker: rt,
+        }
+    }
+    pub fn reset(&self) {
+        submit_default_stream_and_sync(&self.data.device, [self.reset_async()]).unwrap();
+    }
+    pub fn copy_to_vec(&self) -> Vec<T> {
+        self.data.view(..self.len() as u64).copy_to_vec()
+    }
+    pub fn var(&self) -> AppendBufferVar<T> {
+        AppendBufferVar::new(self)
+    }
+}
 pub(crate) struct BindlessArrayHandle {
     pub(crate) device: Device,
     pub(crate) handle: api::BindlessArray,
@@ -294,6 +355,28 @@ impl BindlessArray {
             .borrow_mut()
             .add(texture.handle.clone());
     }
+    // Stored in the tex2d slot, read with `BindlessArrayVar::tex1d`
+    pub fn emplace_tex1d_async<T: IoTexel>(&self, index: usize, texture: &Tex1d<T>, sampler: Sampler) {
+        self.emplace_tex2d_async(index, &texture.tex, sampler);
+    }
+    // Stored in the tex3d slot, read with `BindlessArrayVar::tex2d_array`
+    pub fn emplace_tex2d_array_async<T: IoTexel>(
+        &self,
+        index: usize,
+        texture: &Tex2dArray<T>,
+        sampler: Sampler,
+    ) {
+        self.emplace_tex3d_async(index, &texture.tex, sampler);
+    }
+    // Stored in the tex3d slot, read with `BindlessArrayVar::texcube`
+    pub fn emplace_texcube_async<T: IoTexel>(
+        &self,
+        index: usize,
+        texture: &TexCube<T>,
+        sampler: Sampler,
+    ) {
+        self.emplace_tex3d_async(index, &texture.tex, sampler);
+    }
     pub fn remove_buffer_async(&self, index: usize) {
         self.modifications
             .borrow_mut()
@@ -352,6 +435,23 @@ impl BindlessArray {
         self.emplace_tex3d_async(index, texture, sampler);
         self.update();
     }
+    pub fn set_tex1d<T: IoTexel>(&self, index: usize, texture: &Tex1d<T>, sampler: Sampler) {
+        self.emplace_tex1d_async(index, texture, sampler);
+        self.update();
+    }
+    pub fn set_tex2d_array<T: IoTexel>(
+        &self,
+        index: usize,
+        texture: &Tex2dArray<T>,
+        sampler: Sampler,
+    ) {
+        self.emplace_tex2d_array_async(index, texture, sampler);
+        self.update();
+    }
+    pub fn set_texcube<T: IoTexel>(&self, index: usize, texture: &TexCube<T>, sampler: Sampler) {
+        self.emplace_texcube_async(index, texture, sampler);
+        self.update();
+    }
     pub fn remove_buffer(&self, index: usize) {
         self.remove_buffer_async(index);
         self.update();
@@ -398,8 +498,25 @@ pub(crate) struct TextureHandle {
     pub(crate) width: u32,
     pub(crate) height: u32,
     pub(crate) depth: u32,
-    #[allow(dead_code)]
     pub(crate) levels: u32,
+    pub(crate) cache: RefCell<HashMap<(TypeId, [u32; 3]), Box<dyn Any>>>,
+}
+impl TextureHandle {
+    // Kernels and scratch resources that operations on this texture compile or allocate
+    // once and then reuse, keyed by their type and a discriminator such as a staging extent
+    pub(crate) fn cached<K: Any>(
+        &self,
+        key: [u32; 3],
+        create: impl FnOnce() -> backend::Result<K>,
+    ) -> backend::Result<Arc<K>> {
+        let key = (TypeId::of::<K>(), key);
+        if let Some(value) = self.cache.borrow().get(&key) {
+            return Ok(value.downcast_ref::<Arc<K>>().unwrap().clone());
+        }
+        let value = Arc::new(create()?);
+        self.cache.borrow_mut().insert(key, Box::new(value.clone()));
+        Ok(value)
+    }
 }
 trait GetPixelFormat {
     fn pixel_format(storage: PixelStorage) -> PixelFormat;
@@ -410,7 +527,7 @@ impl GetPixelFormat for f32 {
             PixelStorage::Byte1 => PixelFormat::R8Unorm,
             PixelStorage::Byte2 => PixelFormat::Rg8Unorm,
             PixelStorage::Byte4 => PixelFormat::Rgba8Unorm,
-            PixelStorage::Half1 => PixelFormat::Rg16f,
+            PixelStorage::Half1 => PixelFormat::R16f,
             PixelStorage::Half2 => PixelFormat::Rg16f,
             PixelStorage::Half4 => PixelFormat::Rgba16f,
             PixelStorage::Short1 => PixelFormat::R16Unorm,
@@ -457,22 +574,37 @@ impl GetPixelFormat for u32 {
 }
 // Type that can be converted from a pixel format
 // This is the type that is read from/written to a texture
-pub trait IoTexel: Value {
+pub trait IoTexel: Value + 'static {
     fn pixel_format(storage: PixelStorage) -> PixelFormat;
+    // Conversions used by the built-in texture kernels (e.g. mipmap generation),
+    // integer texels are converted by value and rounded back
+    fn to_float4(texel: Expr<Self>) -> Expr<Float4>;
+    fn from_float4(v: Expr<Float4>) -> Expr<Self>;
 }
 
 macro_rules! impl_io_texel {
-    ($t:ty,$el:ty) => {
+    ($t:ty, $el:ty, |$v:ident| $to_float4:expr, $from_float4:expr) => {
         impl IoTexel for $t {
             fn pixel_format(storage: PixelStorage) -> PixelFormat {
                 <$el as GetPixelFormat>::pixel_format(storage)
             }
+            fn to_float4($v: Expr<Self>) -> Expr<Float4> {
+                $to_float4
+            }
+            fn from_float4($v: Expr<Float4>) -> Expr<Self> {
+                $from_float4
+            }
         }
     };
 }
-impl_io_texel!(f32, f32);
-impl_io_texel!(Float2, f32);
-impl_io_texel!(Float4, f32);
+impl_io_texel!(f32, f32, |v| make_float4(v, 0.0f32, 0.0f32, 0.0f32), v.x());
+impl_io_texel!(
+    Float2,
+    f32,
+    |v| make_float4(v.x(), v.y(), 0.0f32, 0.0f32),
+    make_float2(v.x(), v.y())
+);
+impl_io_texel!(Float4, f32, |v| v, v);
 
 // impl_io_texel!(u16,);
 // impl_io_texel!(i16,);
@@ -480,12 +612,55 @@ impl_io_texel!(Float4, f32);
 // impl_io_texel!(Short2,);
 // impl_io_texel!(Ushort4,);
 // impl_io_texel!(Short4,);
-impl_io_texel!(u32, u32);
-impl_io_texel!(i32, i32);
-impl_io_texel!(Uint2, u32);
-impl_io_texel!(Int2, i32);
-impl_io_texel!(Uint4, u32);
-impl_io_texel!(Int4, i32);
+impl_io_texel!(
+    u32,
+    u32,
+    |v| make_float4(v.float(), 0.0f32, 0.0f32, 0.0f32),
+    v.x().max(0.0f32).round().uint()
+);
+impl_io_texel!(
+    i32,
+    i32,
+    |v| make_float4(v.float(), 0.0f32, 0.0f32, 0.0f32),
+    v.x().round().int()
+);
+impl_io_texel!(
+    Uint2,
+    u32,
+    |v| make_float4(v.x().float(), v.y().float(), 0.0f32, 0.0f32),
+    make_uint2(
+        v.x().max(0.0f32).round().uint(),
+        v.y().max(0.0f32).round().uint()
+    )
+);
+impl_io_texel!(
+    Int2,
+    i32,
+    |v| make_float4(v.x().float(), v.y().float(), 0.0f32, 0.0f32),
+    make_int2(v.x().round().int(), v.y().round().int())
+);
+impl_io_texel!(
+    Uint4,
+    u32,
+    |v| make_float4(v.x().float(), v.y().float(), v.z().float(), v.w().float()),
+    make_uint4(
+        v.x().max(0.0f32).round().uint(),
+        v.y().max(0.0f32).round().uint(),
+        v.z().max(0.0f32).round().uint(),
+        v.w().max(0.0f32).round().uint()
+    )
+);
+impl_io_texel!(
+    Int4,
+    i32,
+    |v| make_float4(v.x().float(), v.y().float(), v.z().float(), v.w().float()),
+    make_int4(
+        v.x().round().int(),
+        v.y().round().int(),
+        v.z().round().int(),
+        v.w().round().int()
+    )
+);
 
 // Types that is stored in a texture
 pub trait StorageTexel<T: IoTexel> {
@@ -514,44 +689,46 @@ impl_storage_texel!([u8; 4], Byte4, f32, Float2, Float4, Int2, Int4, Uint2, Uint
 impl_storage_texel!(i8, Byte1, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!(Byte2, Byte2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!(Byte4, Byte4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!(Char2, Byte2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!(Char4, Byte4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!([i8; 2], Byte2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!([i8; 4], Byte4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 
 impl_storage_texel!(u16, Short1, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!(Ushort2, Short2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!(Ushort4, Short4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
-impl_storage_texel!([u16; 2], Byte2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
-impl_storage_texel!([u16; 4], Byte4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!([u16; 2], Short2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!([u16; 4], Short4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 
 impl_storage_texel!(i16, Short1, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!(Short2, Short2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!(Short4, Short4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
-impl_storage_texel!([i16; 2], Byte2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
-impl_storage_texel!([i16; 4], Byte4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!([i16; 2], Short2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!([i16; 4], Short4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 
 impl_storage_texel!(u32, Int1, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!(Uint2, Int2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!(Uint4, Int4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
-impl_storage_texel!([u32; 2], Byte2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
-impl_storage_texel!([u32; 4], Byte4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!([u32; 2], Int2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!([u32; 4], Int4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 
 impl_storage_texel!(i32, Int1, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!(Int2, Int2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 impl_storage_texel!(Int4, Int4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
-impl_storage_texel!([i32; 2], Byte2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
-impl_storage_texel!([i32; 4], Byte4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!([i32; 2], Int2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!([i32; 4], Int4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
 
 impl_storage_texel!(f32, Float1, f32, Float2, Float4,);
 impl_storage_texel!(Float2, Float2, f32, Float2, Float4,);
 impl_storage_texel!(Float4, Float4, f32, Float2, Float4,);
-impl_storage_texel!([f32; 2], Byte2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
-impl_storage_texel!([f32; 4], Byte4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!([f32; 2], Float2, f32, Float2, Float4,);
+impl_storage_texel!([f32; 4], Float4, f32, Float2, Float4,);
 
 impl_storage_texel!(f16, Half1, f32, Float2, Float4,);
 impl_storage_texel!(Half2, Half2, f32, Float2, Float4,);
 impl_storage_texel!(Half4, Half4, f32, Float2, Float4,);
-impl_storage_texel!([f16; 2], Byte2, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
-impl_storage_texel!([f16; 4], Byte4, f32, Float2, Float4, Int2, Int4, Uint2, Uint4,);
+impl_storage_texel!([f16; 2], Half2, f32, Float2, Float4,);
+impl_storage_texel!([f16; 4], Half4, f32, Float2, Float4,);
 
 // `T` is the read out type of the texture, which is not necessarily the same as the storage type
 // In fact, the texture can be stored in any format as long as it can be converted to `T`
@@ -593,7 +770,7 @@ impl<T: IoTexel> Tex3d<T> {
     }
 }
 macro_rules! impl_tex_view {
-    ($name:ident) => {
+    ($name:ident, $encode:ident, $blit:ident) => {
         impl<'a, T: IoTexel> $name<'a, T> {
             pub fn copy_to_async<U: StorageTexel<T>>(&'a self, data: &'a mut [U]) -> Command<'a> {
                 assert_eq!(data.len(), self.texel_count() as usize);
@@ -619,10 +796,12 @@ macro_rules! impl_tex_view {
                     .unwrap();
             }
             pub fn copy_to_vec<U: StorageTexel<T>>(&'a self) -> Vec<U> {
-                let mut data = Vec::with_capacity(self.texel_count() as usize);
-                self.copy_to(&mut data);
+                let count = self.texel_count() as usize;
+                let mut data = Vec::with_capacity(count);
                 unsafe {
-                    data.set_len(self.texel_count() as usize);
+                    let slice = std::slice::from_raw_parts_mut(data.as_mut_ptr(), count);
+                    self.copy_to(slice);
+                    data.set_len(count);
                 }
                 data
             }
@@ -666,7 +845,7 @@ macro_rules! impl_tex_view {
                         texture_level: self.level,
                         texture_size: self.size(),
                         buffer: buffer_view.handle(),
-                        buffer_offset: buffer_view.offset,
+                        buffer_offset: buffer_view.offset * std::mem::size_of::<U>(),
                     }),
                     resource_tracker: rt,
                     marker: std::marker::PhantomData,
@@ -695,7 +874,7 @@ macro_rules! impl_tex_view {
                         texture_level: self.level,
                         texture_size: self.size(),
                         buffer: buffer_view.handle(),
-                        buffer_offset: buffer_view.offset,
+                        buffer_offset: buffer_view.offset * std::mem::size_of::<U>(),
                     }),
                     resource_tracker: rt,
                     marker: std::marker::PhantomData,
@@ -738,6 +917,300 @@ macro_rules! impl_tex_view {
                 )
                 .unwrap();
             }
+            fn check_region(&self, offset: [u32; 3], extent: [u32; 3]) {
+                let size = self.size();
+                assert!(
+                    extent.iter().all(|e| *e > 0),
+                    "region extent {:?} is empty",
+                    extent
+                );
+                for i in 0..3 {
+                    assert!(
+                        offset[i]
+                            .checked_add(extent[i])
+                            .map_or(false, |end| end <= size[i]),
+                        "region {:?}+{:?} is out of bounds of level size {:?}",
+                        offset,
+                        extent,
+                        size
+                    );
+                }
+            }
+            // Copies texels through a kernel, converting the pixel format when `D` differs from `T`
+            // The commands keep everything they touch alive, so they aren't tied to either view
+            // The kernel and the offsets buffer are cached on `owner`, the caller's texture
+            // rather than a staging one. The offsets are uploaded right before the dispatch,
+            // so blits sharing an owner must be submitted in order on one stream
+            fn blit_region_async_<'b, 'c, D: IoTexel>(
+                &self,
+                owner: &TextureHandle,
+                src_offset: [u32; 3],
+                dst: &$name<'b, D>,
+                dst_offset: [u32; 3],
+                extent: [u32; 3],
+            ) -> backend::Result<Vec<Command<'c>>> {
+                self.check_region(src_offset, extent);
+                dst.check_region(dst_offset, extent);
+                let device = &self.tex.handle.device;
+                let kernel = owner.cached([0; 3], || $blit::<T, D>(device))?;
+                let offsets = owner.cached([0; 3], || device.create_buffer::<Uint3>(2))?;
+                let data = Arc::new([
+                    Uint3::new(src_offset[0], src_offset[1], src_offset[2]),
+                    Uint3::new(dst_offset[0], dst_offset[1], dst_offset[2]),
+                ]);
+                let mut rt = ResourceTracker::new();
+                rt.add(offsets.handle.clone()).add(data.clone());
+                let upload = Command {
+                    inner: api::Command::BufferUpload(BufferUploadCommand {
+                        buffer: offsets.handle.handle,
+                        offset: 0,
+                        size: std::mem::size_of_val(&*data),
+                        data: data.as_ptr() as *const u8,
+                    }),
+                    marker: std::marker::PhantomData,
+                    resource_tracker: rt,
+                };
+                let mut encoder = ArgEncoder::new();
+                encoder.$encode(self);
+                encoder.$encode(dst);
+                encoder.buffer(&offsets);
+                let mut cmd = kernel.dispatch_owned_async(encoder, extent);
+                cmd.resource_tracker
+                    .add(self.tex.handle.clone())
+                    .add(dst.tex.handle.clone())
+                    .add(offsets.handle.clone());
+                Ok(vec![upload, cmd])
+            }
+            pub fn copy_region_to_async<U: StorageTexel<T>>(
+                &'a self,
+                offset: [u32; 3],
+                extent: [u32; 3],
+                data: &'a mut [U],
+            ) -> backend::Result<Vec<Command<'a>>> {
+                assert_eq!(data.len(), extent.iter().product::<u32>() as usize);
+                assert_eq!(self.tex.handle.storage, U::pixel_storage());
+                self.check_region(offset, extent);
+                let staging = self
+                    .tex
+                    .handle
+                    .cached(extent, || self.create_staging(extent))?;
+                let mut rt = ResourceTracker::new();
+                rt.add(staging.handle.clone());
+                let download = Command {
+                    inner: api::Command::TextureDownload(api::TextureDownloadCommand {
+                        texture: staging.handle(),
+                        storage: staging.handle.storage,
+                        level: 0,
+                        size: extent,
+                        data: data.as_mut_ptr() as *mut u8,
+                    }),
+                    resource_tracker: rt,
+                    marker: std::marker::PhantomData,
+                };
+                let owner = &self.tex.handle;
+                let mut commands =
+                    self.blit_region_async_(owner, offset, &staging.view(0), [0, 0, 0], extent)?;
+                commands.push(download);
+                Ok(commands)
+            }
+            pub fn copy_region_to<U: StorageTexel<T>>(
+                &'a self,
+                offset: [u32; 3],
+                extent: [u32; 3],
+                data: &'a mut [U],
+            ) -> backend::Result<()> {
+                submit_default_stream_and_sync(
+                    &self.tex.handle.device,
+                    self.copy_region_to_async(offset, extent, data)?,
+                )
+            }
+            pub fn copy_region_from_async<U: StorageTexel<T>>(
+                &'a self,
+                offset: [u32; 3],
+                extent: [u32; 3],
+                data: &'a [U],
+            ) -> backend::Result<Vec<Command<'a>>> {
+                assert_eq!(data.len(), extent.iter().product::<u32>() as usize);
+                assert_eq!(self.tex.handle.storage, U::pixel_storage());
+                self.check_region(offset, extent);
+                let staging = self
+                    .tex
+                    .handle
+                    .cached(extent, || self.create_staging(extent))?;
+                let mut rt = ResourceTracker::new();
+                rt.add(staging.handle.clone());
+                let upload = Command {
+                    inner: api::Command::TextureUpload(api::TextureUploadCommand {
+                        texture: staging.handle(),
+                        storage: staging.handle.storage,
+                        level: 0,
+                        size: extent,
+                        data: data.as_ptr() as *const u8,
+                    }),
+                    resource_tracker: rt,
+                    marker: std::marker::PhantomData,
+                };
+                let (owner, src) = (&self.tex.handle, staging.view(0));
+                let mut commands = vec![upload];
+                commands.extend(src.blit_region_async_(owner, [0, 0, 0], self, offset, extent)?);
+                Ok(commands)
+            }
+            pub fn copy_region_from<U: StorageTexel<T>>(
+                &'a self,
+                offset: [u32; 3],
+                extent: [u32; 3],
+                data: &[U],
+            ) -> backend::Result<()> {
+                submit_default_stream_and_sync(
+                    &self.tex.handle.device,
+                    self.copy_region_from_async(offset, extent, data)?,
+                )
+            }
+            pub fn copy_region_to_buffer_async<U: StorageTexel<T> + Value>(
+                &'a self,
+                offset: [u32; 3],
+                extent: [u32; 3],
+                buffer_view: &'a BufferView<U>,
+            ) -> backend::Result<Vec<Command<'a>>> {
+                assert_eq!(buffer_view.len, extent.iter().product::<u32>() as usize);
+                assert_eq!(self.tex.handle.storage, U::pixel_storage());
+                self.check_region(offset, extent);
+                let staging = self
+                    .tex
+                    .handle
+                    .cached(extent, || self.create_staging(extent))?;
+                let mut rt = ResourceTracker::new();
+                rt.add(staging.handle.clone());
+                rt.add(buffer_view.buffer.handle.clone());
+                let copy = Command {
+                    inner: api::Command::TextureToBufferCopy(api::TextureToBufferCopyCommand {
+                        texture: staging.handle(),
+                        storage: staging.handle.storage,
+                        texture_level: 0,
+                        texture_size: extent,
+                        buffer: buffer_view.handle(),
+                        buffer_offset: buffer_view.offset * std::mem::size_of::<U>(),
+                    }),
+                    resource_tracker: rt,
+                    marker: std::marker::PhantomData,
+                };
+                let owner = &self.tex.handle;
+                let mut commands =
+                    self.blit_region_async_(owner, offset, &staging.view(0), [0, 0, 0], extent)?;
+                commands.push(copy);
+                Ok(commands)
+            }
+            pub fn copy_region_to_buffer<U: StorageTexel<T> + Value>(
+                &'a self,
+                offset: [u32; 3],
+                extent: [u32; 3],
+                buffer_view: &BufferView<U>,
+            ) -> backend::Result<()> {
+                submit_default_stream_and_sync(
+                    &self.tex.handle.device,
+                    self.copy_region_to_buffer_async(offset, extent, buffer_view)?,
+                )
+            }
+            pub fn copy_region_from_buffer_async<U: StorageTexel<T> + Value>(
+                &'a self,
+                offset: [u32; 3],
+                extent: [u32; 3],
+                buffer_view: &'a BufferView<U>,
+            ) -> backend::Result<Vec<Command<'a>>> {
+                assert_eq!(buffer_view.len, extent.iter().product::<u32>() as usize);
+                assert_eq!(self.tex.handle.storage, U::pixel_storage());
+                self.check_region(offset, extent);
+                let staging = self
+                    .tex
+                    .handle
+                    .cached(extent, || self.create_staging(extent))?;
+                let mut rt = ResourceTracker::new();
+                rt.add(staging.handle.clone());
+                rt.add(buffer_view.buffer.handle.clone());
+                let copy = Command {
+                    inner: api::Command::BufferToTextureCopy(api::BufferToTextureCopyCommand {
+                        texture: staging.handle(),
+                        storage: staging.handle.storage,
+                        texture_level: 0,
+                        texture_size: extent,
+                        buffer: buffer_view.handle(),
+                        buffer_offset: buffer_view.offset * std::mem::size_of::<U>(),
+                    }),
+                    resource_tracker: rt,
+                    marker: std::marker::PhantomData,
+                };
+                let (owner, src) = (&self.tex.handle, staging.view(0));
+                let mut commands = vec![copy];
+                commands.extend(src.blit_region_async_(owner, [0, 0, 0], self, offset, extent)?);
+                Ok(commands)
+            }
+            pub fn copy_region_from_buffer<U: StorageTexel<T> + Value>(
+                &'a self,
+                offset: [u32; 3],
+                extent: [u32; 3],
+                buffer_view: &BufferView<U>,
+            ) -> backend::Result<()> {
+                submit_default_stream_and_sync(
+                    &self.tex.handle.device,
+                    self.copy_region_from_buffer_async(offset, extent, buffer_view)?,
+                )
+            }
+            // Unlike `copy_to_texture_async`, the two textures may use different storages
+            pub fn copy_region_to_texture_async(
+                &'a self,
+                src_offset: [u32; 3],
+                other: $name<T>,
+                dst_offset: [u32; 3],
+                extent: [u32; 3],
+            ) -> backend::Result<Vec<Command<'a>>> {
+                self.blit_region_async_(&self.tex.handle, src_offset, &other, dst_offset, extent)
+            }
+            pub fn copy_region_to_texture(
+                &'a self,
+                src_offset: [u32; 3],
+                other: $name<T>,
+                dst_offset: [u32; 3],
+                extent: [u32; 3],
+            ) -> backend::Result<()> {
+                submit_default_stream_and_sync(
+                    &self.tex.handle.device,
+                    self.copy_region_to_texture_async(src_offset, other, dst_offset, extent)?,
+                )
+            }
+            // Converts texels between any two formats, e.g. a `Byte4` texture read as `Float4`
+            // into a `Float4` or `Half4` one, integer texels are converted by value
+            pub fn blit_to_async<D: IoTexel>(
+                &'a self,
+                other: $name<D>,
+            ) -> backend::Result<Vec<Command<'a>>> {
+                assert_eq!(self.size(), other.size());
+                let (owner, size) = (&self.tex.handle, self.size());
+                self.blit_region_async_(owner, [0, 0, 0], &other, [0, 0, 0], size)
+            }
+            pub fn blit_to<D: IoTexel>(&'a self, other: $name<D>) -> backend::Result<()> {
+                submit_default_stream_and_sync(&self.tex.handle.device, self.blit_to_async(other)?)
+            }
+            pub fn blit_region_to_async<D: IoTexel>(
+                &'a self,
+                src_offset: [u32; 3],
+                other: $name<D>,
+                dst_offset: [u32; 3],
+                extent: [u32; 3],
+            ) -> backend::Result<Vec<Command<'a>>> {
+                self.blit_region_async_(&self.tex.handle, src_offset, &other, dst_offset, extent)
+            }
+            pub fn blit_region_to<D: IoTexel>(
+                &'a self,
+                src_offset: [u32; 3],
+                other: $name<D>,
+                dst_offset: [u32; 3],
+                extent: [u32; 3],
+            ) -> backend::Result<()> {
+                submit_default_stream_and_sync(
+                    &self.tex.handle.device,
+                    self.blit_region_to_async(src_offset, other, dst_offset, extent)?,
+                )
+            }
         }
     };
 }
@@ -759,8 +1232,15 @@ impl<'a, T: IoTexel> Tex2dView<'a, T> {
     pub fn var(&self) -> Tex2dVar<T> {
         Tex2dVar::new(*self)
     }
+    fn create_staging(&self, extent: [u32; 3]) -> backend::Result<Tex2d<T>> {
+        assert_eq!(extent[2], 1);
+        self.tex
+            .handle
+            .device
+            .create_tex2d(self.tex.handle.storage, extent[0], extent[1], 1)
+    }
 }
-impl_tex_view!(Tex2dView);
+impl_tex_view!(Tex2dView, tex2d, create_blit_kernel_2d);
 impl<'a, T: IoTexel> Tex3dView<'a, T> {
     pub(crate) fn handle(&self) -> api::Texture {
         self.tex.handle.handle
@@ -779,8 +1259,13 @@ impl<'a, T: IoTexel> Tex3dView<'a, T> {
     pub fn var(&self) -> Tex3dVar<T> {
         Tex3dVar::new(*self)
     }
+    fn create_staging(&self, extent: [u32; 3]) -> backend::Result<Tex3d<T>> {
+        let [w, h, d] = extent;
+        let handle = &self.tex.handle;
+        handle.device.create_tex3d(handle.storage, w, h, d, 1)
+    }
 }
-impl_tex_view!(Tex3dView);
+impl_tex_view!(Tex3dView, tex3d, create_blit_kernel_3d);
 impl Drop for TextureHandle {
     fn drop(&mut self) {
         self.device.inner.destroy_texture(self.handle);
@@ -803,3 +1288,419 @@ impl<T: IoTexel> Tex3d<T> {
         self.handle.native_handle
     }
 }
+
+// The backends only know about 2D and 3D textures, so the following are layouts on top of them:
+// a `Tex1d` is a `width x 1` `Tex2d`, a `Tex2dArray` is a `Tex3d` with one layer per slice
+// and a `TexCube` is a `Tex3d` with the six faces in the order +X, -X, +Y, -Y, +Z, -Z
+pub struct Tex1d<T: IoTexel> {
+    pub(crate) tex: Tex2d<T>,
+}
+pub struct Tex2dArray<T: IoTexel> {
+    pub(crate) tex: Tex3d<T>,
+}
+pub struct TexCube<T: IoTexel> {
+    pub(crate) tex: Tex3d<T>,
+    // the edge length, which kernels can't query from the texture itself
+    pub(crate) size: Buffer<u32>,
+}
+impl<T: IoTexel> Tex1d<T> {
+    pub fn width(&self) -> u32 {
+        self.tex.handle.width
+    }
+    pub fn levels(&self) -> u32 {
+        self.tex.handle.levels
+    }
+    // The underlying `width x 1` texture, for copies and blits
+    pub fn view(&self, level: u32) -> Tex2dView<T> {
+        self.tex.view(level)
+    }
+    pub fn var(&self) -> Tex1dVar<T> {
+        Tex1dVar::new(self.view(0))
+    }
+    pub fn generate_mipmaps_async<'a>(&'a self) -> backend::Result<Vec<Command<'a>>> {
+        self.tex.generate_mipmaps_async()
+    }
+    pub fn generate_mipmaps(&self) -> backend::Result<()> {
+        self.tex.generate_mipmaps()
+    }
+    pub fn native_handle(&self) -> *mut std::ffi::c_void {
+        self.tex.native_handle()
+    }
+}
+impl<T: IoTexel> Tex2dArray<T> {
+    pub fn size(&self) -> [u32; 2] {
+        [self.tex.handle.width, self.tex.handle.height]
+    }
+    pub fn layers(&self) -> u32 {
+        self.tex.handle.depth
+    }
+    // Layer `i` is the slice `z = i` of the view
+    pub fn view(&self) -> Tex3dView<T> {
+        self.tex.view(0)
+    }
+    pub fn var(&self) -> Tex2dArrayVar<T> {
+        Tex2dArrayVar::new(self.view())
+    }
+    pub fn copy_layer_to<U: StorageTexel<T>>(
+        &self,
+        layer: u32,
+        data: &mut [U],
+    ) -> backend::Result<()> {
+        let [w, h] = self.size();
+        self.view().copy_region_to([0, 0, layer], [w, h, 1], data)
+    }
+    pub fn copy_layer_from<U: StorageTexel<T>>(
+        &self,
+        layer: u32,
+        data: &[U],
+    ) -> backend::Result<()> {
+        let [w, h] = self.size();
+        self.view().copy_region_from([0, 0, layer], [w, h, 1], data)
+    }
+    pub fn native_handle(&self) -> *mut std::ffi::c_void {
+        self.tex.native_handle()
+    }
+}
+impl<T: IoTexel> TexCube<T> {
+    pub fn size(&self) -> u32 {
+        self.tex.handle.width
+    }
+    // Face `i` is the slice `z = i` of the view
+    pub fn view(&self) -> Tex3dView<T> {
+        self.tex.view(0)
+    }
+    pub fn var(&self) -> TexCubeVar<T> {
+        TexCubeVar::new(self)
+    }
+    pub fn copy_face_to<U: StorageTexel<T>>(
+        &self,
+        face: u32,
+        data: &mut [U],
+    ) -> backend::Result<()> {
+        assert!(face < 6);
+        let size = self.size();
+        self.view()
+            .copy_region_to([0, 0, face], [size, size, 1], data)
+    }
+    pub fn copy_face_from<U: StorageTexel<T>>(&self, face: u32, data: &[U]) -> backend::Result<()> {
+        assert!(face < 6);
+        let size = self.size();
+        self.view()
+            .copy_region_from([0, 0, face], [size, size, 1], data)
+    }
+    pub fn native_handle(&self) -> *mut std::ffi::c_void {
+        self.tex.native_handle()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MipmapFilter {
+    // Exact box filter over the footprint of the destination texel
+    Box,
+    // Kaiser windowed sinc, `width` is the radius in destination texels
+    // A good default is `width: 3.0, alpha: 4.0`
+    Kaiser { width: f32, alpha: f32 },
+}
+impl Default for MipmapFilter {
+    fn default() -> Self {
+        MipmapFilter::Box
+    }
+}
+// The source texels covered by a destination texel along one axis
+// Levels are `max(1, size >> level)`, so odd sizes cover non-integer footprints
+struct MipFootprint {
+    begin: Expr<i32>,
+    end: Expr<i32>,
+    center: Expr<f32>,
+    scale: Expr<f32>,
+}
+fn bessel_i0(x: f32) -> f32 {
+    let q = x * x * 0.25;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for k in 1..16 {
+        term = term * q / (k * k) as f32;
+        sum += term;
+    }
+    sum
+}
+fn bessel_i0_expr(x: Expr<f32>) -> Expr<f32> {
+    let q = x * x * 0.25f32;
+    let mut term = const_(1.0f32);
+    let mut sum = term;
+    for k in 1..16 {
+        term = term * q / (k * k) as f32;
+        sum = sum + term;
+    }
+    sum
+}
+impl MipmapFilter {
+    // Tells apart the kernels of different filters in a texture's cache
+    fn cache_key(&self) -> [u32; 3] {
+        match *self {
+            MipmapFilter::Box => [0, 0, 0],
+            MipmapFilter::Kaiser { width, alpha } => [1, width.to_bits(), alpha.to_bits()],
+        }
+    }
+    fn radius(&self) -> f32 {
+        match *self {
+            MipmapFilter::Box => 0.5,
+            MipmapFilter::Kaiser { width, .. } => width,
+        }
+    }
+    fn footprint(&self, i: Expr<u32>, src: Expr<u32>, dst: Expr<u32>) -> MipFootprint {
+        let scale = src.float() / dst.float();
+        let center = (i.float() + 0.5f32) * scale;
+        let radius = scale * self.radius();
+        MipFootprint {
+            begin: (center - radius).floor().int().max(0i32),
+            end: (center + radius).ceil().int().min(src.int()),
+            center,
+            scale,
+        }
+    }
+    fn weight(&self, fp: &MipFootprint, j: Expr<i32>) -> Expr<f32> {
+        match *self {
+            MipmapFilter::Box => {
+                let radius = fp.scale * 0.5f32;
+                let lo = (fp.center - radius).max(j.float());
+                let hi = (fp.center + radius).min(j.float() + 1.0f32);
+                (hi - lo).max(0.0f32)
+            }
+            MipmapFilter::Kaiser { width, alpha } => {
+                let x = (j.float() + 0.5f32 - fp.center) / fp.scale;
+                let t = x / width;
+                let window =
+                    bessel_i0_expr(alpha * (1.0f32 - t * t).max(0.0f32).sqrt()) / bessel_i0(alpha);
+                let px = x * std::f32::consts::PI;
+                let sinc = select(px.abs().cmplt(1e-4f32), const_(1.0f32), px.sin() / px);
+                select(t.abs().cmplt(1.0f32), sinc * window, const_(0.0f32))
+            }
+        }
+    }
+}
+type MipmapKernel2d<T> = Kernel<(Tex2d<T>, Tex2d<T>, Buffer<Uint4>)>;
+type MipmapKernel3d<T> = Kernel<(Tex3d<T>, Tex3d<T>, Buffer<Uint4>)>;
+fn create_mipmap_kernel_2d<T: IoTexel>(
+    device: &Device,
+    filter: MipmapFilter,
+) -> backend::Result<MipmapKernel2d<T>> {
+    device.create_kernel::<(Tex2d<T>, Tex2d<T>, Buffer<Uint4>)>(&|src, dst, src_size| {
+        let p = dispatch_id();
+        let dst_size = dispatch_size();
+        let src_size = src_size.read(0u32);
+        let fx = filter.footprint(p.x(), src_size.x(), dst_size.x());
+        let fy = filter.footprint(p.y(), src_size.y(), dst_size.y());
+        let sum = var!(Float4);
+        let weight_sum = var!(f32);
+        let y = var!(i32, fy.begin);
+        while_!(y.load().cmplt(fy.end), {
+            let wy = filter.weight(&fy, y.load());
+            let x = var!(i32, fx.begin);
+            while_!(x.load().cmplt(fx.end), {
+                let w = wy * filter.weight(&fx, x.load());
+                let texel = src.read(make_uint2(x.load().uint(), y.load().uint()));
+                sum.store(sum.load() + T::to_float4(texel) * w);
+                weight_sum.store(weight_sum.load() + w);
+                x.store(x.load() + 1i32);
+            });
+            y.store(y.load() + 1i32);
+        });
+        dst.write(
+            make_uint2(p.x(), p.y()),
+            T::from_float4(sum.load() / weight_sum.load()),
+        );
+    })
+}
+fn create_mipmap_kernel_3d<T: IoTexel>(
+    device: &Device,
+    filter: MipmapFilter,
+) -> backend::Result<MipmapKernel3d<T>> {
+    device.create_kernel::<(Tex3d<T>, Tex3d<T>, Buffer<Uint4>)>(&|src, dst, src_size| {
+        let p = dispatch_id();
+        let dst_size = dispatch_size();
+        let src_size = src_size.read(0u32);
+        let fx = filter.footprint(p.x(), src_size.x(), dst_size.x());
+        let fy = filter.footprint(p.y(), src_size.y(), dst_size.y());
+        let fz = filter.footprint(p.z(), src_size.z(), dst_size.z());
+        let sum = var!(Float4);
+        let weight_sum = var!(f32);
+        let z = var!(i32, fz.begin);
+        while_!(z.load().cmplt(fz.end), {
+            let wz = filter.weight(&fz, z.load());
+            let y = var!(i32, fy.begin);
+            while_!(y.load().cmplt(fy.end), {
+                let wy = wz * filter.weight(&fy, y.load());
+                let x = var!(i32, fx.begin);
+                while_!(x.load().cmplt(fx.end), {
+                    let w = wy * filter.weight(&fx, x.load());
+                    let texel = src.read(make_uint3(
+                        x.load().uint(),
+                        y.load().uint(),
+                        z.load().uint(),
+                    ));
+                    sum.store(sum.load() + T::to_float4(texel) * w);
+                    weight_sum.store(weight_sum.load() + w);
+                    x.store(x.load() + 1i32);
+                });
+                y.store(y.load() + 1i32);
+            });
+            z.store(z.load() + 1i32);
+        });
+        dst.write(p, T::from_float4(sum.load() / weight_sum.load()));
+    })
+}
+// Level `i` of the returned buffer holds the size of level `i` of the texture,
+// the dispatch for level `i + 1` reads its source size from there
+fn mip_level_sizes(device: &Device, size: [u32; 3], levels: u32) -> backend::Result<Buffer<Uint4>> {
+    let sizes = (0..levels)
+        .map(|level| {
+            let [w, h, d] = size.map(|s| (s >> level).max(1));
+            Uint4::new(w, h, d, 0)
+        })
+        .collect::<Vec<_>>();
+    device.create_buffer_from_slice(&sizes)
+}
+fn mipmap_commands_2d<'a, T: IoTexel>(
+    tex: &'a Tex2d<T>,
+    kernel: &Arc<MipmapKernel2d<T>>,
+) -> backend::Result<Vec<Command<'a>>> {
+    let levels = tex.handle.levels;
+    if levels <= 1 {
+        return Ok(vec![]);
+    }
+    let size = [tex.handle.width, tex.handle.height, 1];
+    let device = &tex.handle.device;
+    let sizes = tex
+        .handle
+        .cached([0; 3], || mip_level_sizes(device, size, levels))?;
+    Ok((1..levels)
+        .map(|level| {
+            let dst = tex.view(level);
+            let mut encoder = ArgEncoder::new();
+            encoder.tex2d(&tex.view(level - 1));
+            encoder.tex2d(&dst);
+            encoder.buffer_view(&sizes.view(level as u64 - 1..level as u64));
+            let mut cmd = kernel.dispatch_owned_async(encoder, dst.size());
+            cmd.resource_tracker
+                .add(tex.handle.clone())
+                .add(sizes.handle.clone());
+            cmd
+        })
+        .collect())
+}
+fn mipmap_commands_3d<'a, T: IoTexel>(
+    tex: &'a Tex3d<T>,
+    kernel: &Arc<MipmapKernel3d<T>>,
+) -> backend::Result<Vec<Command<'a>>> {
+    let levels = tex.handle.levels;
+    if levels <= 1 {
+        return Ok(vec![]);
+    }
+    let size = [tex.handle.width, tex.handle.height, tex.handle.depth];
+    let device = &tex.handle.device;
+    let sizes = tex
+        .handle
+        .cached([0; 3], || mip_level_sizes(device, size, levels))?;
+    Ok((1..levels)
+        .map(|level| {
+            let dst = tex.view(level);
+            let mut encoder = ArgEncoder::new();
+            encoder.tex3d(&tex.view(level - 1));
+            encoder.tex3d(&dst);
+            encoder.buffer_view(&sizes.view(level as u64 - 1..level as u64));
+            let mut cmd = kernel.dispatch_owned_async(encoder, dst.size());
+            cmd.resource_tracker
+                .add(tex.handle.clone())
+                .add(sizes.handle.clone());
+            cmd
+        })
+        .collect())
+}
+impl<T: IoTexel> Tex2d<T> {
+    pub fn generate_mipmaps_async<'a>(&'a self) -> backend::Result<Vec<Command<'a>>> {
+        self.generate_mipmaps_with_filter_async(MipmapFilter::Box)
+    }
+    // Fills level 1.. from level 0, one dispatch per level
+    // The kernel for each filter is compiled on first use and kept on the texture
+    pub fn generate_mipmaps_with_filter_async<'a>(
+        &'a self,
+        filter: MipmapFilter,
+    ) -> backend::Result<Vec<Command<'a>>> {
+        if self.handle.levels <= 1 {
+            return Ok(vec![]);
+        }
+        let device = &self.handle.device;
+        let kernel = self.handle.cached(filter.cache_key(), || {
+            create_mipmap_kernel_2d::<T>(device, filter)
+        })?;
+        mipmap_commands_2d(self, &kernel)
+    }
+    pub fn generate_mipmaps(&self) -> backend::Result<()> {
+        self.generate_mipmaps_with_filter(MipmapFilter::Box)
+    }
+    pub fn generate_mipmaps_with_filter(&self, filter: MipmapFilter) -> backend::Result<()> {
+        submit_default_stream_and_sync(
+            &self.handle.device,
+            self.generate_mipmaps_with_filter_async(filter)?,
+        )
+    }
+}
+impl<T: IoTexel> Tex3d<T> {
+    pub fn generate_mipmaps_async<'a>(&'a self) -> backend::Result<Vec<Command<'a>>> {
+        self.generate_mipmaps_with_filter_async(MipmapFilter::Box)
+    }
+    // Fills level 1.. from level 0, one dispatch per level
+    // The kernel for each filter is compiled on first use and kept on the texture
+    pub fn generate_mipmaps_with_filter_async<'a>(
+        &'a self,
+        filter: MipmapFilter,
+    ) -> backend::Result<Vec<Command<'a>>> {
+        if self.handle.levels <= 1 {
+            return Ok(vec![]);
+        }
+        let device = &self.handle.device;
+        let kernel = self.handle.cached(filter.cache_key(), || {
+            create_mipmap_kernel_3d::<T>(device, filter)
+        })?;
+        mipmap_commands_3d(self, &kernel)
+    }
+    pub fn generate_mipmaps(&self) -> backend::Result<()> {
+        self.generate_mipmaps_with_filter(MipmapFilter::Box)
+    }
+    pub fn generate_mipmaps_with_filter(&self, filter: MipmapFilter) -> backend::Result<()> {
+        submit_default_stream_and_sync(
+            &self.handle.device,
+            self.generate_mipmaps_with_filter_async(filter)?,
+        )
+    }
+}
+
+fn convert_texel<S: IoTexel, D: IoTexel>(texel: Expr<S>) -> Expr<D> {
+    if TypeId::of::<S>() == TypeId::of::<D>() {
+        Expr::<D>::from_node(texel.node())
+    } else {
+        D::from_float4(S::to_float4(texel))
+    }
+}
+// Buffer holds [src_offset, dst_offset], dispatch size is the extent
+fn create_blit_kernel_2d<S: IoTexel, D: IoTexel>(
+    device: &Device,
+) -> backend::Result<Kernel<(Tex2d<S>, Tex2d<D>, Buffer<Uint3>)>> {
+    device.create_kernel::<(Tex2d<S>, Tex2d<D>, Buffer<Uint3>)>(&|src, dst, offsets| {
+        let p = dispatch_id();
+        let src_p = p + offsets.read(0u32);
+        let dst_p = p + offsets.read(1u32);
+        let texel = src.read(make_uint2(src_p.x(), src_p.y()));
+        dst.write(make_uint2(dst_p.x(), dst_p.y()), convert_texel::<S, D>(texel));
+    })
+}
+fn create_blit_kernel_3d<S: IoTexel, D: IoTexel>(
+    device: &Device,
+) -> backend::Result<Kernel<(Tex3d<S>, Tex3d<D>, Buffer<Uint3>)>> {
+    device.create_kernel::<(Tex3d<S>, Tex3d<D>, Buffer<Uint3>)>(&|src, dst, offsets| {
+        let p = dispatch_id();
+        let texel = src.read(p + offsets.read(0u32));
+        dst.write(p + offsets.read(1u32), convert_texel::<S, D>(texel));
+    })
+}
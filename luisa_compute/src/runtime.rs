@@ -85,6 +85,15 @@ impl Device {
         buffer.view(..).fill_fn(f);
         Ok(buffer)
     }
+    pub fn create_append_buffer<T: Value>(
+        &self,
+        capacity: usize,
+    ) -> backend::Result<AppendBuffer<T>> {
+        Ok(AppendBuffer {
+            data: self.create_buffer(capacity)?,
+            counter: self.create_buffer_from_slice(&[0u32])?,
+        })
+    }
     pub fn create_bindless_array(&self, slots: usize) -> backend::Result<BindlessArray> {
         let array = self.inner.create_bindless_array(slots)?;
         Ok(BindlessArray {
@@ -119,6 +128,7 @@ impl Device {
             height,
             depth: 1,
             storage: format.storage(),
+            cache: RefCell::new(HashMap::new()),
         });
         let tex = Tex2d {
             handle,
@@ -148,6 +158,7 @@ impl Device {
             height,
             depth,
             storage: format.storage(),
+            cache: RefCell::new(HashMap::new()),
         });
         let tex = Tex3d {
             handle,
@@ -155,6 +166,36 @@ impl Device {
         };
         Ok(tex)
     }
+    pub fn create_tex1d<T: IoTexel>(
+        &self,
+        storage: PixelStorage,
+        width: u32,
+        mips: u32,
+    ) -> backend::Result<Tex1d<T>> {
+        let tex = self.create_tex2d(storage, width, 1, mips)?;
+        Ok(Tex1d { tex })
+    }
+    // Arrays have no mip levels, as mipmapping the underlying 3D texture would mix the layers
+    pub fn create_tex2d_array<T: IoTexel>(
+        &self,
+        storage: PixelStorage,
+        width: u32,
+        height: u32,
+        layers: u32,
+    ) -> backend::Result<Tex2dArray<T>> {
+        let tex = self.create_tex3d(storage, width, height, layers, 1)?;
+        Ok(Tex2dArray { tex })
+    }
+    // Cubes have no mip levels either, filtered sampling goes through `BindlessTexCubeVar`
+    pub fn create_texcube<T: IoTexel>(
+        &self,
+        storage: PixelStorage,
+        size: u32,
+    ) -> backend::Result<TexCube<T>> {
+        let tex = self.create_tex3d(storage, size, size, 6, 1)?;
+        let size = self.create_buffer_from_slice(&[size])?;
+        Ok(TexCube { tex, size })
+    }
 
     pub fn default_stream(&self) -> Stream {
         Stream {
@@ -176,6 +217,8 @@ impl Device {
             }),
         })
     }
+    // The position is read from the first three f32 of each `V`, so Float3,
+    // PackedFloat3 or a vertex struct starting with either works; the stride is size_of::<V>()
     pub fn create_mesh<V: Value, T: Value>(
         &self,
         vbuffer: BufferView<'_, V>,
@@ -470,7 +513,7 @@ impl ArgEncoder {
     pub fn buffer_view<T: Value>(&mut self, buffer: &BufferView<T>) {
         self.args.push(api::Argument::Buffer(api::BufferArgument {
             buffer: buffer.handle(),
-            offset: buffer.offset,
+            offset: buffer.offset * std::mem::size_of::<T>(),
             size: buffer.len * std::mem::size_of::<T>(),
         }));
     }
@@ -534,6 +577,32 @@ impl<'a, T: IoTexel> KernelArg for Tex3dView<'a, T> {
         encoder.tex3d(self);
     }
 }
+impl<T: IoTexel> KernelArg for Tex1d<T> {
+    type Parameter = lang::Tex1dVar<T>;
+    fn encode(&self, encoder: &mut ArgEncoder) {
+        encoder.tex2d(&self.view(0));
+    }
+}
+impl<T: IoTexel> KernelArg for Tex2dArray<T> {
+    type Parameter = lang::Tex2dArrayVar<T>;
+    fn encode(&self, encoder: &mut ArgEncoder) {
+        encoder.tex3d(&self.view());
+    }
+}
+impl<T: IoTexel> KernelArg for TexCube<T> {
+    type Parameter = lang::TexCubeVar<T>;
+    fn encode(&self, encoder: &mut ArgEncoder) {
+        encoder.tex3d(&self.view());
+        encoder.buffer(&self.size);
+    }
+}
+impl<T: Value> KernelArg for AppendBuffer<T> {
+    type Parameter = lang::AppendBufferVar<T>;
+    fn encode(&self, encoder: &mut ArgEncoder) {
+        encoder.buffer(&self.data);
+        encoder.buffer(&self.counter);
+    }
+}
 impl KernelArg for BindlessArray {
     type Parameter = lang::BindlessArrayVar;
     fn encode(&self, encoder: &mut ArgEncoder) {
@@ -625,6 +694,30 @@ impl<T: KernelArg> Kernel<T> {
         device.inner.shader_cache_dir(handle)
     }
 }
+impl<T: KernelArg + 'static> Kernel<T> {
+    // For built-in operations that compile their kernels on the fly
+    // The returned command keeps both the kernel and the arguments alive
+    pub(crate) fn dispatch_owned_async<'a>(
+        self: &Arc<Self>,
+        args: ArgEncoder,
+        dispatch_size: [u32; 3],
+    ) -> Command<'a> {
+        let args = Arc::new(args);
+        let mut rt = ResourceTracker::new();
+        rt.add(self.clone());
+        rt.add(args.clone());
+        Command {
+            inner: api::Command::ShaderDispatch(api::ShaderDispatchCommand {
+                shader: self.inner.unwrap(),
+                args: args.args.as_ptr(),
+                args_count: args.args.len(),
+                dispatch_size,
+            }),
+            marker: std::marker::PhantomData,
+            resource_tracker: rt,
+        }
+    }
+}
 pub trait AsKernelArg<T: KernelArg>: KernelArg {}
 impl<T: Value> AsKernelArg<Buffer<T>> for Buffer<T> {}
 impl<'a, T: Value> AsKernelArg<Buffer<T>> for BufferView<'a, T> {}
@@ -638,6 +731,12 @@ impl<'a, T: IoTexel> AsKernelArg<Tex3dView<'a, T>> for Tex2d<T> {}
 impl<'a, T: IoTexel> AsKernelArg<Tex3dView<'a, T>> for Tex3d<T> {}
 impl<T: IoTexel> AsKernelArg<Tex2d<T>> for Tex2d<T> {}
 impl<T: IoTexel> AsKernelArg<Tex3d<T>> for Tex3d<T> {}
+impl<T: IoTexel> AsKernelArg<Tex1d<T>> for Tex1d<T> {}
+impl<'a, T: IoTexel> AsKernelArg<Tex1d<T>> for Tex2dView<'a, T> {}
+impl<T: IoTexel> AsKernelArg<Tex2dArray<T>> for Tex2dArray<T> {}
+impl<'a, T: IoTexel> AsKernelArg<Tex2dArray<T>> for Tex3dView<'a, T> {}
+impl<T: IoTexel> AsKernelArg<TexCube<T>> for TexCube<T> {}
+impl<T: Value> AsKernelArg<AppendBuffer<T>> for AppendBuffer<T> {}
 impl AsKernelArg<BindlessArray> for BindlessArray {}
 impl AsKernelArg<Accel> for Accel {}
 macro_rules! impl_dispatch_for_kernel {
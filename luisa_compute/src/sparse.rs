@@ -0,0 +1,467 @@
+// Sparse matrices in device memory, sparse matrix-vector products and a Jacobi
+// preconditioned conjugate gradient solver.
+// Both formats are built from host (row, col, value) triplets: duplicate entries are summed
+// and the entries are stored sorted by row, then by column.
+use crate::algo::{Reduce, SegmentedScan};
+use crate::blas::BlasScalar;
+use crate::*;
+
+fn merge_triplets<T: BlasScalar>(
+    rows: u32,
+    cols: u32,
+    triplets: &[(u32, u32, T)],
+) -> Vec<(u32, u32, T)> {
+    let mut sorted = triplets.to_vec();
+    for (r, c, _) in &sorted {
+        assert!(
+            *r < rows && *c < cols,
+            "entry ({}, {}) is out of bounds",
+            r,
+            c
+        );
+    }
+    sorted.sort_by_key(|(r, c, _)| (*r, *c));
+    let mut merged: Vec<(u32, u32, T)> = Vec::with_capacity(sorted.len());
+    for (r, c, v) in sorted {
+        match merged.last_mut() {
+            Some(last) if last.0 == r && last.1 == c => last.2 += v,
+            _ => merged.push((r, c, v)),
+        }
+    }
+    merged
+}
+// Buffers cannot be empty, so an empty slice gets a single unused element
+fn upload<T: Value>(device: &Device, data: &[T]) -> backend::Result<Buffer<T>> {
+    let buffer = device.create_buffer(data.len().max(1))?;
+    if !data.is_empty() {
+        buffer.view(..data.len() as u64).copy_from(data);
+    }
+    Ok(buffer)
+}
+
+pub struct CooMatrix<T: BlasScalar> {
+    rows: u32,
+    cols: u32,
+    nnz: usize,
+    row_indices: Buffer<u32>,
+    col_indices: Buffer<u32>,
+    values: Buffer<T>,
+    // set at the first entry of every row
+    row_heads: Buffer<bool>,
+}
+impl<T: BlasScalar> CooMatrix<T> {
+    pub fn from_triplets(
+        device: &Device,
+        rows: u32,
+        cols: u32,
+        triplets: &[(u32, u32, T)],
+    ) -> backend::Result<Self> {
+        let entries = merge_triplets(rows, cols, triplets);
+        let row_indices = entries.iter().map(|e| e.0).collect::<Vec<_>>();
+        let row_heads = (0..entries.len())
+            .map(|i| i == 0 || row_indices[i] != row_indices[i - 1])
+            .collect::<Vec<_>>();
+        Ok(Self {
+            rows,
+            cols,
+            nnz: entries.len(),
+            row_indices: upload(device, &row_indices)?,
+            col_indices: upload(device, &entries.iter().map(|e| e.1).collect::<Vec<_>>())?,
+            values: upload(device, &entries.iter().map(|e| e.2).collect::<Vec<_>>())?,
+            row_heads: upload(device, &row_heads)?,
+        })
+    }
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+    pub fn cols(&self) -> u32 {
+        self.cols
+    }
+    pub fn nnz(&self) -> usize {
+        self.nnz
+    }
+    pub fn row_indices(&self) -> BufferView<u32> {
+        self.row_indices.view(..self.nnz as u64)
+    }
+    pub fn col_indices(&self) -> BufferView<u32> {
+        self.col_indices.view(..self.nnz as u64)
+    }
+    pub fn values(&self) -> BufferView<T> {
+        self.values.view(..self.nnz as u64)
+    }
+    pub fn to_triplets(&self) -> Vec<(u32, u32, T)> {
+        if self.nnz == 0 {
+            return vec![];
+        }
+        let rows = self.row_indices().copy_to_vec();
+        let cols = self.col_indices().copy_to_vec();
+        let values = self.values().copy_to_vec();
+        rows.into_iter()
+            .zip(cols)
+            .zip(values)
+            .map(|((r, c), v)| (r, c, v))
+            .collect()
+    }
+}
+
+pub struct CsrMatrix<T: BlasScalar> {
+    rows: u32,
+    cols: u32,
+    nnz: usize,
+    row_offsets: Buffer<u32>,
+    col_indices: Buffer<u32>,
+    values: Buffer<T>,
+}
+impl<T: BlasScalar> CsrMatrix<T> {
+    pub fn from_triplets(
+        device: &Device,
+        rows: u32,
+        cols: u32,
+        triplets: &[(u32, u32, T)],
+    ) -> backend::Result<Self> {
+        let entries = merge_triplets(rows, cols, triplets);
+        let mut row_offsets = vec![0u32; rows as usize + 1];
+        for (r, _, _) in &entries {
+            row_offsets[*r as usize + 1] += 1;
+        }
+        for r in 0..rows as usize {
+            row_offsets[r + 1] += row_offsets[r];
+        }
+        Ok(Self {
+            rows,
+            cols,
+            nnz: entries.len(),
+            row_offsets: device.create_buffer_from_slice(&row_offsets)?,
+            col_indices: upload(device, &entries.iter().map(|e| e.1).collect::<Vec<_>>())?,
+            values: upload(device, &entries.iter().map(|e| e.2).collect::<Vec<_>>())?,
+        })
+    }
+    pub fn from_coo(device: &Device, coo: &CooMatrix<T>) -> backend::Result<Self> {
+        Self::from_triplets(device, coo.rows, coo.cols, &coo.to_triplets())
+    }
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+    pub fn cols(&self) -> u32 {
+        self.cols
+    }
+    pub fn nnz(&self) -> usize {
+        self.nnz
+    }
+    pub fn row_offsets(&self) -> &Buffer<u32> {
+        &self.row_offsets
+    }
+    pub fn col_indices(&self) -> BufferView<u32> {
+        self.col_indices.view(..self.nnz as u64)
+    }
+    pub fn values(&self) -> BufferView<T> {
+        self.values.view(..self.nnz as u64)
+    }
+    pub fn var(&self) -> CsrMatrixVar<T> {
+        CsrMatrixVar {
+            row_offsets: self.row_offsets.var(),
+            col_indices: self.col_indices.var(),
+            values: self.values.var(),
+        }
+    }
+}
+impl<T: BlasScalar> KernelArg for CsrMatrix<T> {
+    type Parameter = CsrMatrixVar<T>;
+    fn encode(&self, encoder: &mut ArgEncoder) {
+        encoder.buffer(&self.row_offsets);
+        encoder.buffer(&self.col_indices);
+        encoder.buffer(&self.values);
+    }
+}
+impl<T: BlasScalar> AsKernelArg<CsrMatrix<T>> for CsrMatrix<T> {}
+
+pub struct CsrMatrixVar<T: BlasScalar> {
+    row_offsets: BufferVar<u32>,
+    col_indices: BufferVar<u32>,
+    values: BufferVar<T>,
+}
+impl<T: BlasScalar> KernelParameter for CsrMatrixVar<T> {
+    fn def_param(builder: &mut KernelBuilder) -> Self {
+        Self {
+            row_offsets: builder.buffer(),
+            col_indices: builder.buffer(),
+            values: builder.buffer(),
+        }
+    }
+}
+impl<T: BlasScalar> CsrMatrixVar<T> {
+    pub fn rows(&self) -> Expr<u32> {
+        self.row_offsets.len() - 1u32
+    }
+    // Calls `f` with the column and value of every stored entry of `row`
+    pub fn for_each_in_row(&self, row: impl Into<Expr<u32>>, f: impl Fn(Expr<u32>, Expr<T>)) {
+        let row = row.into();
+        let i = var!(u32, self.row_offsets.read(row));
+        let end = self.row_offsets.read(row + 1u32);
+        while_!(i.load().cmplt(end), {
+            f(self.col_indices.read(i.load()), self.values.read(i.load()));
+            i.store(i.load() + 1u32);
+        });
+    }
+    // Dot product of `row` with `x`
+    pub fn mul_row(&self, row: impl Into<Expr<u32>>, x: &BufferVar<T>) -> Expr<T> {
+        let sum = var!(T);
+        self.for_each_in_row(row, |col, value| {
+            sum.store(T::add(sum.load(), T::mul(value, x.read(col))));
+        });
+        sum.load()
+    }
+}
+
+// y = a * x for both formats
+pub struct Spmv<T: BlasScalar> {
+    csr: Kernel<(CsrMatrix<T>, Buffer<T>, Buffer<T>)>,
+    coo_products: Kernel<(Buffer<u32>, Buffer<T>, Buffer<T>, Buffer<T>)>,
+    coo_scatter: Kernel<(Buffer<u32>, Buffer<T>, Buffer<T>)>,
+    scan: SegmentedScan<T>,
+    device: Device,
+}
+impl<T: BlasScalar> Spmv<T> {
+    pub fn new(device: &Device) -> backend::Result<Self> {
+        let csr = device.create_kernel::<(CsrMatrix<T>, Buffer<T>, Buffer<T>)>(&|a, x, y| {
+            let row = dispatch_id().x();
+            y.write(row, a.mul_row(row, &x));
+        })?;
+        let coo_products = device.create_kernel::<(Buffer<u32>, Buffer<T>, Buffer<T>, Buffer<T>)>(
+            &|cols, values, x, products| {
+                let i = dispatch_id().x();
+                products.write(i, T::mul(values.read(i), x.read(cols.read(i))));
+            },
+        )?;
+        // the last entry of every row holds the sum of the row after the segmented scan
+        let coo_scatter =
+            device.create_kernel::<(Buffer<u32>, Buffer<T>, Buffer<T>)>(&|rows, sums, y| {
+                let i = dispatch_id().x();
+                let n = rows.len();
+                let row = rows.read(i);
+                let next = rows.read((i + 1u32).min(n - 1u32));
+                if_!(i.cmpeq(n - 1u32) | next.cmpne(row), {
+                    y.write(row, sums.read(i));
+                });
+            })?;
+        Ok(Self {
+            csr,
+            coo_products,
+            coo_scatter,
+            scan: SegmentedScan::new(device, T::ZERO, T::add)?,
+            device: device.clone(),
+        })
+    }
+    pub fn csr(
+        &self,
+        a: &CsrMatrix<T>,
+        x: &BufferView<T>,
+        y: &BufferView<T>,
+    ) -> backend::Result<()> {
+        assert_eq!(x.len(), a.cols as usize);
+        assert_eq!(y.len(), a.rows as usize);
+        if a.rows == 0 {
+            return Ok(());
+        }
+        self.csr.dispatch([a.rows, 1, 1], a, x, y)
+    }
+    pub fn coo(
+        &self,
+        a: &CooMatrix<T>,
+        x: &BufferView<T>,
+        y: &BufferView<T>,
+    ) -> backend::Result<()> {
+        assert_eq!(x.len(), a.cols as usize);
+        assert_eq!(y.len(), a.rows as usize);
+        if a.rows == 0 {
+            return Ok(());
+        }
+        // rows without entries are never written by the scatter
+        y.fill(T::ZERO);
+        if a.nnz == 0 {
+            return Ok(());
+        }
+        let products = self.device.create_buffer::<T>(a.nnz)?;
+        self.coo_products.dispatch(
+            [a.nnz as u32, 1, 1],
+            &a.col_indices(),
+            &a.values(),
+            x,
+            &products,
+        )?;
+        self.scan.inclusive_scan(
+            &products.view(..),
+            &a.row_heads.view(..a.nnz as u64),
+            &products.view(..),
+        )?;
+        self.coo_scatter
+            .dispatch([a.nnz as u32, 1, 1], &a.row_indices(), &products, y)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CgStatus {
+    pub iterations: u32,
+    pub converged: bool,
+    // |b - a * x| / |b|
+    pub residual: f64,
+}
+
+// Slots of the scalars buffer used by the solver
+const RZ: u64 = 0;
+const PAP: u64 = 1;
+const RZ_NEW: u64 = 2;
+const RR: u64 = 3;
+const BB: u64 = 4;
+
+type VectorKernel<T> = Kernel<(Buffer<T>, Buffer<T>, Buffer<T>)>;
+
+// Conjugate gradient with a Jacobi preconditioner for symmetric positive definite matrices.
+// All vector operations run as kernels, only |r|^2 is read back once per iteration
+pub struct ConjugateGradient<T: BlasScalar> {
+    device: Device,
+    spmv: Spmv<T>,
+    sum: Reduce<T>,
+    // c = a * b
+    multiply: VectorKernel<T>,
+    // c = a - b
+    subtract: VectorKernel<T>,
+    inverse_diagonal: Kernel<(CsrMatrix<T>, Buffer<T>)>,
+    // x += alpha * p, r -= alpha * ap with alpha = rz / pap
+    update_x_r: Kernel<(Buffer<T>, Buffer<T>, Buffer<T>, Buffer<T>, Buffer<T>)>,
+    // p = z + beta * p with beta = rz_new / rz
+    update_p: VectorKernel<T>,
+}
+impl<T: BlasScalar> ConjugateGradient<T> {
+    pub fn new(device: &Device) -> backend::Result<Self> {
+        let multiply = device.create_kernel::<(Buffer<T>, Buffer<T>, Buffer<T>)>(&|a, b, c| {
+            let i = dispatch_id().x();
+            c.write(i, T::mul(a.read(i), b.read(i)));
+        })?;
+        let subtract = device.create_kernel::<(Buffer<T>, Buffer<T>, Buffer<T>)>(&|a, b, c| {
+            let i = dispatch_id().x();
+            c.write(i, T::sub(a.read(i), b.read(i)));
+        })?;
+        let inverse_diagonal =
+            device.create_kernel::<(CsrMatrix<T>, Buffer<T>)>(&|a, inv_diag| {
+                let row = dispatch_id().x();
+                let d = var!(T);
+                a.for_each_in_row(row, |col, value| {
+                    if_!(col.cmpeq(row), {
+                        d.store(T::add(d.load(), value));
+                    });
+                });
+                inv_diag.write(row, T::div(const_(T::ONE), d.load()));
+            })?;
+        let update_x_r = device
+            .create_kernel::<(Buffer<T>, Buffer<T>, Buffer<T>, Buffer<T>, Buffer<T>)>(
+                &|x, r, p, ap, scalars| {
+                    let i = dispatch_id().x();
+                    let alpha = T::div(scalars.read(RZ as u32), scalars.read(PAP as u32));
+                    x.write(i, T::add(x.read(i), T::mul(alpha, p.read(i))));
+                    r.write(i, T::sub(r.read(i), T::mul(alpha, ap.read(i))));
+                },
+            )?;
+        let update_p =
+            device.create_kernel::<(Buffer<T>, Buffer<T>, Buffer<T>)>(&|p, z, scalars| {
+                let i = dispatch_id().x();
+                let beta = T::div(scalars.read(RZ_NEW as u32), scalars.read(RZ as u32));
+                p.write(i, T::add(z.read(i), T::mul(beta, p.read(i))));
+            })?;
+        Ok(Self {
+            device: device.clone(),
+            spmv: Spmv::new(device)?,
+            sum: Reduce::new(device, T::ZERO, T::add)?,
+            multiply,
+            subtract,
+            inverse_diagonal,
+            update_x_r,
+            update_p,
+        })
+    }
+    // scalars[slot] = dot(a, b), `tmp` holds the elementwise products
+    fn dot(
+        &self,
+        a: &BufferView<T>,
+        b: &BufferView<T>,
+        tmp: &Buffer<T>,
+        scalars: &Buffer<T>,
+        slot: u64,
+    ) -> backend::Result<()> {
+        self.multiply.dispatch([a.len() as u32, 1, 1], a, b, tmp)?;
+        self.sum
+            .reduce_to(&tmp.view(..), &scalars.view(slot..slot + 1))
+    }
+    // Solves a * x = b starting from the initial guess in `x`, stops once
+    // |b - a * x| <= tolerance * |b| or after `max_iterations` iterations
+    pub fn solve(
+        &self,
+        a: &CsrMatrix<T>,
+        b: &BufferView<T>,
+        x: &BufferView<T>,
+        tolerance: f64,
+        max_iterations: u32,
+    ) -> backend::Result<CgStatus> {
+        assert_eq!(a.rows, a.cols, "the matrix must be square");
+        assert_eq!(b.len(), a.rows as usize);
+        assert_eq!(x.len(), a.rows as usize);
+        let n = a.rows as usize;
+        if n == 0 {
+            return Ok(CgStatus {
+                iterations: 0,
+                converged: true,
+                residual: 0.0,
+            });
+        }
+        let dispatch_size = [n as u32, 1, 1];
+        let scalars = self.device.create_buffer::<T>(5)?;
+        let read = |slot: u64| -> f64 { scalars.view(slot..slot + 1).copy_to_vec()[0].into() };
+        let r = self.device.create_buffer::<T>(n)?;
+        let z = self.device.create_buffer::<T>(n)?;
+        let p = self.device.create_buffer::<T>(n)?;
+        let ap = self.device.create_buffer::<T>(n)?;
+        let tmp = self.device.create_buffer::<T>(n)?;
+        let inv_diag = self.device.create_buffer::<T>(n)?;
+        self.inverse_diagonal
+            .dispatch(dispatch_size, a, &inv_diag)?;
+
+        self.dot(b, b, &tmp, &scalars, BB)?;
+        let bb = read(BB);
+        if bb == 0.0 {
+            x.fill(T::ZERO);
+            return Ok(CgStatus {
+                iterations: 0,
+                converged: true,
+                residual: 0.0,
+            });
+        }
+        self.spmv.csr(a, x, &ap.view(..))?;
+        self.subtract.dispatch(dispatch_size, b, &ap, &r)?;
+        self.multiply.dispatch(dispatch_size, &r, &inv_diag, &z)?;
+        z.view(..).copy_to_buffer(&p.view(..));
+        self.dot(&r.view(..), &z.view(..), &tmp, &scalars, RZ)?;
+        let mut iterations = 0;
+        loop {
+            self.dot(&r.view(..), &r.view(..), &tmp, &scalars, RR)?;
+            let residual = (read(RR) / bb).sqrt();
+            if residual <= tolerance || iterations == max_iterations {
+                return Ok(CgStatus {
+                    iterations,
+                    converged: residual <= tolerance,
+                    residual,
+                });
+            }
+            self.spmv.csr(a, &p.view(..), &ap.view(..))?;
+            self.dot(&p.view(..), &ap.view(..), &tmp, &scalars, PAP)?;
+            self.update_x_r
+                .dispatch(dispatch_size, x, &r, &p, &ap, &scalars)?;
+            self.multiply.dispatch(dispatch_size, &r, &inv_diag, &z)?;
+            self.dot(&r.view(..), &z.view(..), &tmp, &scalars, RZ_NEW)?;
+            self.update_p.dispatch(dispatch_size, &p, &z, &scalars)?;
+            scalars
+                .view(RZ_NEW..RZ_NEW + 1)
+                .copy_to_buffer(&scalars.view(RZ..RZ + 1));
+            iterations += 1;
+        }
+    }
+}
@@ -0,0 +1,267 @@
+// Block sparse voxel grid: the grid is split into 8x8x8 blocks, a dense table maps every
+// block to a slot in a pool of voxel blocks or to INACTIVE_BLOCK. Blocks are activated on
+// the host, kernels read, write and sample active voxels; inactive voxels read as zero and
+// writes to them are dropped.
+// Growing the pool replaces its buffer, so kernels can't capture a grid and it has no
+// `var()`: pass it as a kernel argument instead, which binds the current pool on every dispatch.
+use crate::*;
+
+pub const BLOCK_SIZE: u32 = 8;
+pub const BLOCK_VOLUME: u32 = BLOCK_SIZE * BLOCK_SIZE * BLOCK_SIZE;
+pub const INACTIVE_BLOCK: u32 = u32::MAX;
+
+type ToTex3dKernel<T> = Kernel<(SparseGrid<T>, Tex3d<T>)>;
+type FromTex3dKernel<T> = Kernel<(Tex3d<T>, SparseGrid<T>)>;
+// flags every block holding a nonzero voxel
+type MarkKernel<T> = Kernel<(Tex3d<T>, Buffer<u32>, Buffer<Uint3>)>;
+
+pub struct SparseGrid<T: IoTexel> {
+    device: Device,
+    resolution: [u32; 3],
+    table: Buffer<u32>,
+    host_table: Vec<u32>,
+    // [resolution, block_dims]
+    info: Buffer<Uint3>,
+    data: Buffer<T>,
+    active_blocks: usize,
+}
+impl<T: IoTexel> SparseGrid<T> {
+    // `capacity` is the number of blocks allocated up front, the pool grows on demand
+    pub fn new(device: &Device, resolution: [u32; 3], capacity: usize) -> backend::Result<Self> {
+        let block_dims = resolution.map(|r| r.div_ceil(BLOCK_SIZE));
+        let blocks = block_dims.iter().map(|d| *d as usize).product::<usize>();
+        Ok(Self {
+            device: device.clone(),
+            resolution,
+            table: device.create_buffer_from_fn(blocks.max(1), |_| INACTIVE_BLOCK)?,
+            host_table: vec![INACTIVE_BLOCK; blocks],
+            info: device.create_buffer_from_slice(&[
+                Uint3::new(resolution[0], resolution[1], resolution[2]),
+                Uint3::new(block_dims[0], block_dims[1], block_dims[2]),
+            ])?,
+            data: device.create_buffer(capacity.max(1) * BLOCK_VOLUME as usize)?,
+            active_blocks: 0,
+        })
+    }
+    pub fn resolution(&self) -> [u32; 3] {
+        self.resolution
+    }
+    pub fn block_dims(&self) -> [u32; 3] {
+        self.resolution.map(|r| r.div_ceil(BLOCK_SIZE))
+    }
+    pub fn active_blocks(&self) -> usize {
+        self.active_blocks
+    }
+    pub fn capacity(&self) -> usize {
+        self.data.len() / BLOCK_VOLUME as usize
+    }
+    fn block_index(&self, block: [u32; 3]) -> usize {
+        let dims = self.block_dims();
+        assert!(
+            block[0] < dims[0] && block[1] < dims[1] && block[2] < dims[2],
+            "block {:?} is out of bounds of {:?}",
+            block,
+            dims
+        );
+        ((block[2] * dims[1] + block[1]) * dims[0] + block[0]) as usize
+    }
+    pub fn is_block_active(&self, block: [u32; 3]) -> bool {
+        self.host_table[self.block_index(block)] != INACTIVE_BLOCK
+    }
+    pub fn is_active(&self, voxel: [u32; 3]) -> bool {
+        self.is_block_active(voxel.map(|v| v / BLOCK_SIZE))
+    }
+    // Activates the blocks and zeroes their voxels, already active blocks are kept as is
+    pub fn activate_blocks(
+        &mut self,
+        blocks: impl IntoIterator<Item = [u32; 3]>,
+    ) -> backend::Result<()> {
+        let first = self.active_blocks;
+        for block in blocks {
+            let index = self.block_index(block);
+            if self.host_table[index] == INACTIVE_BLOCK {
+                self.host_table[index] = self.active_blocks as u32;
+                self.active_blocks += 1;
+            }
+        }
+        if self.active_blocks == first {
+            return Ok(());
+        }
+        if self.active_blocks > self.capacity() {
+            let capacity = self.active_blocks.max(self.capacity() * 2);
+            let data = self
+                .device
+                .create_buffer::<T>(capacity * BLOCK_VOLUME as usize)?;
+            let used = (first * BLOCK_VOLUME as usize) as u64;
+            self.data.view(..used).copy_to_buffer(&data.view(..used));
+            self.data = data;
+        }
+        let block_volume = BLOCK_VOLUME as u64;
+        self.data
+            .view(first as u64 * block_volume..self.active_blocks as u64 * block_volume)
+            .fill(unsafe { std::mem::zeroed() });
+        self.table.view(..).copy_from(&self.host_table);
+        Ok(())
+    }
+    // Activates every block overlapping the voxel range [min, max)
+    pub fn activate_region(&mut self, min: [u32; 3], max: [u32; 3]) -> backend::Result<()> {
+        let lo = min.map(|v| v / BLOCK_SIZE);
+        let hi = max.map(|v| v.div_ceil(BLOCK_SIZE));
+        let mut blocks = vec![];
+        for z in lo[2]..hi[2] {
+            for y in lo[1]..hi[1] {
+                for x in lo[0]..hi[0] {
+                    blocks.push([x, y, z]);
+                }
+            }
+        }
+        self.activate_blocks(blocks)
+    }
+    // Deactivates every block, the pool keeps its capacity
+    pub fn clear(&mut self) {
+        self.host_table.fill(INACTIVE_BLOCK);
+        self.active_blocks = 0;
+        self.table.view(..).fill(INACTIVE_BLOCK);
+    }
+    // Writes every voxel into `tex`, whose size must equal the resolution
+    // The conversion kernels are compiled on first use and kept on the texture
+    pub fn to_tex3d(&self, tex: &Tex3dView<T>) -> backend::Result<()> {
+        assert_eq!(tex.size(), self.resolution);
+        let device = &self.device;
+        let kernel = tex.tex.handle.cached::<ToTex3dKernel<T>>([0; 3], || {
+            device.create_kernel::<(SparseGrid<T>, Tex3d<T>)>(&|grid, tex| {
+                let p = dispatch_id();
+                tex.write(p, grid.read(p));
+            })
+        })?;
+        kernel.dispatch(self.resolution, self, tex)
+    }
+    // Builds a grid whose active blocks are those of `tex` holding a nonzero voxel
+    pub fn from_tex3d(device: &Device, tex: &Tex3dView<T>) -> backend::Result<Self> {
+        let mut grid = Self::new(device, tex.size(), 0)?;
+        let flags = device.create_buffer_from_fn(grid.host_table.len().max(1), |_| 0u32)?;
+        let mark = tex.tex.handle.cached::<MarkKernel<T>>([0; 3], || {
+            device.create_kernel::<(Tex3d<T>, Buffer<u32>, Buffer<Uint3>)>(&|tex, flags, info| {
+                let p = dispatch_id();
+                let dims = info.read(1u32);
+                let b = p / BLOCK_SIZE;
+                if_!(
+                    T::to_float4(tex.read(p))
+                        .cmpne(Float4Expr::splat(0.0f32))
+                        .any(),
+                    {
+                        flags.write((b.z() * dims.y() + b.y()) * dims.x() + b.x(), 1u32);
+                    }
+                );
+            })
+        })?;
+        mark.dispatch(grid.resolution, tex, &flags, &grid.info)?;
+        let dims = grid.block_dims();
+        let flags = flags.view(..).copy_to_vec();
+        let blocks = (0..grid.host_table.len())
+            .filter(|i| flags[*i] != 0)
+            .map(|i| {
+                let i = i as u32;
+                [i % dims[0], i / dims[0] % dims[1], i / (dims[0] * dims[1])]
+            })
+            .collect::<Vec<_>>();
+        grid.activate_blocks(blocks)?;
+        let copy = tex.tex.handle.cached::<FromTex3dKernel<T>>([0; 3], || {
+            device.create_kernel::<(Tex3d<T>, SparseGrid<T>)>(&|tex, grid| {
+                let p = dispatch_id();
+                grid.write(p, tex.read(p));
+            })
+        })?;
+        copy.dispatch(grid.resolution, tex, &grid)?;
+        Ok(grid)
+    }
+}
+impl<T: IoTexel> KernelArg for SparseGrid<T> {
+    type Parameter = SparseGridVar<T>;
+    fn encode(&self, encoder: &mut ArgEncoder) {
+        encoder.buffer(&self.table);
+        encoder.buffer(&self.info);
+        encoder.buffer(&self.data);
+    }
+}
+impl<T: IoTexel> AsKernelArg<SparseGrid<T>> for SparseGrid<T> {}
+
+pub struct SparseGridVar<T: IoTexel> {
+    table: BufferVar<u32>,
+    info: BufferVar<Uint3>,
+    data: BufferVar<T>,
+}
+impl<T: IoTexel> KernelParameter for SparseGridVar<T> {
+    fn def_param(builder: &mut KernelBuilder) -> Self {
+        Self {
+            table: builder.buffer(),
+            info: builder.buffer(),
+            data: builder.buffer(),
+        }
+    }
+}
+impl<T: IoTexel> SparseGridVar<T> {
+    pub fn resolution(&self) -> Expr<Uint3> {
+        self.info.read(0u32)
+    }
+    // Index of the voxel in the pool, u32::MAX if it is out of bounds or inactive
+    fn voxel_index(&self, p: Expr<Int3>) -> Expr<u32> {
+        let resolution = self.resolution().int();
+        let in_bounds = (p.cmpge(Int3Expr::splat(0i32)) & p.cmplt(resolution)).all();
+        let p = p.clamp(Int3Expr::splat(0i32), resolution - 1i32).uint();
+        let dims = self.info.read(1u32);
+        let b = p / BLOCK_SIZE;
+        let l = p % BLOCK_SIZE;
+        let slot = self
+            .table
+            .read((b.z() * dims.y() + b.y()) * dims.x() + b.x());
+        let index = slot * BLOCK_VOLUME + (l.z() * BLOCK_SIZE + l.y()) * BLOCK_SIZE + l.x();
+        select(
+            in_bounds & slot.cmpne(INACTIVE_BLOCK),
+            index,
+            const_(u32::MAX),
+        )
+    }
+    fn read_int(&self, p: Expr<Int3>) -> Expr<T> {
+        let index = self.voxel_index(p);
+        let v = var!(T, zeroed::<T>());
+        if_!(index.cmpne(u32::MAX), {
+            v.store(self.data.read(index));
+        });
+        v.load()
+    }
+    pub fn is_active(&self, p: impl Into<Expr<Uint3>>) -> Expr<bool> {
+        self.voxel_index(p.into().int()).cmpne(u32::MAX)
+    }
+    pub fn read(&self, p: impl Into<Expr<Uint3>>) -> Expr<T> {
+        self.read_int(p.into().int())
+    }
+    pub fn write(&self, p: impl Into<Expr<Uint3>>, v: impl Into<Expr<T>>) {
+        let v = v.into();
+        let index = self.voxel_index(p.into().int());
+        if_!(index.cmpne(u32::MAX), {
+            self.data.write(index, v);
+        });
+    }
+    // Trilinear interpolation with voxel centers at integer + 0.5 in voxel units
+    pub fn sample(&self, p: impl Into<Expr<Float3>>) -> Expr<T> {
+        let p = p.into() - 0.5f32;
+        let base = p.floor();
+        let f = p - base;
+        let base = base.int();
+        let sum = var!(Float4);
+        for dz in 0..2 {
+            for dy in 0..2 {
+                for dx in 0..2 {
+                    let wx = if dx == 1 { f.x() } else { 1.0f32 - f.x() };
+                    let wy = if dy == 1 { f.y() } else { 1.0f32 - f.y() };
+                    let wz = if dz == 1 { f.z() } else { 1.0f32 - f.z() };
+                    let texel = self.read_int(base + make_int3(dx, dy, dz));
+                    let w = wx * wy * wz;
+                    sum.store(sum.load() + T::to_float4(texel) * w);
+                }
+            }
+        }
+        T::from_float4(sum.load())
+    }
+}
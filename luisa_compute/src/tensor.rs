@@ -0,0 +1,293 @@
+// Strided N-dimensional views over a Buffer<T>.
+// A Tensor owns a row-major buffer; views only change the offset, shape and strides, so
+// slicing, transposition and broadcasting (stride 0) never copy elements. The layout of a
+// view is uploaded to a small buffer laid out as [offset, shape.., strides..] which kernels
+// read to compute element indices.
+use std::cell::{Ref, RefCell};
+use std::ops::Range;
+
+use crate::*;
+
+fn row_major_strides<const N: usize>(shape: [u32; N]) -> [u32; N] {
+    let mut strides = [1u32; N];
+    for i in (0..N.saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * shape[i + 1];
+    }
+    strides
+}
+fn create_layout<const N: usize>(
+    device: &Device,
+    offset: u32,
+    shape: [u32; N],
+    strides: [u32; N],
+) -> backend::Result<Buffer<u32>> {
+    let mut layout = vec![offset];
+    layout.extend_from_slice(&shape);
+    layout.extend_from_slice(&strides);
+    device.create_buffer_from_slice(&layout)
+}
+
+pub struct Tensor<T: Value, const N: usize> {
+    buffer: Buffer<T>,
+    shape: [u32; N],
+    layout: Buffer<u32>,
+}
+impl<T: Value, const N: usize> Tensor<T, N> {
+    pub fn new(device: &Device, shape: [u32; N]) -> backend::Result<Self> {
+        let len = shape.iter().map(|s| *s as usize).product::<usize>();
+        Ok(Self {
+            buffer: device.create_buffer(len.max(1))?,
+            shape,
+            layout: create_layout(device, 0, shape, row_major_strides(shape))?,
+        })
+    }
+    // `data` is in row-major order
+    pub fn from_slice(device: &Device, shape: [u32; N], data: &[T]) -> backend::Result<Self> {
+        let tensor = Self::new(device, shape)?;
+        assert_eq!(data.len(), tensor.len());
+        if !data.is_empty() {
+            tensor.buffer.view(..data.len() as u64).copy_from(data);
+        }
+        Ok(tensor)
+    }
+    pub fn shape(&self) -> [u32; N] {
+        self.shape
+    }
+    pub fn len(&self) -> usize {
+        self.shape.iter().map(|s| *s as usize).product()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn buffer(&self) -> &Buffer<T> {
+        &self.buffer
+    }
+    pub fn view(&self) -> TensorView<T, N> {
+        TensorView::new(&self.buffer, 0, self.shape, row_major_strides(self.shape))
+    }
+    pub fn copy_to_vec(&self) -> Vec<T> {
+        self.buffer.view(..self.len() as u64).copy_to_vec()
+    }
+    pub fn copy_from(&self, data: &[T]) {
+        assert_eq!(data.len(), self.len());
+        self.buffer.view(..data.len() as u64).copy_from(data);
+    }
+    pub fn var(&self) -> TensorVar<T, N> {
+        TensorVar {
+            buffer: self.buffer.var(),
+            layout: self.layout.var(),
+        }
+    }
+}
+#[cfg(feature = "ndarray")]
+impl<T: Value, const N: usize> Tensor<T, N> {
+    pub fn from_ndarray<S: ndarray::Data<Elem = T>, D: ndarray::Dimension>(
+        device: &Device,
+        array: &ndarray::ArrayBase<S, D>,
+    ) -> backend::Result<Self> {
+        assert_eq!(array.ndim(), N);
+        let shape = std::array::from_fn(|i| array.shape()[i] as u32);
+        Self::from_slice(device, shape, &array.iter().copied().collect::<Vec<_>>())
+    }
+    pub fn to_ndarray(&self) -> ndarray::ArrayD<T> {
+        self.view().to_ndarray()
+    }
+}
+
+pub struct TensorView<'a, T: Value, const N: usize> {
+    buffer: &'a Buffer<T>,
+    offset: u32,
+    shape: [u32; N],
+    strides: [u32; N],
+    // Uploaded on first use, so intermediate views of a slice/permute chain cost nothing
+    layout: RefCell<Option<Buffer<u32>>>,
+}
+impl<'a, T: Value, const N: usize> TensorView<'a, T, N> {
+    fn new(buffer: &'a Buffer<T>, offset: u32, shape: [u32; N], strides: [u32; N]) -> Self {
+        Self {
+            buffer,
+            offset,
+            shape,
+            strides,
+            layout: RefCell::new(None),
+        }
+    }
+    // Uploads the layout, a view must be prepared before it is passed to a dispatch
+    pub fn prepare(&self) -> backend::Result<()> {
+        if self.layout.borrow().is_none() {
+            let device = &self.buffer.device;
+            let layout = create_layout(device, self.offset, self.shape, self.strides)?;
+            *self.layout.borrow_mut() = Some(layout);
+        }
+        Ok(())
+    }
+    fn layout(&self) -> Ref<Buffer<u32>> {
+        Ref::map(self.layout.borrow(), |layout| {
+            layout
+                .as_ref()
+                .expect("TensorView::prepare must be called before dispatching the view")
+        })
+    }
+    pub fn shape(&self) -> [u32; N] {
+        self.shape
+    }
+    pub fn strides(&self) -> [u32; N] {
+        self.strides
+    }
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+    pub fn len(&self) -> usize {
+        self.shape.iter().map(|s| *s as usize).product()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn is_contiguous(&self) -> bool {
+        self.strides == row_major_strides(self.shape)
+    }
+    // Restricts `axis` to `range`
+    pub fn slice(&self, axis: usize, range: Range<u32>) -> Self {
+        assert!(range.start <= range.end && range.end <= self.shape[axis]);
+        let mut shape = self.shape;
+        shape[axis] = range.end - range.start;
+        let offset = if shape[axis] == 0 {
+            self.offset
+        } else {
+            self.offset + range.start * self.strides[axis]
+        };
+        Self::new(self.buffer, offset, shape, self.strides)
+    }
+    // Axis i of the result is axis axes[i] of self
+    pub fn permute(&self, axes: [usize; N]) -> Self {
+        let mut seen = [false; N];
+        for a in axes {
+            assert!(!seen[a], "{:?} is not a permutation", axes);
+            seen[a] = true;
+        }
+        Self::new(
+            self.buffer,
+            self.offset,
+            axes.map(|a| self.shape[a]),
+            axes.map(|a| self.strides[a]),
+        )
+    }
+    pub fn transpose(&self, a: usize, b: usize) -> Self {
+        let mut axes: [usize; N] = std::array::from_fn(|i| i);
+        axes.swap(a, b);
+        self.permute(axes)
+    }
+    // Axes of extent 1 are repeated to the new extent without copying
+    pub fn broadcast_to(&self, shape: [u32; N]) -> Self {
+        let mut strides = self.strides;
+        for ((from, to), stride) in self.shape.iter().zip(shape).zip(strides.iter_mut()) {
+            if *from != to {
+                assert_eq!(*from, 1, "cannot broadcast {:?} to {:?}", self.shape, shape);
+                *stride = 0;
+            }
+        }
+        Self::new(self.buffer, self.offset, shape, strides)
+    }
+    // Gathers the elements in row-major order
+    pub fn copy_to_vec(&self) -> Vec<T> {
+        if self.is_empty() {
+            return vec![];
+        }
+        if self.is_contiguous() {
+            let begin = self.offset as u64;
+            return self
+                .buffer
+                .view(begin..begin + self.len() as u64)
+                .copy_to_vec();
+        }
+        let data = self.buffer.copy_to_vec();
+        let mut out = Vec::with_capacity(self.len());
+        let mut index = [0u32; N];
+        for _ in 0..self.len() {
+            let i = index
+                .iter()
+                .zip(self.strides)
+                .map(|(i, s)| i * s)
+                .sum::<u32>();
+            out.push(data[(self.offset + i) as usize]);
+            for (i, extent) in index.iter_mut().zip(self.shape).rev() {
+                *i += 1;
+                if *i < extent {
+                    break;
+                }
+                *i = 0;
+            }
+        }
+        out
+    }
+    // Prepares the view, so a captured view needs no separate `prepare` call
+    pub fn var(&self) -> backend::Result<TensorVar<T, N>> {
+        self.prepare()?;
+        Ok(TensorVar {
+            buffer: self.buffer.var(),
+            layout: self.layout().var(),
+        })
+    }
+}
+#[cfg(feature = "ndarray")]
+impl<'a, T: Value, const N: usize> TensorView<'a, T, N> {
+    pub fn to_ndarray(&self) -> ndarray::ArrayD<T> {
+        let shape = self.shape.map(|s| s as usize);
+        ndarray::ArrayD::from_shape_vec(ndarray::IxDyn(&shape), self.copy_to_vec()).unwrap()
+    }
+}
+impl<T: Value, const N: usize> KernelArg for Tensor<T, N> {
+    type Parameter = TensorVar<T, N>;
+    fn encode(&self, encoder: &mut ArgEncoder) {
+        encoder.buffer(&self.buffer);
+        encoder.buffer(&self.layout);
+    }
+}
+impl<'a, T: Value, const N: usize> KernelArg for TensorView<'a, T, N> {
+    type Parameter = TensorVar<T, N>;
+    fn encode(&self, encoder: &mut ArgEncoder) {
+        encoder.buffer(self.buffer);
+        encoder.buffer(&self.layout());
+    }
+}
+impl<T: Value, const N: usize> AsKernelArg<Tensor<T, N>> for Tensor<T, N> {}
+impl<'a, T: Value, const N: usize> AsKernelArg<Tensor<T, N>> for TensorView<'a, T, N> {}
+
+pub struct TensorVar<T: Value, const N: usize> {
+    buffer: BufferVar<T>,
+    layout: BufferVar<u32>,
+}
+impl<T: Value, const N: usize> KernelParameter for TensorVar<T, N> {
+    fn def_param(builder: &mut KernelBuilder) -> Self {
+        Self {
+            buffer: builder.buffer(),
+            layout: builder.buffer(),
+        }
+    }
+}
+impl<T: Value, const N: usize> TensorVar<T, N> {
+    pub fn shape(&self, axis: usize) -> Expr<u32> {
+        assert!(axis < N);
+        self.layout.read(1 + axis as u32)
+    }
+    pub fn stride(&self, axis: usize) -> Expr<u32> {
+        assert!(axis < N);
+        self.layout.read(1 + (N + axis) as u32)
+    }
+    pub fn len(&self) -> Expr<u32> {
+        (0..N).fold(const_(1u32), |len, axis| len * self.shape(axis))
+    }
+    // Index of the element in the underlying buffer
+    pub fn offset_of(&self, index: [Expr<u32>; N]) -> Expr<u32> {
+        (0..N).fold(self.layout.read(0u32), |offset, axis| {
+            offset + index[axis] * self.stride(axis)
+        })
+    }
+    pub fn read(&self, index: [Expr<u32>; N]) -> Expr<T> {
+        self.buffer.read(self.offset_of(index))
+    }
+    // Writing through a broadcast view makes several threads store to the same element
+    pub fn write(&self, index: [Expr<u32>; N], v: impl Into<Expr<T>>) {
+        self.buffer.write(self.offset_of(index), v);
+    }
+}
@@ -523,6 +523,93 @@ fn autodiff_mat_det() {
         m.determinant()
     });
 }
+#[test]
+fn autodiff_quat_rotate() {
+    init();
+    autodiff_helper(0.5..2.0, 1024 * 1024, 7, |inputs| {
+        let q = QuatExpr::new(inputs[0], inputs[1], inputs[2], inputs[3]).normalize();
+        let v = make_float3(inputs[4], inputs[5], inputs[6]);
+        let r = q * v;
+        r.dot(make_float3(1.0f32, 2.0f32, 3.0f32)) + (q * q).w()
+    });
+}
+#[test]
+fn autodiff_mat3_svd() {
+    init();
+    autodiff_helper(-2.0..2.0, 1024 * 1024, 9, |inputs| {
+        let col = |j: usize| make_float3(inputs[j * 3], inputs[j * 3 + 1], inputs[j * 3 + 2]);
+        let m = Mat3Expr::new(col(0), col(1), col(2));
+        let (_, sigma, _) = m.svd();
+        sigma.dot(make_float3(1.0f32, 2.0f32, 3.0f32))
+    });
+}
+#[test]
+fn autodiff_mat3_eigen_symmetric() {
+    init();
+    autodiff_helper(-2.0..2.0, 1024 * 1024, 9, |inputs| {
+        let col = |j: usize| make_float3(inputs[j * 3], inputs[j * 3 + 1], inputs[j * 3 + 2]);
+        let m = Mat3Expr::new(col(0), col(1), col(2));
+        let (q, lambda) = m.eigen_symmetric();
+        // eigenvectors are defined up to sign
+        let e = q.col(0).dot(make_float3(0.5f32, -1.0f32, 2.0f32));
+        lambda.dot(make_float3(1.0f32, 2.0f32, 3.0f32)) + e * e
+    });
+}
+#[test]
+fn autodiff_mat3_polar() {
+    init();
+    autodiff_helper(-2.0..2.0, 1024 * 1024, 9, |inputs| {
+        let col = |j: usize| make_float3(inputs[j * 3], inputs[j * 3 + 1], inputs[j * 3 + 2]);
+        let m = Mat3Expr::new(col(0), col(1), col(2));
+        let (r, s) = m.polar();
+        r.col(0).dot(make_float3(1.0f32, 2.0f32, 3.0f32)) + r.col(2).y() + s.col(1).x()
+    });
+}
+#[test]
+fn autodiff_mat3_polar_repeated() {
+    init();
+    // a scaled rotation about z has two equal singular values
+    autodiff_helper(0.5..2.0, 1024 * 1024, 3, |inputs| {
+        let (x, z, y) = (inputs[0], inputs[1], inputs[2]);
+        let zero = const_(0.0f32);
+        let m = Mat3Expr::new(
+            make_float3(x, z, zero),
+            make_float3(-z, x, zero),
+            make_float3(zero, zero, y),
+        );
+        let (r, s) = m.polar();
+        let (_, sigma, _) = m.svd();
+        r.col(0).dot(make_float3(1.0f32, 2.0f32, 3.0f32))
+            + s.col(1).y()
+            + sigma.x()
+            + sigma.y()
+            + sigma.z()
+    });
+}
+#[test]
+fn autodiff_complex() {
+    init();
+    autodiff_helper(0.5..2.0, 1024 * 1024, 4, |inputs| {
+        let a = Complex32Expr::new(inputs[0], inputs[1]);
+        let b = Complex32Expr::new(inputs[2], -inputs[3]);
+        let c = (a * b.conj() * 0.25f32).exp() / (a + b) - b.sqrt() * 0.5f32;
+        c.re() + c.abs() + (a / b).arg()
+    });
+}
+#[test]
+fn autodiff_shading_ops() {
+    init();
+    autodiff_helper(0.1..1.0, 1024 * 1024, 4, |inputs| {
+        let x = inputs[0];
+        let y = inputs[1];
+        let t = inputs[2];
+        let v = make_float3(x, y, inputs[3]).normalize();
+        let n = make_float3(0.0f32, 1.0f32, 0.0f32);
+        x.lerp(y, t) + t.smoothstep(0.2f32, 0.9f32) * x.radians()
+            + v.reflect(n).dot(make_float3(1.0f32, 2.0f32, 3.0f32))
+            + v.refract(n, const_(0.8f32)).x()
+    });
+}
 // #[test]
 // fn autodiff_vec3_reduce_min(){
 //     init();
@@ -34,6 +34,1468 @@ fn vec_cast() {
     }
 }
 #[test]
+fn byte_half_cast() {
+    init();
+    let device = get_device();
+    let packed: Buffer<Ubyte4> = device.create_buffer(1024).unwrap();
+    let half: Buffer<Half2> = device.create_buffer(1024).unwrap();
+    let signed: Buffer<i8> = device.create_buffer(1024).unwrap();
+    let unpacked: Buffer<Float4> = device.create_buffer(1024).unwrap();
+    packed.view(..).fill_fn(|i| {
+        let i = i as u8;
+        Ubyte4::new(i, i.wrapping_add(1), i.wrapping_mul(3), 255 - i)
+    });
+    half.view(..)
+        .fill_fn(|i| Half2::new(f16::from_f32(i as f32 * 0.25), f16::from_f32(-1.5)));
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tid = dispatch_id().x();
+            let p = packed.var().read(tid);
+            let h = half.var().read(tid);
+            let s = p.x().char() ^ -128i8;
+            signed.var().write(tid, s);
+            let f = (h * f16::from_f32(2.0)).float();
+            unpacked.var().write(
+                tid,
+                make_float4(p.x().float(), p.w().float(), f.x(), f.y()),
+            );
+        })
+        .unwrap();
+    kernel.dispatch([1024, 1, 1]).unwrap();
+    let signed = signed.view(..).copy_to_vec();
+    let unpacked = unpacked.view(..).copy_to_vec();
+    for i in 0..1024 {
+        let b = i as u8;
+        assert_eq!(signed[i], (b ^ 0x80) as i8);
+        assert_eq!(unpacked[i].x, b as f32);
+        assert_eq!(unpacked[i].y, (255 - b) as f32);
+        assert_eq!(unpacked[i].z, f16::from_f32(i as f32 * 0.25 * 2.0).to_f32());
+        assert_eq!(unpacked[i].w, -3.0);
+    }
+}
+#[test]
+fn double_vec_mat() {
+    init();
+    let device = get_device();
+    let a: Buffer<Double3> = device.create_buffer(1024).unwrap();
+    let b: Buffer<Double3> = device.create_buffer(1024).unwrap();
+    let cross: Buffer<Double3> = device.create_buffer(1024).unwrap();
+    let solved: Buffer<Double3> = device.create_buffer(1024).unwrap();
+    let mut rng = rand::thread_rng();
+    a.view(..)
+        .fill_fn(|_| Double3::new(rng.gen(), rng.gen(), rng.gen()));
+    b.view(..)
+        .fill_fn(|_| Double3::new(rng.gen(), rng.gen(), rng.gen()));
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tid = dispatch_id().x();
+            let a = a.var().read(tid);
+            let b = b.var().read(tid);
+            let c = a.cross(b);
+            cross.var().write(tid, c);
+            let m = DMat3Expr::new(a, b, c + make_double3(1.0, 0.0, 0.0));
+            solved.var().write(tid, m.inverse() * (m * a));
+        })
+        .unwrap();
+    kernel.dispatch([1024, 1, 1]).unwrap();
+    let a = a.view(..).copy_to_vec();
+    let b = b.view(..).copy_to_vec();
+    let cross = cross.view(..).copy_to_vec();
+    let solved = solved.view(..).copy_to_vec();
+    for i in 0..1024 {
+        let (a, b) = (glam::DVec3::from(a[i]), glam::DVec3::from(b[i]));
+        let expected = a.cross(b);
+        let got = glam::DVec3::from(cross[i]);
+        assert!((got - expected).length() < 1e-12);
+        let m = glam::DMat3::from_cols(a, b, expected + glam::DVec3::X);
+        if m.determinant().abs() > 1e-6 {
+            assert!((glam::DVec3::from(solved[i]) - a).length() < 1e-6);
+        }
+    }
+}
+#[test]
+fn quat_ops() {
+    init();
+    let device = get_device();
+    let mut rng = rand::thread_rng();
+    let mut random_quat = || {
+        glam::Quat::from_axis_angle(
+            glam::Vec3::new(rng.gen(), rng.gen(), rng.gen()).normalize(),
+            rng.gen_range(-3.0..3.0),
+        )
+    };
+    let qs = (0..1024 * 2).map(|_| Quat::from(random_quat())).collect::<Vec<_>>();
+    let qs = device.create_buffer_from_slice(&qs).unwrap();
+    let v = Float3::new(0.3, -1.2, 2.0);
+    let product: Buffer<Quat> = device.create_buffer(1024).unwrap();
+    let slerp: Buffer<Quat> = device.create_buffer(1024).unwrap();
+    let rotated: Buffer<Float3> = device.create_buffer(1024).unwrap();
+    let mat_rotated: Buffer<Float3> = device.create_buffer(1024).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tid = dispatch_id().x();
+            let a = qs.var().read(tid * 2);
+            let b = qs.var().read(tid * 2 + 1);
+            let v: Expr<Float3> = v.into();
+            product.var().write(tid, a * b);
+            slerp.var().write(tid, a.slerp(b, 0.3f32));
+            rotated.var().write(tid, a * v);
+            mat_rotated.var().write(tid, a.to_mat3() * v);
+        })
+        .unwrap();
+    kernel.dispatch([1024, 1, 1]).unwrap();
+    let qs = qs.view(..).copy_to_vec();
+    let product = product.view(..).copy_to_vec();
+    let slerp = slerp.view(..).copy_to_vec();
+    let rotated = rotated.view(..).copy_to_vec();
+    let mat_rotated = mat_rotated.view(..).copy_to_vec();
+    let v = glam::Vec3::from(v);
+    for i in 0..1024 {
+        let a = glam::Quat::from(qs[i * 2]);
+        let b = glam::Quat::from(qs[i * 2 + 1]);
+        let same = |x: glam::Quat, y: glam::Quat| x.dot(y).abs() > 1.0 - 1e-4;
+        assert!(same(glam::Quat::from(product[i]), a * b));
+        assert!(same(glam::Quat::from(slerp[i]), a.slerp(b, 0.3)));
+        assert!((glam::Vec3::from(rotated[i]) - a * v).length() < 1e-4);
+        assert!((glam::Vec3::from(mat_rotated[i]) - a * v).length() < 1e-4);
+    }
+}
+#[test]
+fn complex_ops() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    let n = 1024;
+    let zs = (0..n * 2)
+        .map(|_| Complex32::new(rng.gen_range(-2.0..2.0), rng.gen_range(-2.0..2.0)))
+        .collect::<Vec<_>>();
+    let zs = device.create_buffer_from_slice(&zs).unwrap();
+    let arith: Buffer<Complex32> = device.create_buffer(n * 4).unwrap();
+    let funcs: Buffer<Complex32> = device.create_buffer(n * 3).unwrap();
+    let polar: Buffer<Float2> = device.create_buffer(n).unwrap();
+    let wide: Buffer<Complex64> = device.create_buffer(n).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tid = dispatch_id().x();
+            let a = zs.var().read(tid * 2);
+            let b = zs.var().read(tid * 2 + 1);
+            arith.var().write(tid * 4, a + b);
+            arith.var().write(tid * 4 + 1, a - b * 2.0f32);
+            arith.var().write(tid * 4 + 2, a * b.conj());
+            arith.var().write(tid * 4 + 3, -a / b);
+            funcs.var().write(tid * 3, a.exp());
+            funcs.var().write(tid * 3 + 1, a.sqrt());
+            funcs
+                .var()
+                .write(tid * 3 + 2, Complex32Expr::from_polar(a.abs(), a.arg()));
+            polar.var().write(tid, make_float2(a.abs(), a.arg()));
+            let w = Complex64Expr::new(a.re().double(), a.im().double());
+            wide.var().write(tid, w * w);
+        })
+        .unwrap();
+    kernel.dispatch([n as u32, 1, 1]).unwrap();
+    let zs = zs.view(..).copy_to_vec();
+    let arith = arith.view(..).copy_to_vec();
+    let funcs = funcs.view(..).copy_to_vec();
+    let polar = polar.view(..).copy_to_vec();
+    let wide = wide.view(..).copy_to_vec();
+    let mul = |a: Complex32, b: Complex32| {
+        Complex32::new(a.re * b.re - a.im * b.im, a.re * b.im + a.im * b.re)
+    };
+    let close = |a: Complex32, b: Complex32, eps: f32| {
+        (a.re - b.re).abs() < eps * (1.0 + b.abs()) && (a.im - b.im).abs() < eps * (1.0 + b.abs())
+    };
+    for i in 0..n {
+        let (a, b) = (zs[i * 2], zs[i * 2 + 1]);
+        let sum = Complex32::new(a.re + b.re, a.im + b.im);
+        assert!(close(arith[i * 4], sum, 1e-6));
+        let diff = Complex32::new(a.re - 2.0 * b.re, a.im - 2.0 * b.im);
+        assert!(close(arith[i * 4 + 1], diff, 1e-6));
+        assert!(close(arith[i * 4 + 2], mul(a, b.conj()), 1e-5));
+        // -a / b = -a * conj(b) / |b|^2
+        let q = mul(a, b.conj());
+        let norm = b.re * b.re + b.im * b.im;
+        let quotient = Complex32::new(-q.re / norm, -q.im / norm);
+        assert!(close(arith[i * 4 + 3], quotient, 1e-4));
+
+        let exp = Complex32::from_polar(a.re.exp(), a.im);
+        assert!(close(funcs[i * 3], exp, 1e-5));
+        let root = funcs[i * 3 + 1];
+        assert!(root.re >= 0.0);
+        assert!(close(mul(root, root), a, 1e-5));
+        assert!(close(funcs[i * 3 + 2], a, 1e-5));
+        assert!((polar[i].x - a.abs()).abs() < 1e-5);
+        assert!((polar[i].y - a.arg()).abs() < 1e-5);
+
+        let (re, im) = (a.re as f64, a.im as f64);
+        let square = Complex64::new(re * re - im * im, 2.0 * re * im);
+        assert!((wide[i].re - square.re).abs() < 1e-12);
+        assert!((wide[i].im - square.im).abs() < 1e-12);
+    }
+}
+#[test]
+fn affine_mat3x4() {
+    init();
+    let device = get_device();
+    let mut rng = rand::thread_rng();
+    let mut random_affine = || {
+        glam::Affine3A::from_scale_rotation_translation(
+            glam::Vec3::new(rng.gen(), rng.gen(), rng.gen()) + 0.5,
+            glam::Quat::from_axis_angle(
+                glam::Vec3::new(rng.gen(), rng.gen(), rng.gen()).normalize(),
+                rng.gen_range(-3.0..3.0),
+            ),
+            glam::Vec3::new(rng.gen(), rng.gen(), rng.gen()),
+        )
+    };
+    let ms = (0..1024 * 2)
+        .map(|_| Mat3x4::from(random_affine()))
+        .collect::<Vec<_>>();
+    let ms = device.create_buffer_from_slice(&ms).unwrap();
+    let p = Float3::new(0.3, -1.2, 2.0);
+    let points: Buffer<Float3> = device.create_buffer(1024).unwrap();
+    let composed: Buffer<Mat3x4> = device.create_buffer(1024).unwrap();
+    let gram: Buffer<Mat3> = device.create_buffer(1024).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tid = dispatch_id().x();
+            let a = ms.var().read(tid * 2);
+            let b = ms.var().read(tid * 2 + 1);
+            let p: Expr<Float3> = p.into();
+            points.var().write(tid, a.transform_point(p));
+            composed.var().write(tid, a.compose(b));
+            gram.var().write(tid, a * a.transpose());
+        })
+        .unwrap();
+    kernel.dispatch([1024, 1, 1]).unwrap();
+    let ms = ms.view(..).copy_to_vec();
+    let points = points.view(..).copy_to_vec();
+    let composed = composed.view(..).copy_to_vec();
+    let gram = gram.view(..).copy_to_vec();
+    let p = glam::Vec3::from(p);
+    for i in 0..1024 {
+        let a = glam::Mat4::from(Mat4::from(ms[i * 2]));
+        let b = glam::Mat4::from(Mat4::from(ms[i * 2 + 1]));
+        assert!((glam::Vec3::from(points[i]) - a.transform_point3(p)).length() < 1e-4);
+        let c = glam::Mat4::from(Mat4::from(composed[i]));
+        assert!(c.abs_diff_eq(a * b, 1e-4));
+        let g = glam::Mat3::from(gram[i]);
+        let l = glam::Mat3::from_mat4(a);
+        let t = a.w_axis.truncate();
+        let expected = l * l.transpose() + glam::Mat3::from_cols(t * t.x, t * t.y, t * t.z);
+        assert!(g.abs_diff_eq(expected, 1e-3));
+    }
+}
+#[test]
+fn mat3_decompositions() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut random_rotation = || {
+        glam::Mat3::from_axis_angle(
+            glam::Vec3::new(rng.gen(), rng.gen(), rng.gen()).normalize(),
+            rng.gen_range(-3.0..3.0),
+        )
+    };
+    let mut ms = vec![
+        glam::Mat3::ZERO,
+        glam::Mat3::IDENTITY,
+        glam::Mat3::from_diagonal(glam::Vec3::new(3.0, 3.0, 1.0)),
+        glam::Mat3::from_diagonal(glam::Vec3::new(1.0, 2.0, -3.0)),
+        random_rotation()
+            * glam::Mat3::from_diagonal(glam::Vec3::new(2.0, 2.0, -1.0))
+            * random_rotation(),
+    ];
+    while ms.len() < 1024 {
+        let m = glam::Mat3::from_cols_array(&std::array::from_fn(|_| rng.gen_range(-2.0..2.0)));
+        if ms.len() % 8 == 0 {
+            // rank deficient
+            let sum = m.x_axis + m.y_axis;
+            ms.push(glam::Mat3::from_cols(m.x_axis, m.y_axis, sum));
+        } else {
+            ms.push(m);
+        }
+    }
+    let input = device
+        .create_buffer_from_slice(&ms.iter().map(|m| Mat3::from(*m)).collect::<Vec<_>>())
+        .unwrap();
+    let u: Buffer<Mat3> = device.create_buffer(ms.len()).unwrap();
+    let sigma: Buffer<Float3> = device.create_buffer(ms.len()).unwrap();
+    let v: Buffer<Mat3> = device.create_buffer(ms.len()).unwrap();
+    let q: Buffer<Mat3> = device.create_buffer(ms.len()).unwrap();
+    let lambda: Buffer<Float3> = device.create_buffer(ms.len()).unwrap();
+    let r: Buffer<Mat3> = device.create_buffer(ms.len()).unwrap();
+    let s: Buffer<Mat3> = device.create_buffer(ms.len()).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tid = dispatch_id().x();
+            let m = input.var().read(tid);
+            let (u_, sigma_, v_) = m.svd();
+            u.var().write(tid, u_);
+            sigma.var().write(tid, sigma_);
+            v.var().write(tid, v_);
+            let (q_, lambda_) = m.eigen_symmetric();
+            q.var().write(tid, q_);
+            lambda.var().write(tid, lambda_);
+            let (r_, s_) = m.polar();
+            r.var().write(tid, r_);
+            s.var().write(tid, s_);
+        })
+        .unwrap();
+    kernel.dispatch([ms.len() as u32, 1, 1]).unwrap();
+    let to_glam = |b: &Buffer<Mat3>| {
+        b.view(..)
+            .copy_to_vec()
+            .into_iter()
+            .map(glam::Mat3::from)
+            .collect::<Vec<_>>()
+    };
+    let (u, v, q) = (to_glam(&u), to_glam(&v), to_glam(&q));
+    let (r, s) = (to_glam(&r), to_glam(&s));
+    let sigma = sigma.view(..).copy_to_vec();
+    let lambda = lambda.view(..).copy_to_vec();
+    let is_rotation = |m: glam::Mat3| {
+        (m.transpose() * m).abs_diff_eq(glam::Mat3::IDENTITY, 1e-4)
+            && (m.determinant() - 1.0).abs() < 1e-4
+    };
+    for (i, &m) in ms.iter().enumerate() {
+        let eps = 1e-4 * (1.0 + m.to_cols_array().iter().fold(0.0f32, |a, x| a.max(x.abs())));
+        let sv = glam::Vec3::from(sigma[i]);
+        assert!(is_rotation(u[i]) && is_rotation(v[i]), "{}", i);
+        let sorted = sv.x >= sv.y - eps && sv.y >= sv.z.abs() - eps;
+        assert!(sorted, "{} {:?}", i, sv);
+        assert!(
+            (u[i] * glam::Mat3::from_diagonal(sv) * v[i].transpose()).abs_diff_eq(m, eps),
+            "{}",
+            i
+        );
+
+        let sym = (m + m.transpose()) * 0.5;
+        let lv = glam::Vec3::from(lambda[i]);
+        assert!(is_rotation(q[i]), "{}", i);
+        assert!(lv.x >= lv.y - eps && lv.y >= lv.z - eps, "{} {:?}", i, lv);
+        assert!(
+            (q[i] * glam::Mat3::from_diagonal(lv) * q[i].transpose()).abs_diff_eq(sym, eps),
+            "{}",
+            i
+        );
+
+        assert!(is_rotation(r[i]), "{}", i);
+        assert!(s[i].abs_diff_eq(s[i].transpose(), eps), "{}", i);
+        assert!((r[i] * s[i]).abs_diff_eq(m, eps), "{}", i);
+    }
+}
+#[test]
+fn rng_matches_host() {
+    init();
+    let device = get_device();
+    let n = 1024;
+    let pcg: Buffer<Uint4> = device.create_buffer(n).unwrap();
+    let philox: Buffer<Uint4> = device.create_buffer(n).unwrap();
+    let xorshift: Buffer<Uint4> = device.create_buffer(n).unwrap();
+    let sobol: Buffer<Float4> = device.create_buffer(n).unwrap();
+    let halton: Buffer<Float4> = device.create_buffer(n).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tid = dispatch_id().x();
+            let a = var!(Pcg32, Pcg32Expr::new_seeded(tid.ulong(), const_(7u64)));
+            let b = var!(Philox4x32, Philox4x32Expr::new_seeded(tid.ulong(), const_(3u64)));
+            let c = var!(Xorshift32, Xorshift32Expr::new_seeded(tid));
+            let (a0, a1) = (a.next_u32(), a.next_u32());
+            let (a2, a3) = (a.next_u32(), a.next_u32());
+            pcg.var().write(tid, make_uint4(a0, a1, a2, a3));
+            let _ = b.next_uint4();
+            philox.var().write(tid, b.next_uint4());
+            let (c0, c1) = (c.next_u32(), c.next_u32());
+            let (c2, c3) = (c.next_u32(), c.next_u32());
+            xorshift.var().write(tid, make_uint4(c0, c1, c2, c3));
+            sobol.var().write(
+                tid,
+                make_float4(
+                    rng::sobol(tid, 0),
+                    rng::sobol(tid, 1),
+                    rng::sobol(tid, 5),
+                    rng::sobol_scrambled(tid, 7, const_(0x1234567u32)),
+                ),
+            );
+            halton.var().write(
+                tid,
+                make_float4(
+                    rng::halton(tid, 0),
+                    rng::halton(tid, 1),
+                    rng::halton(tid, 4),
+                    rng::halton(tid, 15),
+                ),
+            );
+        })
+        .unwrap();
+    kernel.dispatch([n as u32, 1, 1]).unwrap();
+    let pcg = pcg.view(..).copy_to_vec();
+    let philox = philox.view(..).copy_to_vec();
+    let xorshift = xorshift.view(..).copy_to_vec();
+    let sobol = sobol.view(..).copy_to_vec();
+    let halton = halton.view(..).copy_to_vec();
+    for i in 0..n {
+        let mut a = Pcg32::new(i as u64, 7);
+        let expected = [a.next_u32(), a.next_u32(), a.next_u32(), a.next_u32()];
+        assert_eq!([pcg[i].x, pcg[i].y, pcg[i].z, pcg[i].w], expected);
+        let mut b = Philox4x32::new(i as u64, 3);
+        b.next_uint4();
+        let expected = b.next_uint4();
+        assert_eq!(
+            [philox[i].x, philox[i].y, philox[i].z, philox[i].w],
+            [expected.x, expected.y, expected.z, expected.w]
+        );
+        let mut c = Xorshift32::new(i as u32);
+        let expected = [c.next_u32(), c.next_u32(), c.next_u32(), c.next_u32()];
+        assert_eq!([xorshift[i].x, xorshift[i].y, xorshift[i].z, xorshift[i].w], expected);
+        let i = i as u32;
+        assert_eq!(sobol[i as usize].x, rng::host::sobol(i, 0));
+        assert_eq!(sobol[i as usize].y, rng::host::sobol(i, 1));
+        assert_eq!(sobol[i as usize].z, rng::host::sobol(i, 5));
+        assert_eq!(sobol[i as usize].w, rng::host::sobol_scrambled(i, 7, 0x1234567));
+        let h = halton[i as usize];
+        assert!((h.x - rng::host::halton(i, 0)).abs() < 1e-6);
+        assert!((h.y - rng::host::halton(i, 1)).abs() < 1e-6);
+        assert!((h.z - rng::host::halton(i, 4)).abs() < 1e-6);
+        assert!((h.w - rng::host::halton(i, 15)).abs() < 1e-6);
+    }
+    // the first points of the second Sobol dimension
+    let s: Vec<_> = (0..4).map(|i| rng::host::sobol(i, 1)).collect();
+    assert_eq!(s, vec![0.0, 0.5, 0.75, 0.25]);
+}
+#[test]
+fn shading_stdlib() {
+    init();
+    let device = get_device();
+    let mut rng = rand::thread_rng();
+    let n = 1024;
+    let x: Buffer<f32> = device.create_buffer(n).unwrap();
+    let y: Buffer<f32> = device.create_buffer(n).unwrap();
+    let bits: Buffer<u32> = device.create_buffer(n).unwrap();
+    x.view(..).fill_fn(|_| rng.gen_range(-10.0..10.0));
+    y.view(..).fill_fn(|_| rng.gen_range(0.5..3.0));
+    bits.view(..).fill_fn(|i| if i == 0 { 0 } else { rng.gen() });
+    let scalar_out: Buffer<Float4> = device.create_buffer(n * 2).unwrap();
+    let frexp_out: Buffer<Float2> = device.create_buffer(n).unwrap();
+    let int_out: Buffer<Uint4> = device.create_buffer(n).unwrap();
+    let vec_out: Buffer<Float3> = device.create_buffer(n * 2).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tid = dispatch_id().x();
+            let x = x.var().read(tid);
+            let y = y.var().read(tid);
+            let b = bits.var().read(tid);
+            let out = scalar_out.var();
+            out.write(
+                tid * 2,
+                make_float4(x.sign(), x.step(0.5f32), x.smoothstep(-5.0f32, 5.0f32), x.fmod(y)),
+            );
+            out.write(
+                tid * 2 + 1,
+                make_float4(x.saturate(), x.lerp(y, 0.25f32), x.radians(), x.ldexp(const_(3i32))),
+            );
+            let (m, e) = x.frexp();
+            frexp_out.var().write(tid, make_float2(m, e.float()));
+            int_out
+                .var()
+                .write(tid, make_uint4(b.popcount(), b.clz(), b.ctz(), b.reverse_bits()));
+            let i = make_float3(x, -1.0f32, y).normalize();
+            let nrm = make_float3(0.0f32, 1.0f32, 0.0f32);
+            vec_out.var().write(tid * 2, i.reflect(nrm));
+            vec_out.var().write(tid * 2 + 1, i.refract(nrm, const_(0.7f32)));
+        })
+        .unwrap();
+    kernel.dispatch([n as u32, 1, 1]).unwrap();
+    let x = x.view(..).copy_to_vec();
+    let y = y.view(..).copy_to_vec();
+    let bits = bits.view(..).copy_to_vec();
+    let scalar_out = scalar_out.view(..).copy_to_vec();
+    let frexp_out = frexp_out.view(..).copy_to_vec();
+    let int_out = int_out.view(..).copy_to_vec();
+    let vec_out = vec_out.view(..).copy_to_vec();
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-4 * (1.0 + b.abs());
+    for i in 0..n {
+        let (x, y, b) = (x[i], y[i], bits[i]);
+        let a = scalar_out[i * 2];
+        let sign = if x > 0.0 { 1.0 } else if x < 0.0 { -1.0 } else { 0.0 };
+        assert_eq!(a.x, sign);
+        assert_eq!(a.y, if x < 0.5 { 0.0 } else { 1.0 });
+        let t = ((x + 5.0) / 10.0).clamp(0.0, 1.0);
+        assert!(close(a.z, t * t * (3.0 - 2.0 * t)));
+        assert!(close(a.w, x % y));
+        let c = scalar_out[i * 2 + 1];
+        assert_eq!(c.x, x.clamp(0.0, 1.0));
+        assert!(close(c.y, x + (y - x) * 0.25));
+        assert!(close(c.z, x.to_radians()));
+        assert!(close(c.w, x * 8.0));
+        let f = frexp_out[i];
+        assert!(f.x.abs() >= 0.5 && f.x.abs() < 1.0);
+        assert!(close(f.x * 2.0f32.powi(f.y as i32), x));
+        let r = int_out[i];
+        assert_eq!(
+            [r.x, r.y, r.z, r.w],
+            [b.count_ones(), b.leading_zeros(), b.trailing_zeros(), b.reverse_bits()]
+        );
+        let d = glam::Vec3::new(x, -1.0, y).normalize();
+        let nrm = glam::Vec3::Y;
+        let reflected = d - 2.0 * d.dot(nrm) * nrm;
+        assert!((glam::Vec3::from(vec_out[i * 2]) - reflected).length() < 1e-4);
+        let k = 1.0 - 0.49 * (1.0 - d.dot(nrm) * d.dot(nrm));
+        let refracted = if k < 0.0 {
+            glam::Vec3::ZERO
+        } else {
+            0.7 * d - (0.7 * d.dot(nrm) + k.sqrt()) * nrm
+        };
+        assert!((glam::Vec3::from(vec_out[i * 2 + 1]) - refracted).length() < 1e-4);
+    }
+}
+#[test]
+fn angle_conversion_and_ldexp_range() {
+    init();
+    let device = get_device();
+    let x: Buffer<f64> = device
+        .create_buffer_from_slice(&[0.1, 1.0, 123.456])
+        .unwrap();
+    let angles: Buffer<Double2> = device.create_buffer(3).unwrap();
+    let scaled: Buffer<Float4> = device.create_buffer(1).unwrap();
+    let split: Buffer<Float4> = device.create_buffer(1).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tid = dispatch_id().x();
+            let v = x.var().read(tid);
+            let angle = make_double2(v.radians(), v.degrees());
+            angles.var().write(tid, angle);
+            // the exponents are outside of what a single exp2 can represent in f32
+            let tiny = const_(2.0f32.powi(-100));
+            let huge = const_(2.0f32.powi(100));
+            scaled.var().write(
+                0,
+                make_float4(
+                    tiny.ldexp(const_(150i32)),
+                    huge.ldexp(const_(-200i32)),
+                    huge.ldexp(const_(-220i32)),
+                    const_(3.0f32).ldexp(const_(0i32)),
+                ),
+            );
+            // subnormal inputs, where 2^-e alone is past the largest exponent
+            let (m32, e32) = const_(1.5f32 * 2.0f32.powi(-140)).frexp();
+            let (m16, e16) = const_(f16::from_f32(1.5 * 2.0f32.powi(-20))).frexp();
+            split
+                .var()
+                .write(0, make_float4(m32, e32.float(), m16.float(), e16.float()));
+        })
+        .unwrap();
+    kernel.dispatch([3, 1, 1]).unwrap();
+    let x = x.view(..).copy_to_vec();
+    let angles = angles.view(..).copy_to_vec();
+    for (v, a) in x.iter().zip(&angles) {
+        assert!((a.x - v.to_radians()).abs() <= 1e-15 * v.to_radians().abs());
+        assert!((a.y - v.to_degrees()).abs() <= 1e-15 * v.to_degrees().abs());
+    }
+    let scaled = scaled.view(..).copy_to_vec()[0];
+    assert_eq!(scaled.x, 2.0f32.powi(50));
+    assert_eq!(scaled.y, 2.0f32.powi(-100));
+    assert_eq!(scaled.z, 2.0f32.powi(-120));
+    assert_eq!(scaled.w, 3.0);
+    let split = split.view(..).copy_to_vec()[0];
+    assert_eq!([split.x, split.y], [0.75, -139.0]);
+    assert_eq!([split.z, split.w], [0.75, -19.0]);
+}
+#[test]
+fn packed_vec3() {
+    init();
+    let device = get_device();
+    let mut rng = rand::thread_rng();
+    let n = 1024;
+    let src = (0..n)
+        .map(|_| PackedFloat3::new(rng.gen(), rng.gen(), rng.gen()))
+        .collect::<Vec<_>>();
+    let x = device.create_buffer_from_slice(&src).unwrap();
+    let y: Buffer<PackedFloat3> = device.create_buffer(n).unwrap();
+    let z: Buffer<PackedInt3> = device.create_buffer(n).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tid = dispatch_id().x();
+            let v = x.var().read(tid).unpack();
+            y.var().write(tid, v * 2.0f32 + 1.0f32);
+            z.var().write(tid, make_int3(tid.int(), -tid.int(), (v.x() * 100.0f32).int()));
+        })
+        .unwrap();
+    kernel.dispatch([n as u32, 1, 1]).unwrap();
+    let y = y.view(..).copy_to_vec();
+    let z = z.view(..).copy_to_vec();
+    for i in 0..n {
+        let v = glam::Vec3::from(src[i]);
+        let expected = v * 2.0 + 1.0;
+        assert!((glam::Vec3::from(y[i]) - expected).length() < 1e-5);
+        assert_eq!(z[i].x, i as i32);
+        assert_eq!(z[i].y, -(i as i32));
+        assert_eq!(z[i].z, (v.x * 100.0) as i32);
+    }
+}
+#[test]
+fn algo_reduce_scan() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    let n = 100_000;
+    let values: Vec<u32> = (0..n).map(|_| rng.gen_range(0..16)).collect();
+    let heads: Vec<bool> = (0..n).map(|i| i == 0 || rng.gen_ratio(1, 1000)).collect();
+    let input = device.create_buffer_from_slice(&values).unwrap();
+    let head_flags = device.create_buffer_from_slice(&heads).unwrap();
+    let output = device.create_buffer::<u32>(n).unwrap();
+
+    let sum = algo::Reduce::new(&device, 0u32, |a, b| a + b).unwrap();
+    let max = algo::Reduce::new(&device, 0u32, |a, b| a.max(b)).unwrap();
+    assert_eq!(
+        sum.reduce(&input.view(..)).unwrap(),
+        values.iter().sum::<u32>()
+    );
+    assert_eq!(max.reduce(&input.view(..)).unwrap(), 15);
+    assert_eq!(sum.reduce(&input.view(0..0)).unwrap(), 0);
+
+    let scan = algo::Scan::new(&device, 0u32, |a, b| a + b).unwrap();
+    scan.inclusive_scan(&input.view(..), &output.view(..))
+        .unwrap();
+    let mut acc = 0;
+    let expected: Vec<u32> = values
+        .iter()
+        .map(|v| {
+            acc += v;
+            acc
+        })
+        .collect();
+    assert_eq!(output.view(..).copy_to_vec(), expected);
+    scan.exclusive_scan(&input.view(..), &output.view(..))
+        .unwrap();
+    let mut acc = 0;
+    let expected: Vec<u32> = values
+        .iter()
+        .map(|v| {
+            let prev = acc;
+            acc += v;
+            prev
+        })
+        .collect();
+    assert_eq!(output.view(..).copy_to_vec(), expected);
+
+    let segmented = algo::SegmentedScan::new(&device, 0u32, |a, b| a + b).unwrap();
+    segmented
+        .inclusive_scan(&input.view(..), &head_flags.view(..), &output.view(..))
+        .unwrap();
+    let mut acc = 0;
+    let expected: Vec<u32> = values
+        .iter()
+        .zip(&heads)
+        .map(|(v, h)| {
+            if *h {
+                acc = 0;
+            }
+            acc += v;
+            acc
+        })
+        .collect();
+    assert_eq!(output.view(..).copy_to_vec(), expected);
+    segmented
+        .exclusive_scan(&input.view(..), &head_flags.view(..), &output.view(..))
+        .unwrap();
+    let mut acc = 0;
+    let expected: Vec<u32> = values
+        .iter()
+        .zip(&heads)
+        .map(|(v, h)| {
+            if *h {
+                acc = 0;
+            }
+            let prev = acc;
+            acc += v;
+            prev
+        })
+        .collect();
+    assert_eq!(output.view(..).copy_to_vec(), expected);
+
+    let values: Vec<f32> = (0..n).map(|_| rng.gen_range(-1.0..1.0)).collect();
+    let input = device.create_buffer_from_slice(&values).unwrap();
+    let min = algo::Reduce::new(&device, f32::INFINITY, |a, b| a.min(b)).unwrap();
+    assert_eq!(
+        min.reduce(&input.view(..)).unwrap(),
+        values.iter().cloned().fold(f32::INFINITY, f32::min)
+    );
+}
+#[test]
+fn algo_radix_sort() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    let n = 50_001;
+    let keys: Vec<u32> = (0..n).map(|_| rng.gen()).collect();
+    let values: Vec<u32> = (0..n as u32).collect();
+
+    let sort = algo::RadixSort::<u32>::new(&device).unwrap();
+    let key_buf = device.create_buffer_from_slice(&keys).unwrap();
+    sort.sort_all(&key_buf.view(..)).unwrap();
+    let mut expected = keys.clone();
+    expected.sort();
+    assert_eq!(key_buf.view(..).copy_to_vec(), expected);
+
+    // only bits 3..13 take part, ties must keep their input order
+    let sort_by_key = algo::RadixSortByKey::<u32, u32>::new(&device).unwrap();
+    let key_buf = device.create_buffer_from_slice(&keys).unwrap();
+    let value_buf = device.create_buffer_from_slice(&values).unwrap();
+    sort_by_key
+        .sort(&key_buf.view(..), &value_buf.view(..), 3..13)
+        .unwrap();
+    let mut expected: Vec<(u32, u32)> = keys.iter().cloned().zip(values.iter().cloned()).collect();
+    expected.sort_by_key(|(k, _)| (k >> 3) & ((1 << 10) - 1));
+    assert_eq!(
+        key_buf.view(..).copy_to_vec(),
+        expected.iter().map(|(k, _)| *k).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        value_buf.view(..).copy_to_vec(),
+        expected.iter().map(|(_, v)| *v).collect::<Vec<_>>()
+    );
+
+    let keys: Vec<u64> = (0..n).map(|_| rng.gen_range(0..1u64 << 40)).collect();
+    let sort = algo::RadixSortByKey::<u64, u32>::new(&device).unwrap();
+    let key_buf = device.create_buffer_from_slice(&keys).unwrap();
+    let value_buf = device.create_buffer_from_slice(&values).unwrap();
+    sort.sort(&key_buf.view(..), &value_buf.view(..), 0..40)
+        .unwrap();
+    let mut expected: Vec<(u64, u32)> = keys.iter().cloned().zip(values.iter().cloned()).collect();
+    expected.sort_by_key(|(k, _)| *k);
+    assert_eq!(
+        key_buf.view(..).copy_to_vec(),
+        expected.iter().map(|(k, _)| *k).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        value_buf.view(..).copy_to_vec(),
+        expected.iter().map(|(_, v)| *v).collect::<Vec<_>>()
+    );
+}
+#[test]
+fn algo_compact_partition_histogram() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    let n = 30_000;
+    let values: Vec<u32> = (0..n).map(|_| rng.gen_range(0..64)).collect();
+    let input = device.create_buffer_from_slice(&values).unwrap();
+    let output = device.create_buffer::<u32>(n).unwrap();
+    let count = device.create_buffer::<u32>(1).unwrap();
+
+    let even = algo::Compact::new(&device, |v: Expr<u32>| (v % 2).cmpeq(0)).unwrap();
+    let selected: Vec<u32> = values.iter().cloned().filter(|v| v % 2 == 0).collect();
+    let rejected: Vec<u32> = values.iter().cloned().filter(|v| v % 2 != 0).collect();
+    let c = even
+        .compact(&input.view(..), &output.view(..), &count.view(..))
+        .unwrap();
+    assert_eq!(c as usize, selected.len());
+    assert_eq!(count.view(..).copy_to_vec()[0], c);
+    assert_eq!(&output.view(..).copy_to_vec()[..selected.len()], &selected[..]);
+    let c = even
+        .partition(&input.view(..), &output.view(..), &count.view(..))
+        .unwrap();
+    assert_eq!(c as usize, selected.len());
+    assert_eq!(output.view(..).copy_to_vec(), [selected, rejected].concat());
+
+    let runs: Vec<u32> = values.iter().map(|v| v / 8).collect();
+    let input = device.create_buffer_from_slice(&runs).unwrap();
+    let unique = algo::Unique::new(&device, |a: Expr<u32>, b| a.cmpeq(b)).unwrap();
+    let c = unique
+        .unique(&input.view(..), &output.view(..), &count.view(..))
+        .unwrap();
+    let mut expected = runs.clone();
+    expected.dedup();
+    assert_eq!(c as usize, expected.len());
+    assert_eq!(
+        &output.view(..).copy_to_vec()[..expected.len()],
+        &expected[..]
+    );
+
+    // values 48..64 fall outside the bins and are ignored
+    let input = device.create_buffer_from_slice(&values).unwrap();
+    let histogram = algo::Histogram::new(&device, |v: Expr<u32>| v).unwrap();
+    let mut expected = vec![0u32; 48];
+    for v in &values {
+        if *v < 48 {
+            expected[*v as usize] += 1;
+        }
+    }
+    assert_eq!(histogram.histogram(&input.view(..), 48).unwrap(), expected);
+}
+#[test]
+fn algo_offset_views() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    let (k, n) = (37, 20_000);
+    // the elements before `k` must be neither read nor written
+    let values: Vec<u32> = (0..k + n).map(|_| rng.gen_range(0..1000)).collect();
+    let input = device.create_buffer_from_slice(&values).unwrap();
+    let output = device.create_buffer_from_slice(&vec![u32::MAX; k + n]).unwrap();
+
+    let sum = algo::Reduce::new(&device, 0u32, |a, b| a + b).unwrap();
+    assert_eq!(
+        sum.reduce(&input.view(k as u64..)).unwrap(),
+        values[k..].iter().sum::<u32>()
+    );
+
+    let scan = algo::Scan::new(&device, 0u32, |a, b| a + b).unwrap();
+    scan.inclusive_scan(&input.view(k as u64..), &output.view(k as u64..))
+        .unwrap();
+    let mut acc = 0;
+    let expected: Vec<u32> = values[k..]
+        .iter()
+        .map(|v| {
+            acc += v;
+            acc
+        })
+        .collect();
+    let got = output.view(..).copy_to_vec();
+    assert!(got[..k].iter().all(|v| *v == u32::MAX));
+    assert_eq!(&got[k..], &expected[..]);
+
+    let sort = algo::RadixSort::<u32>::new(&device).unwrap();
+    let keys = device.create_buffer_from_slice(&values).unwrap();
+    sort.sort_all(&keys.view(k as u64..)).unwrap();
+    let mut expected = values.clone();
+    expected[k..].sort();
+    assert_eq!(keys.view(..).copy_to_vec(), expected);
+
+    let even = algo::Compact::new(&device, |v: Expr<u32>| (v % 2).cmpeq(0)).unwrap();
+    let count = device.create_buffer::<u32>(2).unwrap();
+    let c = even
+        .compact(&input.view(k as u64..), &output.view(k as u64..), &count.view(1..))
+        .unwrap();
+    let selected: Vec<u32> = values[k..].iter().cloned().filter(|v| v % 2 == 0).collect();
+    assert_eq!(c as usize, selected.len());
+    assert_eq!(count.view(1..).copy_to_vec()[0], c);
+    let got = output.view(..).copy_to_vec();
+    assert!(got[..k].iter().all(|v| *v == u32::MAX));
+    assert_eq!(&got[k..k + selected.len()], &selected[..]);
+}
+#[test]
+fn append_buffer() {
+    init();
+    let device = get_device();
+    let odd = device.create_append_buffer::<u32>(1024).unwrap();
+    let slots = device.create_buffer::<u32>(1024).unwrap();
+    let kernel = device
+        .create_kernel::<(AppendBuffer<u32>,)>(&|odd| {
+            let tid = dispatch_id().x();
+            if_!((tid % 2).cmpeq(1), {
+                slots.var().write(tid, odd.push(tid));
+            });
+        })
+        .unwrap();
+    kernel.dispatch([1024, 1, 1], &odd).unwrap();
+    assert_eq!(odd.len(), 512);
+    assert!(!odd.overflowed());
+    let mut values = odd.copy_to_vec();
+    let slots = slots.view(..).copy_to_vec();
+    for (slot, v) in values.iter().enumerate() {
+        assert_eq!(slots[*v as usize] as usize, slot);
+    }
+    values.sort();
+    assert_eq!(values, (0..512).map(|i| 2 * i + 1).collect::<Vec<_>>());
+
+    kernel.dispatch([1024, 1, 1], &odd).unwrap();
+    kernel.dispatch([1024, 1, 1], &odd).unwrap();
+    kernel.dispatch([1024, 1, 1], &odd).unwrap();
+    assert_eq!(odd.count(), 2048);
+    assert_eq!(odd.len(), 1024);
+    assert!(odd.overflowed());
+
+    odd.reset();
+    assert!(odd.is_empty());
+    kernel.dispatch([16, 1, 1], &odd).unwrap();
+    assert_eq!(odd.len(), 8);
+}
+#[test]
+fn device_hash_map() {
+    init();
+    let device = get_device();
+    let counts = hashmap::DeviceHashMap::<u32, u32>::new(&device, 256).unwrap();
+    let kernel = device
+        .create_kernel::<(hashmap::DeviceHashMap<u32, u32>,)>(&|counts| {
+            let tid = dispatch_id().x();
+            counts.insert_or_add(tid % 100 * 7919, 1u32);
+        })
+        .unwrap();
+    kernel.dispatch([1000, 1, 1], &counts).unwrap();
+    let expected: std::collections::HashMap<u32, u32> = (0..100).map(|k| (k * 7919, 10)).collect();
+    assert_eq!(counts.len(), 100);
+    assert_eq!(counts.to_hash_map(), expected);
+    // u32::MAX marks free slots, so inserting it fails
+    let rejected = device.create_buffer::<u32>(1).unwrap();
+    let kernel = device
+        .create_kernel::<(hashmap::DeviceHashMap<u32, u32>,)>(&|counts| {
+            let inserted = counts.insert_or_add(u32::MAX, 1u32);
+            rejected
+                .var()
+                .write(0u32, select(inserted, const_(0u32), const_(1u32)));
+        })
+        .unwrap();
+    kernel.dispatch([1, 1, 1], &counts).unwrap();
+    assert_eq!(rejected.view(..).copy_to_vec(), vec![1]);
+    assert_eq!(counts.len(), 100);
+
+    let squares: std::collections::HashMap<u64, f32> =
+        (0..500u64).map(|k| (k << 33, (k * k) as f32)).collect();
+    let map = hashmap::DeviceHashMap::from_hash_map(&device, &squares, 1024).unwrap();
+    let found = device.create_buffer::<f32>(1000).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let map = map.var();
+            let tid = dispatch_id().x();
+            let (hit, v) = map.get(tid.ulong() << 33u64);
+            found.var().write(tid, select(hit, v, const_(-1.0f32)));
+            if_!(tid.cmpge(500), {
+                map.insert(tid.ulong() << 33u64, tid.float());
+            });
+        })
+        .unwrap();
+    kernel.dispatch([1000, 1, 1]).unwrap();
+    let found = found.view(..).copy_to_vec();
+    for i in 0..1000 {
+        if i < 500 {
+            assert_eq!(found[i], (i * i) as f32);
+        } else {
+            assert_eq!(found[i], -1.0);
+        }
+    }
+    let entries = map.to_hash_map();
+    assert_eq!(entries.len(), 1000);
+    for i in 500..1000u64 {
+        assert_eq!(entries[&(i << 33)], i as f32);
+    }
+
+    map.clear();
+    assert!(map.is_empty());
+}
+#[test]
+fn lbvh_queries() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    let n = 4096;
+    let points: Vec<Float3> = (0..n)
+        .map(|_| Float3::new(rng.gen(), rng.gen(), rng.gen()))
+        .collect();
+    let queries: Vec<Float3> = (0..256)
+        .map(|_| Float3::new(rng.gen(), rng.gen(), rng.gen()))
+        .collect();
+    let point_buf = device.create_buffer_from_slice(&points).unwrap();
+    let query_buf = device.create_buffer_from_slice(&queries).unwrap();
+    let builder = lbvh::LbvhBuilder::new(&device).unwrap();
+    let bvh = builder.build_points(&point_buf.view(..)).unwrap();
+    assert_eq!(bvh.len(), n);
+
+    let radius = 0.05f32;
+    let in_radius = device.create_buffer::<u32>(queries.len()).unwrap();
+    let knn_count = device.create_buffer::<u32>(queries.len()).unwrap();
+    let knn_indices = device.create_buffer::<[u32; 8]>(queries.len()).unwrap();
+    let knn_dist2 = device.create_buffer::<[f32; 8]>(queries.len()).unwrap();
+    let kernel = device
+        .create_kernel::<(lbvh::Lbvh,)>(&|bvh| {
+            let tid = dispatch_id().x();
+            let p = query_buf.var().read(tid);
+            let count = var!(u32, 0u32);
+            bvh.for_each_in_radius(p, radius, |_| {
+                count.store(count.load() + 1u32);
+            });
+            in_radius.var().write(tid, count.load());
+            let (c, indices, dist2) = bvh.knn::<8>(p, 0.2f32);
+            knn_count.var().write(tid, c);
+            knn_indices.var().write(tid, indices);
+            knn_dist2.var().write(tid, dist2);
+        })
+        .unwrap();
+    kernel.dispatch([queries.len() as u32, 1, 1], &bvh).unwrap();
+    let in_radius = in_radius.view(..).copy_to_vec();
+    let knn_count = knn_count.view(..).copy_to_vec();
+    let knn_indices = knn_indices.view(..).copy_to_vec();
+    let knn_dist2 = knn_dist2.view(..).copy_to_vec();
+    for (i, q) in queries.iter().enumerate() {
+        let expected = lbvh::host::points_in_radius(&points, *q, radius);
+        assert_eq!(in_radius[i] as usize, expected.len());
+        let expected = lbvh::host::knn(&points, *q, 8, 0.2);
+        assert_eq!(knn_count[i] as usize, expected.len());
+        for (j, (_, d2)) in expected.iter().enumerate() {
+            assert!((knn_dist2[i][j] - d2).abs() < 1e-5);
+            let p = points[knn_indices[i][j] as usize];
+            let (x, y, z) = (p.x - q.x, p.y - q.y, p.z - q.z);
+            assert!((x * x + y * y + z * z - d2).abs() < 1e-5);
+        }
+    }
+
+    let single = builder.build_points(&point_buf.view(0..1)).unwrap();
+    assert_eq!(single.len(), 1);
+    let empty = builder.build_points(&point_buf.view(0..0)).unwrap();
+    assert!(empty.is_empty());
+}
+#[test]
+fn lbvh_identical_points() {
+    init();
+    let device = get_device();
+    // every morton code is equal, so the tree is split by index alone
+    let n = 10000;
+    let p = Float3::new(0.25, 0.5, 0.75);
+    let point_buf = device.create_buffer_from_fn(n, |_| p).unwrap();
+    let builder = lbvh::LbvhBuilder::new(&device).unwrap();
+    let bvh = builder.build_points(&point_buf.view(..)).unwrap();
+    assert_eq!(bvh.len(), n);
+    let counts = device.create_buffer::<u32>(2).unwrap();
+    let kernel = device
+        .create_kernel::<(lbvh::Lbvh,)>(&|bvh| {
+            let tid = dispatch_id().x();
+            let q = select(
+                tid.cmpeq(0u32),
+                make_float3(0.25f32, 0.5f32, 0.75f32),
+                make_float3(0.9f32, 0.9f32, 0.9f32),
+            );
+            let count = var!(u32, 0u32);
+            bvh.for_each_in_radius(q, 0.01f32, |_| {
+                count.store(count.load() + 1u32);
+            });
+            counts.var().write(tid, count.load());
+        })
+        .unwrap();
+    kernel.dispatch([2, 1, 1], &bvh).unwrap();
+    assert_eq!(counts.view(..).copy_to_vec(), vec![n as u32, 0]);
+}
+#[test]
+fn tensor_views() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    let data: Vec<f32> = (0..24).map(|_| rng.gen()).collect();
+    let a = tensor::Tensor::<f32, 3>::from_slice(&device, [2, 3, 4], &data).unwrap();
+    // a[1, 1..3, :] transposed to shape [4, 2]
+    let view = a.view().slice(0, 1..2).slice(1, 1..3).transpose(1, 2);
+    assert_eq!(view.shape(), [1, 4, 2]);
+    assert!(!view.is_contiguous());
+    let expected: Vec<f32> = (0..4)
+        .flat_map(|k| (1..3).map(move |j| (1, j, k)))
+        .map(|(i, j, k)| data[i * 12 + j * 4 + k])
+        .collect();
+    assert_eq!(view.copy_to_vec(), expected);
+
+    let out = tensor::Tensor::<f32, 3>::new(&device, [1, 4, 2]).unwrap();
+    let kernel = device
+        .create_kernel::<(tensor::Tensor<f32, 3>, tensor::Tensor<f32, 3>)>(&|src, dst| {
+            let p = dispatch_id();
+            let index = [p.z(), p.y(), p.x()];
+            dst.write(index, src.read(index) * 2.0f32);
+        })
+        .unwrap();
+    view.prepare().unwrap();
+    kernel.dispatch([2, 4, 1], &view, &out).unwrap();
+    let doubled: Vec<f32> = expected.iter().map(|x| x * 2.0).collect();
+    assert_eq!(out.copy_to_vec(), doubled);
+
+    let row = tensor::Tensor::<f32, 3>::from_slice(&device, [1, 1, 2], &[10.0, 20.0]).unwrap();
+    let broadcast = row.view().broadcast_to([1, 4, 2]);
+    assert_eq!(broadcast.strides(), [2, 0, 1]);
+    broadcast.prepare().unwrap();
+    kernel.dispatch([2, 4, 1], &broadcast, &out).unwrap();
+    assert_eq!(
+        out.copy_to_vec(),
+        vec![20.0, 40.0, 20.0, 40.0, 20.0, 40.0, 20.0, 40.0]
+    );
+    assert_eq!(broadcast.copy_to_vec(), [10.0, 20.0].repeat(4));
+}
+#[test]
+fn blas_gemm_gemv() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    let (m, n, k) = (37usize, 29usize, 45usize);
+    let blas = blas::Blas::<f32>::new(&device).unwrap();
+    let a: Vec<f32> = (0..m * k).map(|_| rng.gen_range(-1.0..1.0)).collect();
+    let b: Vec<f32> = (0..k * n).map(|_| rng.gen_range(-1.0..1.0)).collect();
+    let c0: Vec<f32> = (0..m * n).map(|_| rng.gen_range(-1.0..1.0)).collect();
+    let a_buf = device.create_buffer_from_slice(&a).unwrap();
+    let b_buf = device.create_buffer_from_slice(&b).unwrap();
+    let c_buf = device.create_buffer::<f32>(m * n).unwrap();
+    let c_mat = blas::Matrix::new(c_buf.view(..), m as u32, n as u32);
+    for (trans_a, trans_b) in [(false, false), (true, false), (false, true), (true, true)] {
+        // the same data is read as an m x k op(a) and a k x n op(b) in every case
+        let a_mat = if trans_a {
+            blas::Matrix::new(a_buf.view(..), k as u32, m as u32).t()
+        } else {
+            blas::Matrix::new(a_buf.view(..), m as u32, k as u32)
+        };
+        let b_mat = if trans_b {
+            blas::Matrix::new(b_buf.view(..), n as u32, k as u32).t()
+        } else {
+            blas::Matrix::new(b_buf.view(..), k as u32, n as u32)
+        };
+        let a_at = |i: usize, p: usize| if trans_a { a[p * m + i] } else { a[i * k + p] };
+        let b_at = |p: usize, j: usize| if trans_b { b[j * k + p] } else { b[p * n + j] };
+        c_buf.view(..).copy_from(&c0);
+        blas.gemm(0.5, &a_mat, &b_mat, 2.0, &c_mat).unwrap();
+        let c = c_buf.view(..).copy_to_vec();
+        for i in 0..m {
+            for j in 0..n {
+                let dot: f32 = (0..k).map(|p| a_at(i, p) * b_at(p, j)).sum();
+                let expected = 0.5 * dot + 2.0 * c0[i * n + j];
+                assert!((c[i * n + j] - expected).abs() < 1e-4);
+            }
+        }
+
+        let x = device.create_buffer_from_slice(&b[..k]).unwrap();
+        let y = device.create_buffer_from_fn(m, |_| f32::NAN).unwrap();
+        blas.gemv(1.5, &a_mat, &x.view(..), 0.0, &y.view(..))
+            .unwrap();
+        let y = y.view(..).copy_to_vec();
+        for (i, y) in y.iter().enumerate() {
+            let dot: f32 = (0..k).map(|p| a_at(i, p) * b[p]).sum();
+            assert!((y - 1.5 * dot).abs() < 1e-4);
+        }
+    }
+
+    // beta = 0 ignores whatever c held before
+    c_buf.view(..).fill(f32::NAN);
+    let a_mat = blas::Matrix::new(a_buf.view(..), m as u32, k as u32);
+    let b_mat = blas::Matrix::new(b_buf.view(..), k as u32, n as u32);
+    blas.gemm(1.0, &a_mat, &b_mat, 0.0, &c_mat).unwrap();
+    assert!(c_buf.view(..).copy_to_vec().iter().all(|c| c.is_finite()));
+
+    // f64 with a padded leading dimension
+    let blas = blas::Blas::<f64>::new(&device).unwrap();
+    let (dim, ld) = (10usize, 16usize);
+    let a: Vec<f64> = (0..dim * ld).map(|_| rng.gen_range(-1.0..1.0)).collect();
+    let a_buf = device.create_buffer_from_slice(&a).unwrap();
+    let c_buf = device.create_buffer::<f64>(dim * dim).unwrap();
+    let a_mat = blas::Matrix::with_ld(a_buf.view(..), dim as u32, dim as u32, ld as u32);
+    let c_mat = blas::Matrix::new(c_buf.view(..), dim as u32, dim as u32);
+    blas.gemm(1.0, &a_mat, &a_mat.t(), 0.0, &c_mat).unwrap();
+    let c = c_buf.view(..).copy_to_vec();
+    for i in 0..dim {
+        for j in 0..dim {
+            let dot: f64 = (0..dim).map(|p| a[i * ld + p] * a[j * ld + p]).sum();
+            assert!((c[i * dim + j] - dot).abs() < 1e-12);
+        }
+    }
+}
+#[test]
+fn blas_small_solves() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    let count = 256;
+    // rows of random matrices whose anti-diagonal dominates, so that LU has to pivot,
+    // and of symmetric positive definite m * m^T + I
+    let mut random = |n: usize| -> (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+        let general: Vec<Vec<f32>> = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| rng.gen_range(-1.0..1.0) + if i + j == n - 1 { 4.0 } else { 0.0 })
+                    .collect()
+            })
+            .collect();
+        let m: Vec<Vec<f32>> = (0..n)
+            .map(|_| (0..n).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect();
+        let spd = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| {
+                        let dot: f32 = (0..n).map(|p| m[i][p] * m[j][p]).sum();
+                        dot + if i == j { 1.0 } else { 0.0 }
+                    })
+                    .collect()
+            })
+            .collect();
+        (general, spd)
+    };
+    let residual = |a: &[Vec<f32>], x: &[f32], b: &[f32]| -> f32 {
+        (0..b.len())
+            .map(|i| ((0..x.len()).map(|j| a[i][j] * x[j]).sum::<f32>() - b[i]).abs())
+            .fold(0.0, f32::max)
+    };
+
+    let mut general3 = vec![];
+    let mut spd3 = vec![];
+    let mut general4 = vec![];
+    let mut spd4 = vec![];
+    for _ in 0..count {
+        let (g, s) = random(3);
+        general3.push(g);
+        spd3.push(s);
+        let (g, s) = random(4);
+        general4.push(g);
+        spd4.push(s);
+    }
+    let rhs3: Vec<Float3> = (0..count)
+        .map(|_| Float3::new(rng.gen(), rng.gen(), rng.gen()))
+        .collect();
+    let rhs4: Vec<Float4> = (0..count)
+        .map(|_| Float4::new(rng.gen(), rng.gen(), rng.gen(), rng.gen()))
+        .collect();
+    let to_mat3 = |a: &Vec<Vec<f32>>| {
+        let col = |j: usize| Float3::new(a[0][j], a[1][j], a[2][j]);
+        Mat3::from_cols(col(0), col(1), col(2))
+    };
+    let to_mat4 = |a: &Vec<Vec<f32>>| {
+        let col = |j: usize| Float4::new(a[0][j], a[1][j], a[2][j], a[3][j]);
+        Mat4::from_cols(col(0), col(1), col(2), col(3))
+    };
+    let general3_buf = device
+        .create_buffer_from_slice(&general3.iter().map(to_mat3).collect::<Vec<_>>())
+        .unwrap();
+    let spd3_buf = device
+        .create_buffer_from_slice(&spd3.iter().map(to_mat3).collect::<Vec<_>>())
+        .unwrap();
+    let general4_buf = device
+        .create_buffer_from_slice(&general4.iter().map(to_mat4).collect::<Vec<_>>())
+        .unwrap();
+    let spd4_buf = device
+        .create_buffer_from_slice(&spd4.iter().map(to_mat4).collect::<Vec<_>>())
+        .unwrap();
+    let rhs3_buf = device.create_buffer_from_slice(&rhs3).unwrap();
+    let rhs4_buf = device.create_buffer_from_slice(&rhs4).unwrap();
+    let lu3 = device.create_buffer::<Float3>(count).unwrap();
+    let chol3 = device.create_buffer::<Float3>(count).unwrap();
+    let factor3 = device.create_buffer::<Mat3>(count).unwrap();
+    let lu4 = device.create_buffer::<Float4>(count).unwrap();
+    let chol4 = device.create_buffer::<Float4>(count).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tid = dispatch_id().x();
+            let b3 = rhs3_buf.var().read(tid);
+            let b4 = rhs4_buf.var().read(tid);
+            let spd3 = spd3_buf.var().read(tid);
+            lu3.var()
+                .write(tid, blas::lu_solve3(general3_buf.var().read(tid), b3));
+            chol3.var().write(tid, blas::cholesky_solve3(spd3, b3));
+            factor3.var().write(tid, blas::cholesky3(spd3));
+            lu4.var()
+                .write(tid, blas::lu_solve4(general4_buf.var().read(tid), b4));
+            chol4
+                .var()
+                .write(tid, blas::cholesky_solve4(spd4_buf.var().read(tid), b4));
+        })
+        .unwrap();
+    kernel.dispatch([count as u32, 1, 1]).unwrap();
+    let lu3 = lu3.view(..).copy_to_vec();
+    let chol3 = chol3.view(..).copy_to_vec();
+    let factor3 = factor3.view(..).copy_to_vec();
+    let lu4 = lu4.view(..).copy_to_vec();
+    let chol4 = chol4.view(..).copy_to_vec();
+    for i in 0..count {
+        let b3 = [rhs3[i].x, rhs3[i].y, rhs3[i].z];
+        let b4 = [rhs4[i].x, rhs4[i].y, rhs4[i].z, rhs4[i].w];
+        let x = [lu3[i].x, lu3[i].y, lu3[i].z];
+        assert!(residual(&general3[i], &x, &b3) < 1e-4);
+        let x = [chol3[i].x, chol3[i].y, chol3[i].z];
+        assert!(residual(&spd3[i], &x, &b3) < 1e-3);
+        let x = [lu4[i].x, lu4[i].y, lu4[i].z, lu4[i].w];
+        assert!(residual(&general4[i], &x, &b4) < 1e-4);
+        let x = [chol4[i].x, chol4[i].y, chol4[i].z, chol4[i].w];
+        assert!(residual(&spd4[i], &x, &b4) < 1e-3);
+
+        let cols = factor3[i].cols;
+        let l = |r: usize, c: usize| [cols[c].x, cols[c].y, cols[c].z][r];
+        for r in 0..3 {
+            for c in 0..3 {
+                if c > r {
+                    assert_eq!(l(r, c), 0.0);
+                }
+                let llt: f32 = (0..3).map(|p| l(r, p) * l(c, p)).sum();
+                assert!((llt - spd3[i][r][c]).abs() < 1e-4);
+            }
+        }
+    }
+}
+#[test]
+fn sparse_spmv_cg() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    // 2D Poisson matrix assembled edge by edge, so the diagonal has duplicate entries
+    let dim = 16u32;
+    let n = dim * dim;
+    let mut triplets: Vec<(u32, u32, f32)> = vec![];
+    for y in 0..dim {
+        for x in 0..dim {
+            let i = y * dim + x;
+            triplets.push((i, i, 0.1));
+            let mut edge = |j: u32| {
+                triplets.push((i, i, 1.0));
+                triplets.push((j, j, 1.0));
+                triplets.push((i, j, -1.0));
+                triplets.push((j, i, -1.0));
+            };
+            if x + 1 < dim {
+                edge(i + 1);
+            }
+            if y + 1 < dim {
+                edge(i + dim);
+            }
+        }
+    }
+    triplets.shuffle(&mut rng);
+    let mut dense = vec![vec![0.0f32; n as usize]; n as usize];
+    for (r, c, v) in &triplets {
+        dense[*r as usize][*c as usize] += v;
+    }
+    let coo = sparse::CooMatrix::from_triplets(&device, n, n, &triplets).unwrap();
+    let csr = sparse::CsrMatrix::from_coo(&device, &coo).unwrap();
+    let nnz = dense.iter().flatten().filter(|v| **v != 0.0).count();
+    assert_eq!(coo.nnz(), nnz);
+    assert_eq!(csr.nnz(), nnz);
+    let entries = coo.to_triplets();
+    assert!(entries
+        .windows(2)
+        .all(|e| (e[0].0, e[0].1) < (e[1].0, e[1].1)));
+
+    let x: Vec<f32> = (0..n).map(|_| rng.gen_range(-1.0..1.0)).collect();
+    let x_buf = device.create_buffer_from_slice(&x).unwrap();
+    let y_csr = device.create_buffer::<f32>(n as usize).unwrap();
+    let y_coo = device.create_buffer::<f32>(n as usize).unwrap();
+    let spmv = sparse::Spmv::<f32>::new(&device).unwrap();
+    spmv.csr(&csr, &x_buf.view(..), &y_csr.view(..)).unwrap();
+    spmv.coo(&coo, &x_buf.view(..), &y_coo.view(..)).unwrap();
+    let y_csr = y_csr.view(..).copy_to_vec();
+    let y_coo = y_coo.view(..).copy_to_vec();
+    for i in 0..n as usize {
+        let expected: f32 = (0..n as usize).map(|j| dense[i][j] * x[j]).sum();
+        assert!((y_csr[i] - expected).abs() < 1e-4);
+        assert!((y_coo[i] - expected).abs() < 1e-4);
+    }
+
+    let b: Vec<f32> = (0..n).map(|_| rng.gen_range(-1.0..1.0)).collect();
+    let b_buf = device.create_buffer_from_slice(&b).unwrap();
+    let solution = device
+        .create_buffer_from_fn(n as usize, |_| 0.0f32)
+        .unwrap();
+    let cg = sparse::ConjugateGradient::<f32>::new(&device).unwrap();
+    let status = cg
+        .solve(&csr, &b_buf.view(..), &solution.view(..), 1e-5, 1000)
+        .unwrap();
+    assert!(status.converged);
+    assert!(status.iterations > 0 && status.iterations < 1000);
+    let solution = solution.view(..).copy_to_vec();
+    for i in 0..n as usize {
+        let ax: f32 = (0..n as usize).map(|j| dense[i][j] * solution[j]).sum();
+        assert!((ax - b[i]).abs() < 1e-3);
+    }
+
+    // a converged solution needs no further iterations
+    let warm = device.create_buffer_from_slice(&solution).unwrap();
+    let status = cg
+        .solve(&csr, &b_buf.view(..), &warm.view(..), 1e-3, 1000)
+        .unwrap();
+    assert_eq!(status.iterations, 0);
+
+    // views that start inside larger buffers, the padding must stay untouched
+    let (k, len) = (5usize, n as usize);
+    let pad = |data: &[f32]| [vec![7.0f32; k], data.to_vec(), vec![7.0f32; k]].concat();
+    let x_pad = device.create_buffer_from_slice(&pad(&x)).unwrap();
+    let zeros = pad(&vec![0.0; len]);
+    let y_pad = device.create_buffer_from_slice(&zeros).unwrap();
+    let range = k as u64..(k + len) as u64;
+    spmv.coo(&coo, &x_pad.view(range.clone()), &y_pad.view(range.clone()))
+        .unwrap();
+    let y = y_pad.view(..).copy_to_vec();
+    assert_eq!(&y[..k], &[7.0; 5]);
+    assert_eq!(&y[k + len..], &[7.0; 5]);
+    for (got, expected) in y[k..k + len].iter().zip(&y_coo) {
+        assert!((got - expected).abs() < 1e-5);
+    }
+    let b_pad = device.create_buffer_from_slice(&pad(&b)).unwrap();
+    let solution_pad = device.create_buffer_from_slice(&zeros).unwrap();
+    let (b_view, solution_view) = (b_pad.view(range.clone()), solution_pad.view(range));
+    let status = cg.solve(&csr, &b_view, &solution_view, 1e-5, 1000).unwrap();
+    assert!(status.converged);
+    let solution = solution_pad.view(..).copy_to_vec();
+    assert_eq!(&solution[..k], &[7.0; 5]);
+    assert_eq!(&solution[k + len..], &[7.0; 5]);
+    let solution = &solution[k..k + len];
+    for i in 0..len {
+        let ax: f32 = (0..len).map(|j| dense[i][j] * solution[j]).sum();
+        assert!((ax - b[i]).abs() < 1e-3);
+    }
+}
+#[test]
+fn fft_transforms() {
+    init();
+    let device = get_device();
+    let mut rng = StdRng::seed_from_u64(0);
+    let fft = fft::Fft::new(&device).unwrap();
+    let max_error = |a: &[Float2], b: &[Float2]| {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| (a.x - b.x).abs().max((a.y - b.y).abs()))
+            .fold(0.0f32, f32::max)
+    };
+    // power of two, mixed radix and prime sizes, including a generic radix pass
+    let shapes: [&[u32]; 7] = [&[64], &[60], &[77], &[13], &[16, 8], &[12, 7], &[4, 6, 5]];
+    for shape in shapes {
+        let len = shape.iter().product::<u32>() as usize;
+        let data: Vec<Float2> = (0..len)
+            .map(|_| Float2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)))
+            .collect();
+        let buffer = device.create_buffer_from_slice(&data).unwrap();
+        fft.forward(&buffer.view(..), shape).unwrap();
+        let spectrum = buffer.view(..).copy_to_vec();
+        let expected = fft::host::fft(&data, shape, false);
+        assert!(max_error(&spectrum, &expected) < 1e-3, "{:?}", shape);
+        fft.inverse(&buffer.view(..), shape).unwrap();
+        let restored = buffer.view(..).copy_to_vec();
+        assert!(max_error(&restored, &data) < 1e-4, "{:?}", shape);
+        assert!(max_error(&fft::host::fft(&expected, shape, true), &data) < 1e-5);
+    }
+
+    for shape in [&[30u32][..], &[16, 10], &[9, 4, 3]] {
+        let len = shape.iter().product::<u32>() as usize;
+        let data: Vec<f32> = (0..len).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        let half_len = fft::real_spectrum_shape(shape).iter().product::<u32>() as usize;
+        let input = device.create_buffer_from_slice(&data).unwrap();
+        let spectrum = device.create_buffer::<Float2>(half_len).unwrap();
+        fft.forward_real(&input.view(..), &spectrum.view(..), shape)
+            .unwrap();
+        let expected = fft::host::rfft(&data, shape);
+        assert!(max_error(&spectrum.view(..).copy_to_vec(), &expected) < 1e-3);
+        let output = device.create_buffer::<f32>(len).unwrap();
+        fft.inverse_real(&spectrum.view(..), &output.view(..), shape)
+            .unwrap();
+        let output = output.view(..).copy_to_vec();
+        for (a, b) in output.iter().zip(&data) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    let (w, h) = (16u32, 12u32);
+    let tex: Tex2d<f32> = device.create_tex2d(PixelStorage::Float1, w, h, 1).unwrap();
+    let data: Vec<f32> = (0..w * h).map(|_| rng.gen_range(-1.0..1.0)).collect();
+    tex.view(0).copy_from(&data);
+    let spectrum = device.create_buffer::<Float2>((w * h) as usize).unwrap();
+    fft.forward_tex2d(&tex.view(0), &spectrum.view(..)).unwrap();
+    let complex: Vec<Float2> = data.iter().map(|v| Float2::new(*v, 0.0)).collect();
+    let expected = fft::host::fft(&complex, &[w, h], false);
+    assert!(max_error(&spectrum.view(..).copy_to_vec(), &expected) < 1e-3);
+    tex.view(0).copy_from(&vec![0.0f32; (w * h) as usize]);
+    fft.inverse_tex2d(&spectrum.view(..), &tex.view(0)).unwrap();
+    let mut texels = vec![0.0f32; (w * h) as usize];
+    tex.view(0).copy_to(&mut texels);
+    for (a, b) in texels.iter().zip(&data) {
+        assert!((a - b).abs() < 1e-4);
+    }
+    // a second texel type gets its own kernels, the f32 ones stay cached
+    let tex: Tex2d<Float2> = device.create_tex2d(PixelStorage::Float2, w, h, 1).unwrap();
+    tex.view(0).copy_from(&complex);
+    fft.forward_tex2d(&tex.view(0), &spectrum.view(..)).unwrap();
+    assert!(max_error(&spectrum.view(..).copy_to_vec(), &expected) < 1e-3);
+    fft.inverse_tex2d(&spectrum.view(..), &tex.view(0)).unwrap();
+    let texels: Vec<Float2> = tex.view(0).copy_to_vec();
+    assert!(max_error(&texels, &complex) < 1e-4);
+}
+#[test]
 fn bool_op() {
     init();
     let device = get_device();
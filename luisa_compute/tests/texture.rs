@@ -0,0 +1,557 @@
+use luisa_compute as luisa;
+use luisa::*;
+use rand::prelude::*;
+fn get_device() -> Device {
+    let device = match std::env::var("LUISA_TEST_DEVICE") {
+        Ok(device) => device,
+        Err(_) => "cpu".to_string(),
+    };
+    luisa::create_device(&device).unwrap()
+}
+// box filter over the footprint of dst texel `i` in a level of size `src`
+fn box_weights(i: u32, src: u32, dst: u32) -> Vec<(u32, f32)> {
+    let scale = src as f32 / dst as f32;
+    let lo = i as f32 * scale;
+    let hi = (i + 1) as f32 * scale;
+    (0..src)
+        .map(|j| (j, (hi.min(j as f32 + 1.0) - lo.max(j as f32)).max(0.0)))
+        .filter(|(_, w)| *w > 0.0)
+        .collect()
+}
+#[test]
+fn tex2d_mipmap_npot() {
+    init();
+    let device = get_device();
+    let (w, h) = (13u32, 7u32);
+    let tex: Tex2d<f32> = device
+        .create_tex2d(PixelStorage::Float1, w, h, 3)
+        .unwrap();
+    let mut rng = rand::thread_rng();
+    let level0 = (0..w * h).map(|_| rng.gen::<f32>()).collect::<Vec<_>>();
+    tex.view(0).copy_from(&level0);
+    tex.generate_mipmaps().unwrap();
+    let mut expected = level0;
+    let (mut sw, mut sh) = (w, h);
+    for level in 1..3 {
+        let (dw, dh) = ((sw / 2).max(1), (sh / 2).max(1));
+        let mut next = vec![0.0f32; (dw * dh) as usize];
+        for y in 0..dh {
+            for x in 0..dw {
+                let mut sum = 0.0;
+                let mut weight_sum = 0.0;
+                for (sy, wy) in box_weights(y, sh, dh) {
+                    for (sx, wx) in box_weights(x, sw, dw) {
+                        sum += expected[(sx + sy * sw) as usize] * wx * wy;
+                        weight_sum += wx * wy;
+                    }
+                }
+                next[(x + y * dw) as usize] = sum / weight_sum;
+            }
+        }
+        let mut got = vec![0.0f32; (dw * dh) as usize];
+        tex.view(level).copy_to(&mut got);
+        assert_eq!(tex.view(level).size(), [dw, dh, 1]);
+        for i in 0..got.len() {
+            assert!(
+                (got[i] - next[i]).abs() < 1e-4,
+                "level {} texel {}: {} != {}",
+                level,
+                i,
+                got[i],
+                next[i]
+            );
+        }
+        expected = next;
+        sw = dw;
+        sh = dh;
+    }
+}
+#[test]
+fn tex3d_mipmap_constant() {
+    init();
+    let device = get_device();
+    let tex: Tex3d<Float4> = device
+        .create_tex3d(PixelStorage::Byte4, 5, 4, 3, 2)
+        .unwrap();
+    tex.view(0).copy_from(&vec![[64u8, 128, 192, 255]; 5 * 4 * 3]);
+    tex.generate_mipmaps_with_filter(MipmapFilter::Kaiser {
+        width: 3.0,
+        alpha: 4.0,
+    })
+    .unwrap();
+    let mut got = vec![[0u8; 4]; 2 * 2];
+    tex.view(1).copy_to(&mut got);
+    for texel in got {
+        assert_eq!(texel, [64u8, 128, 192, 255]);
+    }
+}
+#[test]
+fn mipmap_kernel_reuse() {
+    init();
+    let device = get_device();
+    let kaiser = MipmapFilter::Kaiser {
+        width: 3.0,
+        alpha: 4.0,
+    };
+    for (w, h) in [(8u32, 8u32), (5, 3)] {
+        let tex: Tex2d<f32> = device
+            .create_tex2d(PixelStorage::Float1, w, h, 2)
+            .unwrap();
+        // every call after the first per filter reuses the kernel cached on `tex`
+        for (value, filter) in [
+            (0.25f32, MipmapFilter::Box),
+            (0.75, kaiser),
+            (0.5, MipmapFilter::Box),
+        ] {
+            tex.view(0).copy_from(&vec![value; (w * h) as usize]);
+            tex.generate_mipmaps_with_filter(filter).unwrap();
+            let mut got = vec![0.0f32; ((w / 2) * (h / 2)) as usize];
+            tex.view(1).copy_to(&mut got);
+            for texel in got {
+                assert!((texel - value).abs() < 1e-5);
+            }
+        }
+    }
+    let tex: Tex3d<f32> = device
+        .create_tex3d(PixelStorage::Float1, 4, 4, 4, 3)
+        .unwrap();
+    for value in [1.5f32, -2.0] {
+        tex.view(0).copy_from(&vec![value; 4 * 4 * 4]);
+        tex.generate_mipmaps().unwrap();
+        let mut got = [0.0f32; 1];
+        tex.view(2).copy_to(&mut got);
+        assert!((got[0] - value).abs() < 1e-6);
+    }
+}
+#[test]
+fn tex2d_region_copy_and_blit() {
+    init();
+    let device = get_device();
+    let (w, h) = (9u32, 6u32);
+    let tex: Tex2d<Float4> = device
+        .create_tex2d(PixelStorage::Byte4, w, h, 1)
+        .unwrap();
+    tex.view(0).copy_from(&vec![[0u8; 4]; (w * h) as usize]);
+    let mut rng = rand::thread_rng();
+    let region = (0..4 * 3).map(|_| rng.gen::<[u8; 4]>()).collect::<Vec<_>>();
+    tex.view(0)
+        .copy_region_from([2, 1, 0], [4, 3, 1], &region)
+        .unwrap();
+    let mut got = vec![[0u8; 4]; region.len()];
+    tex.view(0)
+        .copy_region_to([2, 1, 0], [4, 3, 1], &mut got)
+        .unwrap();
+    assert_eq!(got, region);
+    // the second copy reuses the blit kernel cached on `tex`
+    let mut corner = vec![[0u8; 4]; 4];
+    tex.view(0)
+        .copy_region_to([2, 1, 0], [2, 2, 1], &mut corner)
+        .unwrap();
+    assert_eq!(corner, [region[0], region[1], region[4], region[5]]);
+    let mut all = vec![[0u8; 4]; (w * h) as usize];
+    tex.view(0).copy_to(&mut all);
+    for y in 0..h {
+        for x in 0..w {
+            let inside = (2..6).contains(&x) && (1..4).contains(&y);
+            let expected = if inside {
+                region[((x - 2) + (y - 1) * 4) as usize]
+            } else {
+                [0u8; 4]
+            };
+            assert_eq!(all[(x + y * w) as usize], expected);
+        }
+    }
+    let float_tex: Tex2d<Float4> = device
+        .create_tex2d(PixelStorage::Float4, w, h, 1)
+        .unwrap();
+    tex.view(0).blit_to(float_tex.view(0)).unwrap();
+    let mut texels = vec![Float4::default(); (w * h) as usize];
+    float_tex.view(0).copy_to(&mut texels);
+    for (texel, expected) in texels.iter().zip(all.iter()) {
+        let texel = [texel.x, texel.y, texel.z, texel.w];
+        for c in 0..4 {
+            assert_eq!((texel[c] * 255.0).round() as u8, expected[c]);
+        }
+    }
+}
+#[test]
+fn tex1d_and_tex2d_array() {
+    init();
+    let device = get_device();
+    let tex1d: Tex1d<f32> = device.create_tex1d(PixelStorage::Float1, 8, 1).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let tex1d = tex1d.var();
+            let x = dispatch_id().x();
+            tex1d.write(x, x.float() * 2.0f32);
+        })
+        .unwrap();
+    kernel.dispatch([8, 1, 1]).unwrap();
+    let mut got = vec![0.0f32; 8];
+    tex1d.view(0).copy_to(&mut got);
+    for (x, v) in got.iter().enumerate() {
+        assert_eq!(*v, x as f32 * 2.0);
+    }
+    let array: Tex2dArray<Float4> = device
+        .create_tex2d_array(PixelStorage::Byte4, 4, 3, 3)
+        .unwrap();
+    let layers = (0..3u8)
+        .map(|l| vec![[l, l + 1, l + 2, 255]; 4 * 3])
+        .collect::<Vec<_>>();
+    for (l, data) in layers.iter().enumerate() {
+        array.copy_layer_from(l as u32, data).unwrap();
+    }
+    for (l, data) in layers.iter().enumerate() {
+        let mut got = vec![[0u8; 4]; 4 * 3];
+        array.copy_layer_to(l as u32, &mut got).unwrap();
+        assert_eq!(&got, data);
+    }
+}
+#[test]
+fn texcube_direction_lookup() {
+    init();
+    let device = get_device();
+    let cube: TexCube<f32> = device.create_texcube(PixelStorage::Float1, 4).unwrap();
+    for face in 0..6 {
+        cube.copy_face_from(face, &vec![face as f32; 4 * 4]).unwrap();
+    }
+    let dirs = [
+        Float3::new(1.0, 0.0, 0.0),
+        Float3::new(-1.0, 0.0, 0.0),
+        Float3::new(0.0, 1.0, 0.0),
+        Float3::new(0.0, -1.0, 0.0),
+        Float3::new(0.0, 0.0, 1.0),
+        Float3::new(0.0, 0.0, -1.0),
+        Float3::new(0.9, 0.1, -0.2),
+        Float3::new(-0.3, 0.2, -0.8),
+    ];
+    let expected = [0.0f32, 1.0, 2.0, 3.0, 4.0, 5.0, 0.0, 5.0];
+    let dir_buf = device.create_buffer_from_slice(&dirs).unwrap();
+    let out = device.create_buffer::<f32>(dirs.len()).unwrap();
+    let kernel = device
+        .create_kernel::<()>(&|| {
+            let i = dispatch_id().x();
+            let dir = dir_buf.var().read(i);
+            out.var().write(i, cube.var().read_dir(dir));
+        })
+        .unwrap();
+    kernel.dispatch([dirs.len() as u32, 1, 1]).unwrap();
+    assert_eq!(out.copy_to_vec(), expected);
+    // the edge length also reaches kernels taking the cube as an argument
+    let kernel = device
+        .create_kernel::<(TexCube<f32>,)>(&|cube| {
+            let i = dispatch_id().x();
+            let dir = dir_buf.var().read(i);
+            out.var().write(i, cube.read_dir(dir) + cube.size().float());
+        })
+        .unwrap();
+    kernel.dispatch([dirs.len() as u32, 1, 1], &cube).unwrap();
+    let shifted = expected.iter().map(|v| v + 4.0).collect::<Vec<_>>();
+    assert_eq!(out.copy_to_vec(), shifted);
+}
+#[test]
+fn tex1d_array_cube_as_kernel_args() {
+    init();
+    let device = get_device();
+    let tex1d: Tex1d<f32> = device.create_tex1d(PixelStorage::Float1, 8, 1).unwrap();
+    let kernel = device
+        .create_kernel::<(Tex1d<f32>,)>(&|tex| {
+            let x = dispatch_id().x();
+            tex.write(x, x.float() + 1.0f32);
+        })
+        .unwrap();
+    kernel.dispatch([8, 1, 1], &tex1d).unwrap();
+    let mut got = vec![0.0f32; 8];
+    tex1d.view(0).copy_to(&mut got);
+    assert_eq!(got, (1..=8).map(|x| x as f32).collect::<Vec<_>>());
+    kernel.dispatch([8, 1, 1], &tex1d.view(0)).unwrap();
+
+    let array: Tex2dArray<f32> = device
+        .create_tex2d_array(PixelStorage::Float1, 4, 3, 2)
+        .unwrap();
+    let kernel = device
+        .create_kernel::<(Tex2dArray<f32>,)>(&|tex| {
+            let p = dispatch_id();
+            let v = p.z() * 100u32 + p.y() * 4u32 + p.x();
+            tex.write(p.xy(), p.z(), v.float());
+        })
+        .unwrap();
+    kernel.dispatch([4, 3, 2], &array).unwrap();
+    kernel.dispatch([4, 3, 2], &array.view()).unwrap();
+    for layer in 0..2u32 {
+        let mut got = vec![0.0f32; 4 * 3];
+        array.copy_layer_to(layer, &mut got).unwrap();
+        let expected = (0..12u32)
+            .map(|i| (layer * 100 + i) as f32)
+            .collect::<Vec<_>>();
+        assert_eq!(got, expected);
+    }
+
+    let cube: TexCube<f32> = device.create_texcube(PixelStorage::Float1, 2).unwrap();
+    let kernel = device
+        .create_kernel::<(TexCube<f32>,)>(&|tex| {
+            let p = dispatch_id();
+            tex.write(p.xy(), p.z(), p.z().float());
+        })
+        .unwrap();
+    kernel.dispatch([2, 2, 6], &cube).unwrap();
+    for face in 0..6u32 {
+        let mut got = vec![0.0f32; 2 * 2];
+        cube.copy_face_to(face, &mut got).unwrap();
+        assert_eq!(got, vec![face as f32; 4]);
+    }
+}
+#[cfg(feature = "image")]
+#[test]
+fn tex2d_image_roundtrip() {
+    init();
+    let device = get_device();
+    let (w, h) = (17u32, 9u32);
+    let tex: Tex2d<Float4> = device
+        .create_tex2d(PixelStorage::Byte4, w, h, 1)
+        .unwrap();
+    let mut rng = rand::thread_rng();
+    let data = (0..w * h).map(|_| rng.gen::<[u8; 4]>()).collect::<Vec<_>>();
+    tex.view(0).copy_from(&data);
+    let path = std::env::temp_dir().join("luisa_tex2d_image_roundtrip.png");
+    tex.view(0).save_png(&path).unwrap();
+    let loaded: Tex2d<Float4> =
+        Tex2d::from_image_file(&device, &path, PixelStorage::Float4, 1).unwrap();
+    assert_eq!(loaded.view(0).size(), [w, h, 1]);
+    let mut texels = vec![Float4::default(); (w * h) as usize];
+    loaded.view(0).copy_to(&mut texels);
+    for (texel, expected) in texels.iter().zip(data.iter()) {
+        let texel = [texel.x, texel.y, texel.z, texel.w];
+        for c in 0..4 {
+            assert_eq!((texel[c] * 255.0).round() as u8, expected[c]);
+        }
+    }
+    let buffer = device.create_buffer_from_slice(&texels).unwrap();
+    let path = std::env::temp_dir().join("luisa_buffer_image_roundtrip.exr");
+    buffer.save_exr(&path, w, h).unwrap();
+    let (loaded, lw, lh) = Buffer::<Float4>::from_image_file(&device, &path).unwrap();
+    assert_eq!((lw, lh), (w, h));
+    let loaded = loaded.copy_to_vec();
+    for (a, b) in loaded.iter().zip(texels.iter()) {
+        assert_eq!([a.x, a.y, a.z, a.w], [b.x, b.y, b.z, b.w]);
+    }
+}
+#[test]
+fn sparse_grid_roundtrip() {
+    init();
+    let device = get_device();
+    let res = [20u32, 12, 9];
+    let mut grid = sparse_grid::SparseGrid::<f32>::new(&device, res, 1).unwrap();
+    grid.activate_region([0, 0, 0], [9, 4, 4]).unwrap();
+    assert_eq!(grid.active_blocks(), 2);
+    assert!(grid.is_active([15, 7, 7]));
+    assert!(!grid.is_active([16, 0, 0]));
+
+    let value = |x: u32, y: u32, z: u32| (x + 100 * y + 10000 * z) as f32;
+    let fill = device
+        .create_kernel::<(sparse_grid::SparseGrid<f32>,)>(&|grid| {
+            let p = dispatch_id();
+            grid.write(p, (p.x() + p.y() * 100u32 + p.z() * 10000u32).float());
+        })
+        .unwrap();
+    fill.dispatch(res, &grid).unwrap();
+    let samples = device.create_buffer::<f32>(4).unwrap();
+    let query = device
+        .create_kernel::<(sparse_grid::SparseGrid<f32>,)>(&|grid| {
+            let s = samples.var();
+            s.write(0u32, grid.sample(make_float3(1.5f32, 2.5f32, 3.5f32)));
+            s.write(1u32, grid.sample(make_float3(2.0f32, 2.5f32, 3.5f32)));
+            s.write(2u32, grid.read(make_uint3(17u32, 0u32, 0u32)));
+            let active = grid.is_active(make_uint3(3u32, 3u32, 3u32));
+            s.write(3u32, select(active, const_(1.0f32), const_(0.0f32)));
+        })
+        .unwrap();
+    query.dispatch([1, 1, 1], &grid).unwrap();
+    let samples = samples.view(..).copy_to_vec();
+    assert_eq!(samples[0], value(1, 2, 3));
+    assert_eq!(samples[1], (value(1, 2, 3) + value(2, 2, 3)) * 0.5);
+    assert_eq!(samples[2], 0.0);
+    assert_eq!(samples[3], 1.0);
+
+    let tex: Tex3d<f32> = device
+        .create_tex3d(PixelStorage::Float1, res[0], res[1], res[2], 1)
+        .unwrap();
+    grid.to_tex3d(&tex.view(0)).unwrap();
+    let mut dense = vec![0.0f32; (res[0] * res[1] * res[2]) as usize];
+    tex.view(0).copy_to(&mut dense);
+    for z in 0..res[2] {
+        for y in 0..res[1] {
+            for x in 0..res[0] {
+                let expected = if grid.is_active([x, y, z]) {
+                    value(x, y, z)
+                } else {
+                    0.0
+                };
+                assert_eq!(dense[((z * res[1] + y) * res[0] + x) as usize], expected);
+            }
+        }
+    }
+
+    let rebuilt = sparse_grid::SparseGrid::from_tex3d(&device, &tex.view(0)).unwrap();
+    assert_eq!(rebuilt.active_blocks(), 2);
+    let tex2: Tex3d<f32> = device
+        .create_tex3d(PixelStorage::Float1, res[0], res[1], res[2], 1)
+        .unwrap();
+    rebuilt.to_tex3d(&tex2.view(0)).unwrap();
+    let mut dense2 = vec![0.0f32; dense.len()];
+    tex2.view(0).copy_to(&mut dense2);
+    assert_eq!(dense, dense2);
+}
+#[test]
+fn imaging_blur_and_resize() {
+    init();
+    let device = get_device();
+    let imaging = imaging::Imaging::<f32>::new(&device).unwrap();
+    let (w, h) = (17u32, 11u32);
+    let mut rng = StdRng::seed_from_u64(0);
+    let pixels = (0..w * h).map(|_| rng.gen::<f32>()).collect::<Vec<_>>();
+    let src: Tex2d<f32> = device.create_tex2d(PixelStorage::Float1, w, h, 1).unwrap();
+    let dst: Tex2d<f32> = device.create_tex2d(PixelStorage::Float1, w, h, 1).unwrap();
+    src.view(0).copy_from(&pixels);
+
+    let sigma = 1.5;
+    imaging
+        .gaussian_blur(&src.view(0), &dst.view(0), sigma)
+        .unwrap();
+    let taps = imaging::gaussian_kernel(sigma);
+    assert!((taps.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    let r = (taps.len() / 2) as i32;
+    let at = |x: i32, y: i32| {
+        let x = x.clamp(0, w as i32 - 1) as u32;
+        let y = y.clamp(0, h as i32 - 1) as u32;
+        pixels[(y * w + x) as usize]
+    };
+    let mut blurred = vec![0.0f32; pixels.len()];
+    dst.view(0).copy_to(&mut blurred);
+    for y in 0..h as i32 {
+        for x in 0..w as i32 {
+            let mut expected = 0.0;
+            for (j, wy) in taps.iter().enumerate() {
+                for (i, wx) in taps.iter().enumerate() {
+                    expected += wx * wy * at(x + i as i32 - r, y + j as i32 - r);
+                }
+            }
+            let got = blurred[(y as u32 * w + x as u32) as usize];
+            assert!((got - expected).abs() < 1e-4, "{} {}", got, expected);
+        }
+    }
+
+    let (dw, dh) = (w / 2, h / 2);
+    let small: Tex2d<f32> = device
+        .create_tex2d(PixelStorage::Float1, dw, dh, 1)
+        .unwrap();
+    let (sw, sh) = (2 * dw, 2 * dh);
+    let even: Tex2d<f32> = device
+        .create_tex2d(PixelStorage::Float1, sw, sh, 1)
+        .unwrap();
+    let even_pixels = (0..sw * sh).map(|_| rng.gen::<f32>()).collect::<Vec<_>>();
+    even.view(0).copy_from(&even_pixels);
+    imaging
+        .resize(&even.view(0), &small.view(0), imaging::ResizeFilter::Box)
+        .unwrap();
+    let mut halved = vec![0.0f32; (dw * dh) as usize];
+    small.view(0).copy_to(&mut halved);
+    for y in 0..dh {
+        for x in 0..dw {
+            let mut expected = 0.0;
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                expected += even_pixels[((2 * y + dy) * sw + 2 * x + dx) as usize] * 0.25;
+            }
+            let got = halved[(y * dw + x) as usize];
+            assert!((got - expected).abs() < 1e-5, "{} {}", got, expected);
+        }
+    }
+
+    src.view(0).copy_from(&vec![0.25f32; pixels.len()]);
+    for filter in [
+        imaging::ResizeFilter::Triangle,
+        imaging::ResizeFilter::Mitchell,
+        imaging::ResizeFilter::Lanczos3,
+    ] {
+        for (rw, rh) in [(dw, dh), (2 * w + 1, 3 * h)] {
+            let resized: Tex2d<f32> = device
+                .create_tex2d(PixelStorage::Float1, rw, rh, 1)
+                .unwrap();
+            imaging
+                .resize(&src.view(0), &resized.view(0), filter)
+                .unwrap();
+            let mut out = vec![0.0f32; (rw * rh) as usize];
+            resized.view(0).copy_to(&mut out);
+            for v in out {
+                assert!((v - 0.25).abs() < 1e-5, "{:?} {}", filter, v);
+            }
+        }
+    }
+}
+#[test]
+fn imaging_tone_map_and_srgb() {
+    init();
+    let device = get_device();
+    let imaging = imaging::Imaging::<Float4>::new(&device).unwrap();
+    let (w, h) = (8u32, 4u32);
+    let mut rng = StdRng::seed_from_u64(0);
+    let hdr = (0..w * h)
+        .map(|_| {
+            Float4::new(
+                rng.gen::<f32>() * 8.0,
+                rng.gen::<f32>() * 8.0,
+                rng.gen::<f32>() * 8.0,
+                rng.gen::<f32>(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let src: Tex2d<Float4> = device.create_tex2d(PixelStorage::Float4, w, h, 1).unwrap();
+    let dst: Tex2d<Float4> = device.create_tex2d(PixelStorage::Float4, w, h, 1).unwrap();
+    src.view(0).copy_from(&hdr);
+    let mut out = vec![Float4::default(); hdr.len()];
+    let exposure = 0.5;
+    let reinhard = |x: f32| x / (1.0 + x);
+    let aces = |x: f32| ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0);
+    for (op, f) in [
+        (
+            imaging::ToneMapping::Reinhard,
+            &reinhard as &dyn Fn(f32) -> f32,
+        ),
+        (imaging::ToneMapping::Aces, &aces),
+    ] {
+        imaging
+            .tone_map(&src.view(0), &dst.view(0), op, exposure)
+            .unwrap();
+        dst.view(0).copy_to(&mut out);
+        for (got, input) in out.iter().zip(&hdr) {
+            for (g, i) in [(got.x, input.x), (got.y, input.y), (got.z, input.z)] {
+                assert!((g - f(i * exposure)).abs() < 1e-5, "{:?} {} {}", op, g, i);
+            }
+            assert_eq!(got.w, input.w);
+        }
+    }
+
+    let linear = (0..w * h)
+        .map(|i| {
+            let x = i as f32 / (w * h - 1) as f32;
+            Float4::new(x, x * x, 0.001 * x, 0.5)
+        })
+        .collect::<Vec<_>>();
+    src.view(0).copy_from(&linear);
+    imaging.srgb_encode(&src.view(0), &dst.view(0)).unwrap();
+    imaging.srgb_decode(&dst.view(0), &src.view(0)).unwrap();
+    src.view(0).copy_to(&mut out);
+    for (got, expected) in out.iter().zip(&linear) {
+        assert!((got.x - expected.x).abs() < 1e-5);
+        assert!((got.y - expected.y).abs() < 1e-5);
+        assert!((got.z - expected.z).abs() < 1e-5);
+        assert_eq!(got.w, expected.w);
+    }
+    src.view(0)
+        .copy_from(&vec![Float4::new(0.5, 0.0, 1.0, 1.0); (w * h) as usize]);
+    imaging.srgb_encode(&src.view(0), &dst.view(0)).unwrap();
+    dst.view(0).copy_to(&mut out);
+    assert!((out[0].x - 0.7354).abs() < 1e-3);
+    assert_eq!(out[0].y, 0.0);
+    assert!((out[0].z - 1.0).abs() < 1e-5);
+}
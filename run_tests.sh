@@ -1,6 +1,6 @@
 cargo run --release --example atomic
 cargo run --release --example autodiff
-cargo run --release --example bindless
+cargo run --release --features luisa_compute/image --example bindless
 cargo run --release --example custom_aggreagate
 cargo run --release --example custom_op
 cargo run --release --example polymorphism